@@ -1,24 +1,63 @@
 use error::CommandError;
+use crate::prompt::{self, PromptContext};
+use parser::Connector;
+use set::ShellOption;
+use shopt::ShoptOption;
+use state::STATE;
+use variables::Attribute;
 
-mod cd;
+pub(crate) mod arithmetic;
+pub(crate) mod case;
+pub(crate) mod cd;
+pub(crate) mod coproc;
+pub(crate) mod declare;
+pub(crate) mod dirstack;
 pub(crate) mod echo;
 pub(crate) mod error;
+pub(crate) mod exec;
 pub(crate) mod exit;
+pub(crate) mod expand;
 pub(crate) mod external;
+pub(crate) mod glob;
+pub(crate) mod jobs;
+pub(crate) mod kill;
+pub(crate) mod parser;
+pub(crate) mod pattern;
+pub(crate) mod printf;
+pub(crate) mod procsub;
 pub(crate) mod pwd;
+pub(crate) mod read;
+pub(crate) mod set;
+pub(crate) mod shopt;
+pub(crate) mod signals;
+pub(crate) mod special;
+pub(crate) mod state;
+pub(crate) mod test;
+pub(crate) mod trap;
 pub(crate) mod typee;
+pub(crate) mod unset;
 pub(crate) mod utils; // extracted shared utilities
+pub(crate) mod variables;
+pub(crate) mod z;
 
-// Represents whether to overwrite (>) or append (>>)
-#[derive(Debug)]
+// Names handled by the shell itself rather than looked up in PATH.
+pub const BUILTINS: &[&str] = &["echo", "exit", "type", "pwd", "cd", "pushd", "popd", "dirs", "z", "zi", "trap", "set", "shopt", "test", "[", "printf", "read", "declare", "typeset", "export", "unset", "wait", "kill", "disown", "suspend", "exec"];
+
+// Represents whether to overwrite (>), overwrite even with noclobber (>|), append (>>), read (<)
+// or duplicate a file descriptor (>&N, <&N), whose number is then the redirection's file.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RedirectionMode {
     Overwrite,
+    Clobber,
     Append,
+    Read,
+    Duplicate,
 }
 
-// Represents the output stream to be redirected
-#[derive(Debug, PartialEq)]
+// Represents the stream to be redirected
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RedirectionTarget {
+    Stdin,
     Stdout,
     Stderr,
 }
@@ -35,18 +74,117 @@ pub struct Redirection {
 pub enum Command {
     Noop,
     Exit(i32),
-    Cd(String),
+    Assign(Vec<variables::Assignment>),
+    // A command run with `name=value` assignments in its environment.
+    Prefixed {
+        assignments: Vec<variables::Assignment>,
+        command: Box<Command>,
+    },
+    Cd {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
     Echo {
-        message: String,
+        args: Vec<String>,
         redirections: Vec<Redirection>,
     },
     Type {
-        cmd: String,
+        args: Vec<String>,
         redirections: Vec<Redirection>,
     },
     Pwd {
+        physical: bool,
+        redirections: Vec<Redirection>,
+    },
+    Pushd {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Popd {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Dirs {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Z {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Zi {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Trap {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Set {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Shopt {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Printf {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Read {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Declare {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Export {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Unset {
+        args: Vec<String>,
         redirections: Vec<Redirection>,
     },
+    Wait {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Kill {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Disown {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Suspend {
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Test {
+        // Invoked as `[`, which needs a closing `]`.
+        bracket: bool,
+        args: Vec<String>,
+        redirections: Vec<Redirection>,
+    },
+    Case {
+        word: String,
+        arms: Vec<case::CaseArm>,
+    },
+    Conditional {
+        tokens: Vec<String>,
+    },
+    Exec {
+        args: Vec<String>,
+        redirections: Vec<exec::FdRedirection>,
+    },
+    Coproc {
+        name: String,
+        body: String,
+    },
     External {
         cmd: String,
         args: Vec<String>,
@@ -55,83 +193,313 @@ pub enum Command {
     },
 }
 
+// Prints a command about to run, for `set -x`.
+fn trace(words: &[String]) {
+    let ps4 = variables::get("PS4").unwrap_or_else(|| "+ ".to_string());
+    let ctx = PromptContext {
+        status: STATE.read().unwrap().last_status,
+        duration: None,
+        history_number: 0,
+        command_number: 0,
+        jobs: jobs::count(),
+        git: None,
+    };
+    let words: Vec<String> = words
+        .iter()
+        .map(|word| match word.split_once(variables::COMPOUND_MARK) {
+            Some((target, inner)) => format!("{}({})", target, inner),
+            None => utils::quote_if_needed(word),
+        })
+        .collect();
+    eprintln!("{}{}", prompt::expand(&ps4, &ctx).plain, words.join(" "));
+}
+
 impl Command {
-    pub fn execute(&self) {
+    // Runs the command and returns its exit status.
+    pub fn execute(&self) -> i32 {
         use Command::*;
         match self {
-            Noop => (),
+            Noop => 0,
             Exit(code) => exit::exit_cmd(*code),
-            Cd(path) => cd::cd_cmd(path),
-
-            Echo {
-                message,
-                redirections,
-            } => {
-                let mut stdout_writer = utils::get_output_writer(redirections, RedirectionTarget::Stdout);
-                // Eagerly get stderr writer to create/truncate the file, even if unused.
-                let _stderr_writer = utils::get_output_writer(redirections, RedirectionTarget::Stderr);
-                echo::echo_cmd(message, &mut stdout_writer);
-            }
-            Type { cmd, redirections } => {
-                let mut stdout_writer = utils::get_output_writer(redirections, RedirectionTarget::Stdout);
-                let mut stderr_writer = utils::get_output_writer(redirections, RedirectionTarget::Stderr);
-                typee::type_cmd(cmd, &mut stdout_writer, &mut stderr_writer);
-            }
-            Pwd { redirections } => {
-                let mut stdout_writer = utils::get_output_writer(redirections, RedirectionTarget::Stdout);
-                let mut stderr_writer = utils::get_output_writer(redirections, RedirectionTarget::Stderr);
-                pwd::pwd_cmd(&mut stdout_writer, &mut stderr_writer);
+            Assign(assignments) => {
+                let mut status = 0;
+                for assignment in assignments {
+                    if let Err(err) = assignment.apply() {
+                        eprintln!("{}", err);
+                        status = 1;
+                    }
+                }
+                status
+            }
+            Prefixed { assignments, command } => {
+                // Only plain variables can go in the environment of a command,
+                // and they are exported to it whatever their attributes.
+                let assignments: Vec<&variables::Assignment> = assignments.iter().filter(|a| a.is_scalar()).collect();
+                let saved: Vec<variables::Saved> = assignments.iter().map(|a| variables::save(&a.name)).collect();
+                for assignment in assignments {
+                    if assignment.apply().is_ok() {
+                        variables::set_attribute(&variables::resolve(&assignment.name), Attribute::Export, true);
+                    }
+                }
+                let status = command.execute();
+                for saved in saved {
+                    variables::restore(saved);
+                }
+                status
+            }
+            Cd { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                cd::cd_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+
+            Echo { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                echo::echo_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Type { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                typee::type_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Pwd { physical, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                pwd::pwd_cmd(*physical, &mut stdout_writer, &mut stderr_writer)
+            }
+            Pushd { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                dirstack::pushd_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Popd { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                dirstack::popd_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Dirs { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                dirstack::dirs_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Z { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                z::z_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Zi { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                z::zi_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Trap { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                trap::trap_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Set { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                set::set_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Shopt { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                shopt::shopt_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Printf { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                printf::printf_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Read { args, redirections } => {
+                let Some((_stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                read::read_cmd(args, redirections, &mut stderr_writer)
+            }
+            Declare { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                declare::declare_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Export { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                declare::export_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Unset { args, redirections } => {
+                let Some((_stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                unset::unset_cmd(args, &mut stderr_writer)
+            }
+            Wait { args, redirections } => {
+                let Some((_stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                jobs::wait_cmd(args, &mut stderr_writer)
+            }
+            Kill { args, redirections } => {
+                let Some((mut stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                kill::kill_cmd(args, &mut stdout_writer, &mut stderr_writer)
+            }
+            Disown { args, redirections } => {
+                let Some((_stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                jobs::disown_cmd(args, &mut stderr_writer)
             }
+            Suspend { args, redirections } => {
+                let Some((_stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                jobs::suspend_cmd(args, &mut stderr_writer)
+            }
+            Test { bracket, args, redirections } => {
+                let Some((_stdout_writer, mut stderr_writer)) = utils::get_writers(redirections) else {
+                    return 1;
+                };
+                test::test_cmd(*bracket, args, &mut stderr_writer)
+            }
+            Case { word, arms } => case::case_cmd(word, arms),
+            Conditional { tokens } => test::conditional_cmd(tokens),
+            Exec { args, redirections } => exec::exec_cmd(args, redirections),
+            Coproc { name, body } => coproc::coproc_cmd(name, body),
             External { .. } => external::external_cmd(self),
         }
     }
 
-    pub fn from(input: &str) -> Result<Command, CommandError> {
-        use Command::*;
-        let input = input.trim();
-        let mut input_tokens = utils::split_args(input);
-
-        // Use Options to ensure the last redirection for each stream is the one that's kept.
-        let mut stdout_redir = None;
-        let mut stderr_redir = None;
-
-        let mut i = 0;
-        while i < input_tokens.len() {
-            let (target, mode) = match input_tokens[i].as_str() {
-                ">" => (RedirectionTarget::Stdout, RedirectionMode::Overwrite),
-                "1>" => (RedirectionTarget::Stdout, RedirectionMode::Overwrite),
-                ">>" => (RedirectionTarget::Stdout, RedirectionMode::Append),
-                "1>>" => (RedirectionTarget::Stdout, RedirectionMode::Append),
-                "2>" => (RedirectionTarget::Stderr, RedirectionMode::Overwrite),
-                "2>>" => (RedirectionTarget::Stderr, RedirectionMode::Append),
-                _ => {
-                    i += 1;
-                    continue;
+    // Parses and runs a single command, reporting parse errors on stderr.
+    fn run_simple(input: &str) -> i32 {
+        if case::is_case(input) || test::is_conditional(input) || coproc::is_coproc(input) || exec::is_exec(input) {
+            return match Command::from(input) {
+                Ok(cmd) => cmd.execute(),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    e.status()
                 }
             };
-
-            if i + 1 >= input_tokens.len() {
-                return Err(CommandError::InvalidArguments(
-                    "Missing file for redirection".to_string(),
-                ));
+        }
+        let parsed = utils::split_redirections(input.trim()).and_then(|(text, redirections)| {
+            let words = utils::split_args(&text)?;
+            if set::enabled(ShellOption::Xtrace) && !words.is_empty() {
+                trace(&words);
+            }
+            Command::from_words(words, redirections)
+        });
+        match parsed {
+            Ok(cmd) => cmd.execute(),
+            Err(e) => {
+                eprintln!("{}", e);
+                if e.is_fatal() && !STATE.read().unwrap().interactive {
+                    exit::exit_cmd(e.exit_status());
+                }
+                e.status()
             }
-            let file = input_tokens.remove(i + 1);
-            input_tokens.remove(i);
+        }
+    }
 
-            let redir = Redirection { target, file, mode };
-            match redir.target {
-                RedirectionTarget::Stdout => stdout_redir = Some(redir),
-                RedirectionTarget::Stderr => stderr_redir = Some(redir),
+    // Runs a line of input: commands joined by `;`, `&&`, `||` or newlines.
+    // Returns the status of the last command that ran.
+    pub fn run(input: &str) -> i32 {
+        // Input given with `-c`, to a substitution or to a trap is checked here.
+        if let Err(message) = parser::pending(input) {
+            eprintln!("{}", message);
+            return 2;
+        }
+        let list = parser::split_list(input);
+        let mut status = STATE.read().unwrap().last_status;
+        // `$LINENO` counts from the line the input starts on.
+        let first_line = special::line_number();
+        for (i, (command, connector, line)) in list.iter().enumerate() {
+            let skip = match connector {
+                Connector::Sequence => false,
+                Connector::And => status != 0,
+                Connector::Or => status == 0,
+            };
+            if skip {
+                continue;
+            }
+            // `-n` reads commands without running them, except in an interactive shell.
+            if set::enabled(ShellOption::Noexec) && !STATE.read().unwrap().interactive {
+                continue;
+            }
+            let (negated, command) = match command.trim_start().strip_prefix('!') {
+                Some(rest) if rest.starts_with(char::is_whitespace) => (true, rest),
+                _ => (false, command.as_str()),
+            };
+            special::set_line_number(first_line + line);
+            if !trap::in_trap() {
+                special::set_current_command(command);
+            }
+            trap::run_debug_trap();
+            status = Command::run_simple(command);
+            procsub::finish();
+            special::set_pipe_status(status);
+            if negated {
+                status = i32::from(status == 0);
             }
+            STATE.write().unwrap().last_status = status;
+            // A failure that a following `&&` or `||` tests for, or that `!` inverts, is not an error.
+            let tested = negated || list.get(i + 1).is_some_and(|(_, next, _)| *next != Connector::Sequence);
+            if status != 0 && !tested {
+                trap::run_err_trap();
+                if set::enabled(ShellOption::Errexit) {
+                    exit::exit_cmd(status);
+                }
+            }
+            trap::run_pending();
         }
+        status
+    }
 
-        let mut redirections = Vec::new();
-        if let Some(r) = stdout_redir {
-            redirections.push(r);
+    pub fn from(input: &str) -> Result<Command, CommandError> {
+        if case::is_case(input) {
+            return case::parse_case(input);
         }
-        if let Some(r) = stderr_redir {
-            redirections.push(r);
+        if test::is_conditional(input) {
+            return test::parse_conditional(input);
         }
+        if coproc::is_coproc(input) {
+            return coproc::parse_coproc(input);
+        }
+        if exec::is_exec(input) {
+            return exec::parse_exec(input);
+        }
+        let (text, redirections) = utils::split_redirections(input.trim())?;
+        Command::from_words(utils::split_args(&text)?, redirections)
+    }
+
+    // Builds a command from its expanded words and its redirections.
+    fn from_words(mut input_tokens: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+        use Command::*;
+
+        // A command made only of `name=value` words sets variables.
+        let assignments: Vec<variables::Assignment> = input_tokens.iter().map_while(|word| variables::Assignment::parse(word)).collect();
+        if !assignments.is_empty() && assignments.len() == input_tokens.len() {
+            return Ok(Assign(assignments));
+        }
+        // Before a command they set variables for that command only.
+        input_tokens.drain(..assignments.len());
 
         let cmd = input_tokens.first().map(|s| s.as_str()).unwrap_or("");
         let args_tokens: Vec<String> = if input_tokens.len() > 1 {
@@ -145,17 +513,48 @@ impl Command {
             args_tokens.join(" ")
         };
 
-        Ok(match cmd {
+        let command = match cmd {
+            // `name=(...)` is only allowed as an assignment.
+            _ if !matches!(cmd, "declare" | "typeset" | "export") && input_tokens.iter().any(|word| word.contains(variables::COMPOUND_MARK)) => {
+                return Err(CommandError::Syntax("(".to_string()));
+            }
             "" => Noop,
-            "echo" => echo::parse_echo_cmd(&args_for_builtins, redirections)?,
+            "echo" => echo::parse_echo_cmd(args_tokens, redirections)?,
             "exit" => exit::parse_exit_cmd(&args_for_builtins)?,
-            "type" => typee::parse_type_cmd(&args_for_builtins, redirections)?,
-            "pwd" => pwd::parse_pwd_cmd(&args_for_builtins, redirections)?,
-            "cd" => cd::parse_cd_cmd(&args_for_builtins)?,
+            "type" => typee::parse_type_cmd(args_tokens, redirections)?,
+            "pwd" => pwd::parse_pwd_cmd(&args_tokens, redirections)?,
+            "cd" => cd::parse_cd_cmd(args_tokens, redirections)?,
+            "pushd" => dirstack::parse_pushd_cmd(args_tokens, redirections)?,
+            "popd" => dirstack::parse_popd_cmd(args_tokens, redirections)?,
+            "dirs" => dirstack::parse_dirs_cmd(args_tokens, redirections)?,
+            "z" => z::parse_z_cmd(args_tokens, redirections)?,
+            "zi" => z::parse_zi_cmd(args_tokens, redirections)?,
+            "trap" => trap::parse_trap_cmd(args_tokens, redirections)?,
+            "set" => set::parse_set_cmd(args_tokens, redirections)?,
+            "shopt" => shopt::parse_shopt_cmd(args_tokens, redirections)?,
+            "printf" => printf::parse_printf_cmd(args_tokens, redirections)?,
+            "read" => read::parse_read_cmd(args_tokens, redirections)?,
+            "declare" | "typeset" => declare::parse_declare_cmd(args_tokens, redirections)?,
+            "export" => declare::parse_export_cmd(args_tokens, redirections)?,
+            "unset" => unset::parse_unset_cmd(args_tokens, redirections)?,
+            "wait" => jobs::parse_wait_cmd(args_tokens, redirections)?,
+            "kill" => kill::parse_kill_cmd(args_tokens, redirections)?,
+            "disown" => jobs::parse_disown_cmd(args_tokens, redirections)?,
+            "suspend" => jobs::parse_suspend_cmd(args_tokens, redirections)?,
+            "test" | "[" => test::parse_test_cmd(cmd, args_tokens, redirections)?,
+            // With autocd, a directory name on its own that is not a command changes to it.
+            _ if args_tokens.is_empty()
+                && shopt::enabled(ShoptOption::Autocd)
+                && std::path::Path::new(cmd).is_dir()
+                && external::find_in_path(cmd).is_none() =>
+            {
+                cd::parse_cd_cmd(vec![cmd.to_string()], redirections)?
+            }
             _ => match external::parse_external_cmd(cmd, args_tokens, redirections) {
                 Some(cmd) => cmd,
                 None => return Err(CommandError::NotFound(cmd.to_string())),
             },
-        })
+        };
+        Ok(if assignments.is_empty() { command } else { Prefixed { assignments, command: Box::new(command) } })
     }
 }
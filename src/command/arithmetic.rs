@@ -0,0 +1,507 @@
+// Shell arithmetic, as for the values of `declare -i` variables: the C integer
+// operators on 64-bit integers that wrap on overflow. A name stands for the
+// value of that variable, itself evaluated as an expression. Assignments and
+// `++`/`--` store through the variables module, so attributes still apply.
+use std::thread;
+use super::{variables, CommandError};
+
+// Longest first, so that `**` is not read as two `*`.
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=", "/=", "%=", "&=", "^=",
+    "|=", "+", "-", "*", "/", "%", "<", ">", "&", "|", "^", "!", "~", "?", ":", "=", ",", "(", ")",
+];
+
+const ASSIGNMENTS: &[&str] = &["=", "+=", "-=", "*=", "/=", "%=", "<<=", ">>=", "&=", "^=", "|="];
+
+// Binary operators from the loosest to the tightest binding; `**` binds tighter still.
+const LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", ">", "<=", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+// How deeply variables may refer to other expressions.
+const MAX_DEPTH: usize = 1024;
+// Each level nests the whole parser, so every so many levels the evaluation
+// continues on a thread with a fresh stack of this size.
+const LEVELS_PER_STACK: usize = 64;
+const STACK_SIZE: usize = 8 << 20;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    Name(String),
+    Operator(&'static str),
+    Invalid,
+    End,
+}
+
+fn tokenize(expr: &str) -> Vec<(Token, usize)> {
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+    while !rest.is_empty() {
+        let start = expr.len() - rest.len();
+        let word_len = |rest: &str, extra: &str| {
+            rest.find(|c: char| !(c.is_ascii_alphanumeric() || extra.contains(c))).unwrap_or(rest.len())
+        };
+        let (token, len) = if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            let len = word_len(rest, "#@_");
+            (Token::Number(rest[..len].to_string()), len)
+        } else if rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            let mut len = word_len(rest, "_");
+            // An array element, as in `a[1]`.
+            if rest[len..].starts_with('[') {
+                if let Some(close) = rest[len..].find(']') {
+                    len += close + 1;
+                }
+            }
+            (Token::Name(rest[..len].to_string()), len)
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            // `++` and `--` only follow or precede a name; otherwise they are two signs.
+            let follows_name = matches!(tokens.last(), Some((Token::Name(_), _)));
+            let precedes_name = rest[op.len()..].trim_start().starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+            match *op {
+                "++" | "--" if !follows_name && !precedes_name => (Token::Operator(&op[1..]), 1),
+                _ => (Token::Operator(op), op.len()),
+            }
+        } else {
+            (Token::Invalid, rest.chars().next().map_or(1, char::len_utf8))
+        };
+        tokens.push((token, start));
+        rest = rest[len..].trim_start();
+    }
+    tokens.push((Token::End, expr.len()));
+    tokens
+}
+
+// The value of a numeric constant: decimal, octal with a leading `0`,
+// hexadecimal with `0x`, or `base#digits` for bases 2 to 64.
+fn number(text: &str) -> Option<i64> {
+    let (base, digits) = if let Some((base, digits)) = text.split_once('#') {
+        (base.parse::<i64>().ok().filter(|base| (2..=64).contains(base))?, digits)
+    } else if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        (16, hex)
+    } else if text.len() > 1 && text.starts_with('0') {
+        (8, &text[1..])
+    } else {
+        (10, text)
+    };
+    digits.chars().try_fold(0i64, |value, c| {
+        let digit = match c {
+            '0'..='9' => c as i64 - '0' as i64,
+            'a'..='z' => c as i64 - 'a' as i64 + 10,
+            'A'..='Z' if base <= 36 => c as i64 - 'A' as i64 + 10,
+            'A'..='Z' => c as i64 - 'A' as i64 + 36,
+            '@' => 62,
+            _ => 63,
+        };
+        (digit < base).then(|| value.wrapping_mul(base).wrapping_add(digit))
+    })
+}
+
+fn power(mut base: i64, mut exponent: i64) -> i64 {
+    let mut result: i64 = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exponent >>= 1;
+    }
+    result
+}
+
+struct Evaluator<'a> {
+    expr: &'a str,
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    // Set while parsing an operand that `&&`, `||` or `?:` does not evaluate.
+    skip: usize,
+    depth: usize,
+}
+
+impl Evaluator<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Token::Operator(found) if *found == op) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    // Errors name the text from the offending token on; at the end of the
+    // expression, that is the last token.
+    fn error(&self, message: &str) -> CommandError {
+        self.error_at(self.pos, message)
+    }
+
+    fn error_at(&self, pos: usize, message: &str) -> CommandError {
+        let pos = if self.tokens[pos].0 == Token::End { pos.saturating_sub(1) } else { pos };
+        let start = self.tokens[pos].1.min(self.expr.len());
+        CommandError::NullParameter(
+            self.expr.to_string(),
+            format!("{} (error token is \"{}\")", message, self.expr[start..].trim_end()),
+        )
+    }
+
+    fn comma(&mut self) -> Result<i64, CommandError> {
+        let mut value = self.assignment()?;
+        while self.eat(",") {
+            value = self.assignment()?;
+        }
+        Ok(value)
+    }
+
+    fn assignment_operator(&self, pos: usize) -> Option<&'static str> {
+        match self.tokens.get(pos) {
+            Some((Token::Operator(op), _)) if ASSIGNMENTS.contains(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    // Assignments bind loosest but for `,`, and from the right.
+    fn assignment(&mut self) -> Result<i64, CommandError> {
+        if let (Token::Name(name), Some(op)) = (self.peek().clone(), self.assignment_operator(self.pos + 1)) {
+            self.pos += 2;
+            let start = self.pos;
+            let right = self.assignment()?;
+            if self.skip > 0 {
+                return Ok(0);
+            }
+            let value = match op {
+                "=" => right,
+                _ => {
+                    let left = self.value(&name)?;
+                    self.operate(&op[..op.len() - 1], left, right, start)?
+                }
+            };
+            self.store(&name, value)?;
+            return Ok(value);
+        }
+        let value = self.conditional()?;
+        if self.assignment_operator(self.pos).is_some() {
+            return Err(self.error("attempted assignment to non-variable"));
+        }
+        Ok(value)
+    }
+
+    fn conditional(&mut self) -> Result<i64, CommandError> {
+        let condition = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let then = self.skipping(condition == 0, |this| this.comma())?;
+        if !self.eat(":") {
+            return Err(self.error("`:' expected for conditional expression"));
+        }
+        let otherwise = self.skipping(condition != 0, |this| this.conditional())?;
+        Ok(if condition != 0 { then } else { otherwise })
+    }
+
+    fn skipping(&mut self, skip: bool, parse: impl FnOnce(&mut Self) -> Result<i64, CommandError>) -> Result<i64, CommandError> {
+        self.skip += usize::from(skip);
+        let value = parse(self);
+        self.skip -= usize::from(skip);
+        value
+    }
+
+    // Parses operators from `LEVELS[min..]` by precedence climbing, which keeps
+    // the recursion shallow for values that are expressions themselves.
+    fn binary(&mut self, min: usize) -> Result<i64, CommandError> {
+        let mut left = self.exponent()?;
+        loop {
+            let Token::Operator(op) = *self.peek() else {
+                return Ok(left);
+            };
+            let Some(level) = LEVELS.iter().position(|operators| operators.contains(&op)).filter(|&level| level >= min) else {
+                return Ok(left);
+            };
+            self.pos += 1;
+            let start = self.pos;
+            let right = match op {
+                "&&" => self.skipping(left == 0, |this| this.binary(level + 1))?,
+                "||" => self.skipping(left != 0, |this| this.binary(level + 1))?,
+                _ => self.binary(level + 1)?,
+            };
+            left = self.operate(op, left, right, start)?;
+        }
+    }
+
+    // Applies a binary operator; `pos` is where its right operand starts.
+    fn operate(&self, op: &str, left: i64, right: i64, pos: usize) -> Result<i64, CommandError> {
+        Ok(match op {
+            "||" => i64::from(left != 0 || right != 0),
+            "&&" => i64::from(left != 0 && right != 0),
+            "|" => left | right,
+            "^" => left ^ right,
+            "&" => left & right,
+            "==" => i64::from(left == right),
+            "!=" => i64::from(left != right),
+            "<" => i64::from(left < right),
+            ">" => i64::from(left > right),
+            "<=" => i64::from(left <= right),
+            ">=" => i64::from(left >= right),
+            "<<" => left.wrapping_shl(right as u32),
+            ">>" => left.wrapping_shr(right as u32),
+            "+" => left.wrapping_add(right),
+            "-" => left.wrapping_sub(right),
+            _ if right == 0 && self.skip == 0 => return Err(self.error_at(pos, "division by 0")),
+            _ if right == 0 => 0,
+            "*" => left.wrapping_mul(right),
+            "/" => left.wrapping_div(right),
+            _ => left.wrapping_rem(right),
+        })
+    }
+
+    fn exponent(&mut self) -> Result<i64, CommandError> {
+        let base = self.unary()?;
+        if !self.eat("**") {
+            return Ok(base);
+        }
+        let exponent = self.exponent()?;
+        if exponent < 0 && self.skip == 0 {
+            return Err(self.error("exponent less than 0"));
+        }
+        Ok(power(base, exponent))
+    }
+
+    fn unary(&mut self) -> Result<i64, CommandError> {
+        if let Token::Operator(op @ ("++" | "--")) = *self.peek() {
+            self.pos += 1;
+            let Token::Name(name) = self.peek().clone() else {
+                return Err(self.error("syntax error: operand expected"));
+            };
+            self.pos += 1;
+            return self.increment(&name, op, false);
+        }
+        if let Token::Operator(op @ ("-" | "+" | "!" | "~")) = *self.peek() {
+            self.pos += 1;
+            let value = self.unary()?;
+            return Ok(match op {
+                "-" => value.wrapping_neg(),
+                "+" => value,
+                "!" => i64::from(value == 0),
+                _ => !value,
+            });
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<i64, CommandError> {
+        match self.peek().clone() {
+            Token::Number(text) => {
+                let value = number(&text).ok_or_else(|| self.error("value too great for base"))?;
+                self.pos += 1;
+                Ok(value)
+            }
+            Token::Name(name) => {
+                if let Some(&(Token::Operator(op @ ("++" | "--")), _)) = self.tokens.get(self.pos + 1) {
+                    self.pos += 2;
+                    return self.increment(&name, op, true);
+                }
+                // Errors in the value point at the name.
+                let value = if self.skip > 0 { 0 } else { self.value(&name)? };
+                self.pos += 1;
+                Ok(value)
+            }
+            Token::Operator("(") => {
+                self.pos += 1;
+                let value = self.comma()?;
+                if !self.eat(")") {
+                    return Err(self.error("missing `)'"));
+                }
+                Ok(value)
+            }
+            _ => Err(self.error("syntax error: operand expected")),
+        }
+    }
+
+    // Adds or subtracts 1, giving the old value for the postfix forms.
+    fn increment(&mut self, name: &str, op: &str, postfix: bool) -> Result<i64, CommandError> {
+        if self.skip > 0 {
+            return Ok(0);
+        }
+        let old = self.value(name)?;
+        let new = if op == "++" { old.wrapping_add(1) } else { old.wrapping_sub(1) };
+        self.store(name, new)?;
+        Ok(if postfix { old } else { new })
+    }
+
+    // The value of a variable or array element, evaluated as an expression.
+    fn value(&self, name: &str) -> Result<i64, CommandError> {
+        let value = match variables::split_subscript(name) {
+            Some((array, subscript)) => variables::element(array, subscript)?,
+            None => variables::get(name),
+        };
+        match value {
+            Some(value) if !value.trim().is_empty() => self.nested(&value),
+            _ => Ok(0),
+        }
+    }
+
+    fn store(&self, name: &str, value: i64) -> Result<(), CommandError> {
+        match variables::split_subscript(name) {
+            Some((array, subscript)) => variables::set_element(array, subscript, &value.to_string(), false),
+            None => variables::set(name, &value.to_string()),
+        }
+    }
+
+    // Evaluates the value of a variable, which may be an expression itself.
+    fn nested(&self, expr: &str) -> Result<i64, CommandError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("expression recursion level exceeded"));
+        }
+        let depth = self.depth + 1;
+        if depth % LEVELS_PER_STACK != 0 {
+            return evaluate_at(expr, depth);
+        }
+        thread::scope(|scope| match thread::Builder::new().stack_size(STACK_SIZE).spawn_scoped(scope, || evaluate_at(expr, depth)) {
+            Ok(handle) => handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+            Err(_) => evaluate_at(expr, depth),
+        })
+    }
+}
+
+fn evaluate_at(expr: &str, depth: usize) -> Result<i64, CommandError> {
+    let mut evaluator = Evaluator { expr, tokens: tokenize(expr), pos: 0, skip: 0, depth };
+    if *evaluator.peek() == Token::End {
+        return Ok(0);
+    }
+    let value = evaluator.comma()?;
+    if *evaluator.peek() != Token::End {
+        return Err(evaluator.error("syntax error in expression"));
+    }
+    Ok(value)
+}
+
+/// Evaluates an arithmetic expression. An empty expression is 0.
+pub(crate) fn evaluate(expr: &str) -> Result<i64, CommandError> {
+    evaluate_at(expr, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expr: &str) -> i64 {
+        evaluate(expr).unwrap()
+    }
+
+    fn error(expr: &str) -> String {
+        match evaluate(expr) {
+            Err(CommandError::NullParameter(_, message)) => message,
+            other => panic!("{} gave {:?}", expr, other),
+        }
+    }
+
+    #[test]
+    fn numbers_in_any_base() {
+        assert_eq!(eval(""), 0);
+        assert_eq!(eval("  "), 0);
+        assert_eq!(eval("0x1F"), 31);
+        assert_eq!(eval("010"), 8);
+        assert_eq!(eval("2#101"), 5);
+        assert_eq!(eval("36#z"), 35);
+        assert_eq!(eval("64#_"), 63);
+        assert_eq!(eval("64#A"), 36);
+        assert_eq!(error("08"), "value too great for base (error token is \"08\")");
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(eval("1 + 2 * 3"), 7);
+        assert_eq!(eval("(1 + 2) * 3"), 9);
+        assert_eq!(eval("2 ** 3 ** 2"), 512);
+        assert_eq!(eval("-2 ** 2"), 4);
+        assert_eq!(eval("10 - 4 - 3"), 3);
+        assert_eq!(eval("1 << 2 + 1"), 8);
+        assert_eq!(eval("5 & 3 | 8 ^ 1"), 9);
+        assert_eq!(eval("1 < 2 == 2 > 1"), 1);
+        assert_eq!(eval("!0 + ~0"), 0);
+        assert_eq!(eval("1, 2, 3"), 3);
+        assert_eq!(eval("0 ? 1 : 2 ? 3 : 4"), 3);
+    }
+
+    #[test]
+    fn integers_wrap() {
+        assert_eq!(eval("9223372036854775807 + 1"), i64::MIN);
+        assert_eq!(eval("-9223372036854775807 - 1"), i64::MIN);
+        assert_eq!(eval("-7 / 2"), -3);
+        assert_eq!(eval("-7 % 2"), -1);
+        assert_eq!(eval("(-9223372036854775807 - 1) / -1"), i64::MIN);
+    }
+
+    #[test]
+    fn logic_short_circuits() {
+        assert_eq!(eval("0 && 1 / 0"), 0);
+        assert_eq!(eval("1 || 1 / 0"), 1);
+        assert_eq!(eval("1 ? 2 : 1 / 0"), 2);
+        assert_eq!(eval("0 && (arith_test_skipped = 1)"), 0);
+        assert_eq!(variables::get("arith_test_skipped"), None);
+    }
+
+    #[test]
+    fn assignments_store_the_result() {
+        assert_eq!(eval("arith_test_a = 5"), 5);
+        assert_eq!(eval("arith_test_a += 2 * 3"), 11);
+        assert_eq!(eval("arith_test_a <<= 1"), 22);
+        assert_eq!(eval("arith_test_b = arith_test_c = 3"), 3);
+        assert_eq!(variables::get("arith_test_a").as_deref(), Some("22"));
+        assert_eq!(variables::get("arith_test_b").as_deref(), Some("3"));
+        assert_eq!(variables::get("arith_test_c").as_deref(), Some("3"));
+        assert_eq!(error("1 = 2"), "attempted assignment to non-variable (error token is \"= 2\")");
+    }
+
+    #[test]
+    fn increments() {
+        variables::set("arith_test_i", "5").unwrap();
+        assert_eq!(eval("arith_test_i++"), 5);
+        assert_eq!(eval("++arith_test_i"), 7);
+        assert_eq!(eval("arith_test_i--"), 7);
+        assert_eq!(eval("--arith_test_i"), 5);
+        // A sign before a number, or one not next to a name, is not an increment.
+        assert_eq!(eval("--5"), 5);
+        assert_eq!(eval("1 - -arith_test_i"), 6);
+        assert_eq!(variables::get("arith_test_i").as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn variables_are_expressions() {
+        variables::set("arith_test_x", "3 + 4").unwrap();
+        assert_eq!(eval("arith_test_x * 2"), 14);
+        assert_eq!(eval("arith_test_unset + 1"), 1);
+        variables::set("arith_test_loop", "(arith_test_loop)").unwrap();
+        assert_eq!(
+            error("1 + arith_test_loop"),
+            "expression recursion level exceeded (error token is \"arith_test_loop)\")"
+        );
+    }
+
+    #[test]
+    fn array_elements() {
+        assert_eq!(eval("arith_test_array[1 + 1] = 4"), 4);
+        assert_eq!(eval("arith_test_array[2] * 2"), 8);
+        assert_eq!(variables::element("arith_test_array", "2").unwrap().as_deref(), Some("4"));
+    }
+
+    #[test]
+    fn errors_name_the_offending_text() {
+        assert_eq!(error("1 / 0"), "division by 0 (error token is \"0\")");
+        assert_eq!(error("5 % (2 - 2) + 1"), "division by 0 (error token is \"(2 - 2) + 1\")");
+        assert_eq!(error("1 +"), "syntax error: operand expected (error token is \"+\")");
+        assert_eq!(error("1 2"), "syntax error in expression (error token is \"2\")");
+        assert_eq!(error("(1"), "missing `)' (error token is \"1\")");
+        assert_eq!(error("2 ** -1"), "exponent less than 0 (error token is \"1\")");
+    }
+}
@@ -0,0 +1,156 @@
+// The `case` compound command: `case word in pattern|pattern) list ;; ... esac`.
+use std::ops::Range;
+use super::parser::{self, Token};
+use super::pattern::Pattern;
+use super::shopt::{self, ShoptOption};
+use super::{utils, Command, CommandError};
+
+// How an arm of a `case` ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Terminator {
+    // `;;`: stop after this arm.
+    Break,
+    // `;&`: also run the next arm's commands.
+    FallThrough,
+    // `;;&`: keep testing the following patterns.
+    Continue,
+}
+
+#[derive(Debug)]
+pub struct CaseArm {
+    pub patterns: Vec<String>,
+    pub body: String,
+    pub terminator: Terminator,
+}
+
+// Whether `input` starts with the `case` keyword.
+pub(crate) fn is_case(input: &str) -> bool {
+    let input = input.trim_start();
+    input.strip_prefix("case").is_some_and(|rest| rest.starts_with(char::is_whitespace))
+}
+
+fn syntax_error(token: Option<&Token>) -> CommandError {
+    CommandError::Syntax(match token {
+        Some(Token::Word(word)) => word.clone(),
+        Some(Token::Operator(op)) => op.to_string(),
+        Some(Token::Newline) | None => "newline".to_string(),
+    })
+}
+
+fn is_word(token: Option<&Token>, word: &str) -> bool {
+    matches!(token, Some(Token::Word(found)) if found == word)
+}
+
+pub(crate) fn parse_case(input: &str) -> Result<Command, CommandError> {
+    let input = input.trim();
+    let tokens = parser::tokenize(input).map_err(|_| syntax_error(None))?;
+    let token = |i: usize| tokens.get(i).map(|(token, _)| token);
+    // Newlines and `;` may come between the parts of a `case`.
+    let skip_blanks = |mut i: usize| {
+        while matches!(token(i), Some(Token::Newline | Token::Operator(";"))) {
+            i += 1;
+        }
+        i
+    };
+    let Some(Token::Word(word)) = token(1) else {
+        return Err(syntax_error(token(1)));
+    };
+    let mut i = skip_blanks(2);
+    if !is_word(token(i), "in") {
+        return Err(syntax_error(token(i)));
+    }
+    i += 1;
+
+    let mut arms = Vec::new();
+    loop {
+        i = skip_blanks(i);
+        match token(i) {
+            None => return Err(syntax_error(None)),
+            Some(Token::Word(word)) if word == "esac" => {
+                i += 1;
+                break;
+            }
+            Some(Token::Operator("(")) => i += 1,
+            _ => {}
+        }
+        let mut patterns = Vec::new();
+        loop {
+            let Some(Token::Word(pattern)) = token(i) else {
+                return Err(syntax_error(token(i)));
+            };
+            patterns.push(pattern.clone());
+            match token(i + 1) {
+                Some(Token::Operator("|")) => i += 2,
+                Some(Token::Operator(")")) => break,
+                other => return Err(syntax_error(other)),
+            }
+        }
+        let (body, terminator, next) = read_body(input, &tokens, i + 2);
+        i = next;
+        arms.push(CaseArm { patterns, body, terminator: terminator.unwrap_or(Terminator::Break) });
+    }
+
+    match token(skip_blanks(i)) {
+        None => Ok(Command::Case { word: word.clone(), arms }),
+        other => Err(syntax_error(other)),
+    }
+}
+
+// Reads the commands of an arm, from token `start` up to its terminator or the
+// closing `esac`. Nested `case` commands are skipped over.
+fn read_body(input: &str, tokens: &[(Token, Range<usize>)], start: usize) -> (String, Option<Terminator>, usize) {
+    let from = tokens[start - 1].1.end;
+    let mut nested = 0;
+    for (i, (token, span)) in tokens.iter().enumerate().skip(start) {
+        let previous = &tokens[i - 1].0;
+        let command_start = parser::at_command_start(Some(previous));
+        let body = || input[from..span.start].to_string();
+        match token {
+            Token::Word(word) if word == "case" && command_start => nested += 1,
+            Token::Word(word) if word == "esac" && (command_start || is_word(Some(previous), "in")) => {
+                if nested == 0 {
+                    return (body(), None, i);
+                }
+                nested -= 1;
+            }
+            Token::Operator(op) if nested == 0 => match *op {
+                ";;" => return (body(), Some(Terminator::Break), i + 1),
+                ";&" => return (body(), Some(Terminator::FallThrough), i + 1),
+                ";;&" => return (body(), Some(Terminator::Continue), i + 1),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    (input[from..].to_string(), None, tokens.len())
+}
+
+pub(crate) fn case_cmd(word: &str, arms: &[CaseArm]) -> i32 {
+    let word = match utils::expand_word(word) {
+        Ok(word) => word,
+        Err(e) => {
+            eprintln!("{}", e);
+            return e.status();
+        }
+    };
+    let extglob = shopt::enabled(ShoptOption::Extglob);
+    let ignore_case = shopt::enabled(ShoptOption::Nocasematch);
+    let matches = |pattern: &String| {
+        utils::expand_pattern(pattern).is_ok_and(|pattern| Pattern::new(&pattern, extglob, ignore_case).matches(&word))
+    };
+
+    let mut status = 0;
+    let mut fall_through = false;
+    for arm in arms {
+        if !fall_through && !arm.patterns.iter().any(matches) {
+            continue;
+        }
+        status = if arm.body.trim().is_empty() { 0 } else { Command::run(&arm.body) };
+        match arm.terminator {
+            Terminator::Break => break,
+            Terminator::FallThrough => fall_through = true,
+            Terminator::Continue => fall_through = false,
+        }
+    }
+    status
+}
@@ -1,33 +1,197 @@
-use std::path::PathBuf;
-use std::env::var_os;
-use std::env::set_current_dir;
-use super::{Command, CommandError};
-
-pub(crate) fn cd_cmd(args: &str) {
-    let args = args.trim();
-    let target = if args.is_empty() {
-        // no argument -> HOME if set
-        var_os("HOME").map(PathBuf::from)
-    } else if args.starts_with('~') {
-        // expand ~ to HOME if possible, otherwise treat literally without the ~
-        var_os("HOME").map(|h| {
-            let rest = args.strip_prefix('~').unwrap_or("");
-            PathBuf::from(h).join(rest)
-        }).or_else(|| Some(PathBuf::from(args.strip_prefix('~').unwrap_or(args))))
+use std::path::{Component, Path, PathBuf};
+use std::env::{set_current_dir, split_paths};
+use std::io::{self, ErrorKind, Write};
+use super::shopt::{self, ShoptOption};
+use super::state::STATE;
+use super::{pwd, utils, variables, z, Command, CommandError, Redirection};
+
+// Resolves `.` and `..` textually, the way `cd -L` does, so that symlinks stay in the path.
+fn logical_path(path: &Path) -> PathBuf {
+    let mut result = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            Component::Normal(part) => result.push(part),
+        }
+    }
+    result
+}
+
+// Looks the operand up in CDPATH. Returns the directory and whether it came from a
+// non-empty CDPATH entry (in which case the new directory is printed).
+fn search_cdpath(dir: &str) -> Option<(PathBuf, bool)> {
+    let first = Path::new(dir).components().next();
+    if dir.starts_with('/') || matches!(first, Some(Component::CurDir) | Some(Component::ParentDir)) {
+        return None;
+    }
+    let cdpath = variables::get("CDPATH")?;
+    split_paths(&cdpath).find_map(|entry| {
+        let from_entry = !entry.as_os_str().is_empty();
+        let candidate = if from_entry { entry.join(dir) } else { PathBuf::from(".").join(dir) };
+        candidate.is_dir().then_some((candidate, from_entry))
+    })
+}
+
+// Whether `a` and `b` differ by one transposed, missing, extra or wrong character.
+fn nearly_equal(a: &str, b: &str) -> bool {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    if a.len() == b.len() {
+        let diffs: Vec<usize> = (0..a.len()).filter(|&i| a[i] != b[i]).collect();
+        match diffs.as_slice() {
+            [_] => true,
+            [i, j] => *j == i + 1 && a[*i] == b[*j] && a[*j] == b[*i],
+            _ => false,
+        }
+    } else {
+        let (long, short) = if a.len() > b.len() { (&a, &b) } else { (&b, &a) };
+        long.len() == short.len() + 1 && (0..long.len()).any(|skip| {
+            long.iter().enumerate().filter(|&(i, _)| i != skip).map(|(_, c)| c).eq(short.iter())
+        })
+    }
+}
+
+// Corrects each missing component of `path` to a nearly equal directory name, for cdspell.
+fn correct_spelling(path: &Path) -> Option<PathBuf> {
+    let mut corrected = PathBuf::new();
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            corrected.push(component);
+            continue;
+        };
+        if corrected.join(name).is_dir() {
+            corrected.push(name);
+            continue;
+        }
+        let dir = if corrected.as_os_str().is_empty() { Path::new(".") } else { corrected.as_path() };
+        let name = name.to_str()?;
+        let found = std::fs::read_dir(dir).ok()?.flatten().find_map(|entry| {
+            let candidate = entry.file_name().into_string().ok()?;
+            (nearly_equal(name, &candidate) && entry.path().is_dir()).then_some(candidate)
+        })?;
+        corrected.push(found);
+    }
+    Some(corrected)
+}
+
+fn describe_error(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::PermissionDenied => "Permission denied",
+        ErrorKind::NotADirectory => "Not a directory",
+        _ => "No such file or directory",
+    }
+}
+
+// Changes the working directory to `target` and updates PWD/OLDPWD.
+// With `physical` unset the new PWD keeps symlinks, as in `cd -L`.
+pub(crate) fn change_directory(target: &Path, physical: bool) -> std::io::Result<PathBuf> {
+    let old_pwd = pwd::logical_pwd();
+    let new_pwd = if physical {
+        set_current_dir(target)?;
+        std::env::current_dir()?
     } else {
-        Some(PathBuf::from(args))
+        let absolute = match &old_pwd {
+            Some(old) if target.is_relative() => old.join(target),
+            _ => target.to_path_buf(),
+        };
+        let logical = logical_path(&absolute);
+        match set_current_dir(&logical) {
+            Ok(()) => logical,
+            // The logical path may not exist (e.g. `..` out of a symlinked dir that was removed):
+            // fall back to the physical lookup.
+            Err(_) => {
+                set_current_dir(target)?;
+                std::env::current_dir()?
+            }
+        }
+    };
+    if let Some(old) = old_pwd {
+        let _ = variables::set("OLDPWD", &old.to_string_lossy());
+    }
+    let _ = variables::set("PWD", &new_pwd.to_string_lossy());
+    z::record(&new_pwd);
+    Ok(new_pwd)
+}
+
+pub(crate) fn cd_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    cd(args, stdout_writer, stderr_writer).unwrap_or_else(|err| utils::write_error("cd", &err, stderr_writer))
+}
+
+fn cd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> io::Result<i32> {
+    let mut physical = false;
+    let mut operands = args.iter().map(String::as_str).peekable();
+    while let Some(&arg) = operands.peek() {
+        match arg {
+            "-L" => physical = false,
+            "-P" => physical = true,
+            "--" => {
+                operands.next();
+                break;
+            }
+            _ => break,
+        }
+        operands.next();
+    }
+    let operands: Vec<&str> = operands.collect();
+    if operands.len() > 1 {
+        let _ = writeln!(stderr_writer, "cd: too many arguments");
+        return Ok(1);
+    }
+
+    let mut print_directory = false;
+    let target = match operands.first() {
+        None => match variables::get("HOME") {
+            Some(home) if !home.is_empty() => PathBuf::from(home),
+            _ => {
+                let _ = writeln!(stderr_writer, "cd: HOME not set");
+                return Ok(1);
+            }
+        },
+        Some(&"-") => match variables::get("OLDPWD") {
+            Some(old) if !old.is_empty() => {
+                print_directory = true;
+                PathBuf::from(old)
+            }
+            _ => {
+                let _ = writeln!(stderr_writer, "cd: OLDPWD not set");
+                return Ok(1);
+            }
+        },
+        Some(&dir) => match search_cdpath(dir) {
+            Some((found, from_entry)) => {
+                print_directory = from_entry;
+                found
+            }
+            None => PathBuf::from(dir),
+        },
     };
 
-    match target {
-        Some(path) => {
-            if let Err(_err) = set_current_dir(&path) {
-                eprintln!("cd: {}: No such file or directory", path.display());
+    match change_directory(&target, physical) {
+        Ok(new_pwd) => {
+            if print_directory {
+                writeln!(stdout_writer, "{}", new_pwd.display())?;
             }
+            Ok(0)
+        }
+        Err(err) => {
+            // cdspell fixes small typos in interactive shells and prints what it used.
+            if shopt::enabled(ShoptOption::Cdspell) && STATE.read().unwrap().interactive {
+                if let Some(corrected) = correct_spelling(&target) {
+                    if change_directory(&corrected, physical).is_ok() {
+                        writeln!(stdout_writer, "{}", corrected.display())?;
+                        return Ok(0);
+                    }
+                }
+            }
+            let shown = operands.first().map_or_else(|| target.display().to_string(), |dir| dir.to_string());
+            let _ = writeln!(stderr_writer, "cd: {}: {}", shown, describe_error(err.kind()));
+            Ok(1)
         }
-        None => eprintln!("cd: {}: HOME not set", args),
     }
 }
 
-pub(crate) fn parse_cd_cmd(args: &str) -> Result<Command, CommandError> {
-    Ok(Command::Cd(args.to_owned()))
-}
\ No newline at end of file
+pub(crate) fn parse_cd_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Cd { args, redirections })
+}
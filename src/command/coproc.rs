@@ -0,0 +1,116 @@
+// The `coproc` command: `coproc NAME { list; }`, `coproc { list; }` or
+// `coproc command args` runs in the background with its output and input
+// connected to the shell through pipes, as file descriptors `NAME[0]` and
+// `NAME[1]` (NAME is COPROC by default), and its pid in `NAME_PID`.
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::process::Stdio;
+use super::jobs::{self, JobState};
+use super::state::STATE;
+use super::{expand, utils, variables, Command, CommandError};
+
+const DEFAULT_NAME: &str = "COPROC";
+
+// A running coprocess.
+pub(crate) struct Coproc {
+    pid: u32,
+    name: String,
+    // The shell's ends of the pipes: the coprocess's output, then its input,
+    // unless `exec` has closed or replaced them since.
+    fds: Vec<OwnedFd>,
+}
+
+// Whether `input` starts with the `coproc` keyword.
+pub(crate) fn is_coproc(input: &str) -> bool {
+    let input = input.trim_start();
+    input.strip_prefix("coproc").is_some_and(|rest| rest.starts_with(char::is_whitespace))
+}
+
+// The list inside `{ list; }`, if `text` is such a group.
+fn group(text: &str) -> Option<&str> {
+    let inner = text.strip_prefix('{')?.strip_suffix('}')?;
+    inner.starts_with(char::is_whitespace).then_some(inner)
+}
+
+pub(crate) fn parse_coproc(input: &str) -> Result<Command, CommandError> {
+    let rest = input.trim().strip_prefix("coproc").unwrap_or_default().trim_start();
+    let (name, body) = match group(rest) {
+        Some(body) => (DEFAULT_NAME, body),
+        None => {
+            let (word, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            match group(after.trim_start()) {
+                Some(body) => (word, body),
+                None => (DEFAULT_NAME, rest),
+            }
+        }
+    };
+    let body = body.trim().trim_end_matches(';').trim_end();
+    if body.is_empty() {
+        return Err(CommandError::Syntax(if rest.ends_with('}') { "}" } else { "newline" }.to_string()));
+    }
+    Ok(Command::Coproc { name: name.to_string(), body: body.to_string() })
+}
+
+pub(crate) fn coproc_cmd(name: &str, body: &str) -> i32 {
+    if !variables::valid_name(name) {
+        eprintln!("`{}': not a valid identifier", name);
+        return 1;
+    }
+    // As in bash, only the newest coprocess is expected to be in use.
+    if let Some(coproc) = STATE.read().unwrap().coprocs.last() {
+        eprintln!("warning: execute_coproc: coproc [{}:{}] still exists", coproc.pid, coproc.name);
+    }
+    let Some(mut command) = expand::subshell(body) else {
+        return 1;
+    };
+    command.stdin(Stdio::piped()).stdout(Stdio::piped());
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("coproc: {}", utils::describe_io_error(&err));
+            return 1;
+        }
+    };
+    let (Some(output), Some(input)) = (child.stdout.take(), child.stdin.take()) else {
+        return 1;
+    };
+    let fds: Vec<OwnedFd> = vec![output.into(), input.into()];
+    let pid = child.id();
+    let values = fds.iter().map(|fd| fd.as_raw_fd().to_string()).collect();
+    if let Err(err) = variables::set_array(name, variables::indexed(values))
+        .and_then(|_| variables::set(&format!("{}_PID", name), &pid.to_string()))
+    {
+        eprintln!("{}", err);
+    }
+    let id = jobs::add_job(pid, &format!("coproc {} {{ {}; }}", name, body), JobState::Running);
+    let mut shell = STATE.write().unwrap();
+    shell.last_background = Some(pid);
+    shell.coprocs.push(Coproc { pid, name: name.to_string(), fds });
+    if shell.interactive {
+        eprintln!("[{}] {}", id, pid);
+    }
+    0
+}
+
+/// Once the process `pid` has been reaped, closes the pipes of the coprocess
+/// it was, if any, and unsets its variables.
+pub(crate) fn reaped(pid: u32) {
+    let mut shell = STATE.write().unwrap();
+    let Some(index) = shell.coprocs.iter().position(|coproc| coproc.pid == pid) else {
+        return;
+    };
+    let Coproc { name, fds, .. } = shell.coprocs.remove(index);
+    drop(shell);
+    drop(fds);
+    let _ = variables::unset(&name);
+    let _ = variables::unset(&format!("{}_PID", name));
+}
+
+/// Hands over the descriptor `fd` if it is a coprocess pipe, so that `exec`
+/// can close or replace it without it being closed again once reaped.
+pub(crate) fn take_fd(fd: RawFd) -> Option<OwnedFd> {
+    let mut shell = STATE.write().unwrap();
+    shell.coprocs.iter_mut().find_map(|coproc| {
+        let index = coproc.fds.iter().position(|owned| owned.as_raw_fd() == fd)?;
+        Some(coproc.fds.remove(index))
+    })
+}
@@ -0,0 +1,328 @@
+// The `declare` builtin, also called `typeset`: sets variables and their attributes.
+// `export` is the part of it that deals with the export attribute.
+use std::io::{self, Write};
+use super::state::STATE;
+use super::variables::{self, Array, Attribute, ATTRIBUTES};
+use super::{printf, special, utils, Command, CommandError, Redirection};
+
+const USAGE: &str = "declare: usage: declare [-aAfFgiIlnrtux] [name[=value] ...] or declare -p [-aAfFilnrtux] [name ...]";
+const EXPORT_USAGE: &str = "export: usage: export [-fn] [name[=value] ...] or export -p";
+
+#[derive(Default)]
+struct Options {
+    // Attributes turned on with `-x` and off with `+x`.
+    on: Vec<Attribute>,
+    off: Vec<Attribute>,
+    indexed: bool,
+    associative: bool,
+    print: bool,
+    // `-f` and `-F` are about functions, which this shell does not have.
+    functions: bool,
+}
+
+impl Options {
+    // Whether `name` has everything asked for, when listing variables.
+    fn matches(&self, name: &str) -> bool {
+        let kind = match STATE.read().unwrap().arrays.get(name) {
+            Some(Array::Indexed(_)) => (true, false),
+            Some(Array::Associative(_)) => (false, true),
+            None => (false, false),
+        };
+        (!self.indexed || kind.0)
+            && (!self.associative || kind.1)
+            && self.on.iter().all(|&attribute| variables::has_attribute(name, attribute))
+    }
+}
+
+fn parse_options<'a>(args: &'a [String], stderr_writer: &mut dyn Write) -> Result<(Options, &'a [String]), i32> {
+    let mut options = Options::default();
+    let mut operands = args;
+    while let Some(arg) = operands.first().filter(|arg| arg.len() > 1 && arg.starts_with(['-', '+'])) {
+        operands = &operands[1..];
+        if arg == "--" {
+            break;
+        }
+        let on = arg.starts_with('-');
+        for flag in arg[1..].chars() {
+            match flag {
+                'a' => options.indexed = true,
+                'A' => options.associative = true,
+                'p' => options.print = true,
+                'f' | 'F' => options.functions = true,
+                // Without functions every variable is global, and there is nothing to inherit.
+                'g' | 'I' => {}
+                _ => match ATTRIBUTES.iter().find(|&&(_, letter)| letter == flag) {
+                    Some(&(attribute, _)) if on => options.on.push(attribute),
+                    Some(&(attribute, _)) => options.off.push(attribute),
+                    None => {
+                        let _ = writeln!(stderr_writer, "declare: {}{}: invalid option", &arg[..1], flag);
+                        let _ = writeln!(stderr_writer, "{}", USAGE);
+                        return Err(2);
+                    }
+                },
+            }
+        }
+    }
+    // Asking for both cases at once gives neither.
+    if options.on.contains(&Attribute::Lowercase) && options.on.contains(&Attribute::Uppercase) {
+        options.on.retain(|attribute| !matches!(attribute, Attribute::Lowercase | Attribute::Uppercase));
+    }
+    Ok((options, operands))
+}
+
+// Quotes a value the way `declare -p` prints it.
+fn quote_value(value: &str) -> String {
+    if value.chars().any(|c| c.is_control()) {
+        return printf::quote(value);
+    }
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+    out
+}
+
+// The elements of an array as `[key]="value"` words.
+fn array_items(array: &Array) -> Vec<String> {
+    match array {
+        Array::Indexed(map) => map.iter().map(|(index, value)| format!("[{}]={}", index, quote_value(value))).collect(),
+        Array::Associative(map) => map.iter().map(|(key, value)| format!("[{}]={}", key, quote_value(value))).collect(),
+    }
+}
+
+// A `declare` command that recreates `name`, or None if there is no such variable.
+fn declaration(name: &str) -> Option<String> {
+    let shell = STATE.read().unwrap();
+    let attributes = shell.attributes.get(name).cloned().unwrap_or_default();
+    let mut flags = String::new();
+    let value = match shell.arrays.get(name) {
+        Some(array) => {
+            flags.push(if matches!(array, Array::Indexed(_)) { 'a' } else { 'A' });
+            let items = array_items(array);
+            // bash leaves a space before the `)` of an associative array.
+            let end = if matches!(array, Array::Associative(_)) { " )" } else { ")" };
+            (!items.is_empty()).then(|| format!("=({}{}", items.join(" "), end))
+        }
+        None => match shell.scalars.get(name) {
+            Some(value) => Some(format!("={}", quote_value(value))),
+            // A variable may have attributes before it has a value.
+            None if !attributes.is_empty() => None,
+            None => return None,
+        },
+    };
+    flags.extend(ATTRIBUTES.iter().filter(|(attribute, _)| attributes.contains(attribute)).map(|&(_, letter)| letter));
+    if flags.is_empty() {
+        flags.push('-');
+    }
+    Some(format!("declare -{} {}{}", flags, name, value.unwrap_or_default()))
+}
+
+// Passes the variables on to a subshell as `declare` commands, since the
+// environment holds neither arrays nor attributes.
+const SUBSHELL_VAR: &str = "__SHELL_VARIABLES";
+
+/// The environment entry that recreates the shell's variables in a subshell.
+pub(crate) fn subshell_env() -> (&'static str, String) {
+    let declarations: Vec<String> = variables::names()
+        .iter()
+        .filter(|name| !special::is_dynamic(name))
+        .filter_map(|name| declaration(name))
+        .collect();
+    (SUBSHELL_VAR, declarations.join("\n"))
+}
+
+/// Recreates the variables of the shell this subshell runs for.
+pub(crate) fn inherit() {
+    let Ok(declarations) = std::env::var(SUBSHELL_VAR) else {
+        return;
+    };
+    std::env::remove_var(SUBSHELL_VAR);
+    let (mut stdout, mut stderr) = (std::io::sink(), std::io::sink());
+    for line in declarations.lines() {
+        if let Ok(args) = utils::split_args(line) {
+            declare_cmd(args.get(1..).unwrap_or_default(), &mut stdout, &mut stderr);
+        }
+    }
+}
+
+// `declare` on its own lists the variables as assignments.
+fn print_assignments(stdout_writer: &mut dyn Write) -> io::Result<()> {
+    for name in variables::names() {
+        let array = STATE.read().unwrap().arrays.get(&name).cloned();
+        match array {
+            Some(array) => writeln!(stdout_writer, "{}=({})", name, array_items(&array).join(" "))?,
+            None => {
+                let value = STATE.read().unwrap().scalars.get(&name).cloned();
+                if let Some(value) = value {
+                    writeln!(stdout_writer, "{}={}", name, utils::quote_if_needed(&value))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Declares one operand, `name` or `name=value`.
+fn declare(operand: &str, options: &Options) -> Result<(), CommandError> {
+    let assignment = variables::Assignment::parse(operand);
+    let name = match &assignment {
+        Some(assignment) => assignment.name.as_str(),
+        None if variables::valid_name(operand) => operand,
+        None => return Err(CommandError::NullParameter(format!("`{}'", operand), "not a valid identifier".to_string())),
+    };
+    let reference = options.on.contains(&Attribute::Nameref);
+    if let Some(target) = assignment.as_ref().filter(|_| reference).map(|assignment| &assignment.value) {
+        if !variables::valid_name(target) {
+            return Err(CommandError::NullParameter(format!("`{}'", target), "invalid variable name for name reference".to_string()));
+        }
+    }
+    // Other attributes may be added to a readonly variable, but it cannot change.
+    if variables::has_attribute(name, Attribute::Readonly) && (assignment.is_some() || options.off.contains(&Attribute::Readonly)) {
+        return Err(CommandError::NullParameter(name.to_string(), "readonly variable".to_string()));
+    }
+
+    // Attributes go to the variable a reference names, unless the reference itself is changed.
+    let target = if reference || options.off.contains(&Attribute::Nameref) { name.to_string() } else { variables::resolve(name) };
+    if options.indexed || options.associative {
+        variables::declare_array(&target, options.associative)?;
+    }
+    for &attribute in &options.off {
+        variables::set_attribute(&target, attribute, false);
+    }
+    for &attribute in options.on.iter().filter(|&&attribute| attribute != Attribute::Readonly) {
+        variables::set_attribute(&target, attribute, true);
+        match attribute {
+            Attribute::Lowercase => variables::set_attribute(&target, Attribute::Uppercase, false),
+            Attribute::Uppercase => variables::set_attribute(&target, Attribute::Lowercase, false),
+            _ => {}
+        }
+    }
+
+    match &assignment {
+        Some(assignment) if reference => variables::set_reference(name, &assignment.value)?,
+        Some(assignment) => assignment.apply()?,
+        None => {}
+    }
+    // Readonly comes last, so that `declare -r name=value` can set the value.
+    if options.on.contains(&Attribute::Readonly) {
+        variables::set_attribute(&target, Attribute::Readonly, true);
+    }
+    Ok(())
+}
+
+pub(crate) fn declare_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    declare_variables(args, stdout_writer, stderr_writer).unwrap_or_else(|err| utils::write_error("declare", &err, stderr_writer))
+}
+
+fn declare_variables(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> io::Result<i32> {
+    let (options, operands) = match parse_options(args, stderr_writer) {
+        Ok(parsed) => parsed,
+        Err(status) => return Ok(status),
+    };
+
+    if options.functions {
+        // No function is ever defined, so only listing them all succeeds.
+        return Ok(i32::from(!operands.is_empty()));
+    }
+    if operands.is_empty() {
+        if !options.print && options.on.is_empty() && options.off.is_empty() && !options.indexed && !options.associative {
+            print_assignments(stdout_writer)?;
+            return Ok(0);
+        }
+        for name in variables::names().iter().filter(|name| options.matches(name)) {
+            if let Some(line) = declaration(name) {
+                writeln!(stdout_writer, "{}", line)?;
+            }
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for operand in operands {
+        if options.print {
+            match declaration(operand) {
+                Some(line) => writeln!(stdout_writer, "{}", line)?,
+                None => {
+                    let _ = writeln!(stderr_writer, "declare: {}: not found", operand);
+                    status = 1;
+                }
+            }
+            continue;
+        }
+        if let Err(err) = declare(operand, &options) {
+            let _ = writeln!(stderr_writer, "declare: {}", err);
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+pub(crate) fn export_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    export_variables(args, stdout_writer, stderr_writer).unwrap_or_else(|err| utils::write_error("export", &err, stderr_writer))
+}
+
+// `export name[=value]` is `declare -x`, and `export -n name` is `declare +x`.
+fn export_variables(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> io::Result<i32> {
+    let mut options = Options::default();
+    let mut unexport = false;
+    let mut operands = args;
+    while let Some(arg) = operands.first().filter(|arg| arg.len() > 1 && arg.starts_with('-')) {
+        operands = &operands[1..];
+        if arg == "--" {
+            break;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'n' => unexport = true,
+                'f' => options.functions = true,
+                // Listing is what `export` does without names anyway.
+                'p' => {}
+                _ => {
+                    let _ = writeln!(stderr_writer, "export: -{}: invalid option", flag);
+                    let _ = writeln!(stderr_writer, "{}", EXPORT_USAGE);
+                    return Ok(2);
+                }
+            }
+        }
+    }
+
+    if operands.is_empty() {
+        for name in variables::names().iter().filter(|name| variables::has_attribute(name, Attribute::Export)) {
+            if let Some(line) = declaration(name) {
+                writeln!(stdout_writer, "{}", line)?;
+            }
+        }
+        return Ok(0);
+    }
+    if unexport {
+        options.off.push(Attribute::Export);
+    } else {
+        options.on.push(Attribute::Export);
+    }
+    let mut status = 0;
+    for operand in operands {
+        let result = if options.functions {
+            // There are no functions to export.
+            Err(CommandError::NullParameter(operand.clone(), "not a function".to_string()))
+        } else {
+            declare(operand, &options)
+        };
+        if let Err(err) = result {
+            let _ = writeln!(stderr_writer, "export: {}", err);
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+pub(crate) fn parse_export_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Export { args, redirections })
+}
+
+pub(crate) fn parse_declare_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Declare { args, redirections })
+}
@@ -0,0 +1,252 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use super::state::STATE;
+use super::{cd, pwd, utils, Command, CommandError, Redirection};
+
+// Passes the saved directories on to a subshell, one per line.
+const SUBSHELL_VAR: &str = "__SHELL_DIRS";
+
+/// The environment entry that passes the directory stack on to a subshell.
+pub(crate) fn subshell_env() -> (&'static str, String) {
+    let shell = STATE.read().unwrap();
+    let dirs: Vec<String> = shell.dir_stack.iter().map(|dir| dir.to_string_lossy().into_owned()).collect();
+    (SUBSHELL_VAR, dirs.join("\n"))
+}
+
+/// Takes over the directory stack of the shell this subshell runs for.
+pub(crate) fn inherit() {
+    let Some(dirs) = std::env::var_os(SUBSHELL_VAR) else {
+        return;
+    };
+    std::env::remove_var(SUBSHELL_VAR);
+    let dirs = dirs.to_string_lossy().into_owned();
+    STATE.write().unwrap().dir_stack = dirs.lines().map(PathBuf::from).collect();
+}
+
+// The whole stack as shown by `dirs`: the current directory followed by the saved ones.
+fn full_stack() -> Vec<PathBuf> {
+    let mut stack = vec![pwd::logical_pwd().unwrap_or_default()];
+    stack.extend(STATE.read().unwrap().dir_stack.iter().cloned());
+    stack
+}
+
+// Converts `+N` (from the top) or `-N` (from the bottom) to an index into `full_stack`.
+fn resolve_index(spec: &str, len: usize) -> Option<usize> {
+    if let Some(n) = spec.strip_prefix('-') {
+        let n: usize = n.parse().ok()?;
+        (n < len).then(|| len - 1 - n)
+    } else {
+        let n: usize = spec.strip_prefix('+').unwrap_or(spec).parse().ok()?;
+        (n < len).then_some(n)
+    }
+}
+
+fn is_index(arg: &str) -> bool {
+    arg.len() > 1 && arg.starts_with(['+', '-']) && arg[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+// Stack entry for the `~N`, `~+N` and `~-N` tilde prefixes.
+pub(crate) fn entry(spec: &str) -> Option<PathBuf> {
+    let stack = full_stack();
+    resolve_index(spec, stack.len()).map(|i| stack[i].clone())
+}
+
+// Makes `stack[0]` the working directory (unless `no_cd`) and saves the rest.
+fn apply(stack: Vec<PathBuf>, no_cd: bool, name: &str, stderr_writer: &mut dyn Write) -> Result<(), ()> {
+    let mut stack = stack.into_iter();
+    let top = stack.next().unwrap_or_default();
+    if !no_cd {
+        if let Err(_err) = cd::change_directory(&top, false) {
+            let _ = writeln!(stderr_writer, "{}: {}: No such file or directory", name, top.display());
+            return Err(());
+        }
+    }
+    STATE.write().unwrap().dir_stack = stack.collect();
+    Ok(())
+}
+
+fn print_stack(stdout_writer: &mut dyn Write) -> io::Result<()> {
+    let line: Vec<String> = full_stack()
+        .iter()
+        .map(|dir| utils::abbreviate_home(&dir.to_string_lossy()))
+        .collect();
+    writeln!(stdout_writer, "{}", line.join(" "))
+}
+
+pub(crate) fn pushd_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    pushd(args, stdout_writer, stderr_writer).unwrap_or_else(|err| utils::write_error("pushd", &err, stderr_writer))
+}
+
+fn pushd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> io::Result<i32> {
+    let no_cd = args.iter().any(|a| a == "-n");
+    let operands: Vec<&String> = args.iter().filter(|a| *a != "-n").collect();
+    let mut stack = full_stack();
+
+    match operands.as_slice() {
+        [] => {
+            if stack.len() < 2 {
+                let _ = writeln!(stderr_writer, "pushd: no other directory");
+                return Ok(1);
+            }
+            stack.swap(0, 1);
+        }
+        [spec] if is_index(spec) => {
+            let Some(n) = resolve_index(spec, stack.len()) else {
+                let _ = writeln!(stderr_writer, "pushd: {}: directory stack index out of range", spec);
+                return Ok(1);
+            };
+            stack.rotate_left(n);
+        }
+        [dir] => {
+            let dir = stack[0].join(dir.as_str());
+            if no_cd {
+                // Saved below the current directory, which stays the same.
+                stack.insert(1, dir);
+            } else {
+                if !dir.is_dir() {
+                    let _ = writeln!(stderr_writer, "pushd: {}: No such file or directory", dir.display());
+                    return Ok(1);
+                }
+                stack.insert(0, dir);
+            }
+        }
+        _ => {
+            let _ = writeln!(stderr_writer, "pushd: too many arguments");
+            return Ok(1);
+        }
+    }
+
+    if apply(stack, no_cd, "pushd", stderr_writer).is_err() {
+        return Ok(1);
+    }
+    print_stack(stdout_writer)?;
+    Ok(0)
+}
+
+pub(crate) fn popd_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    popd(args, stdout_writer, stderr_writer).unwrap_or_else(|err| utils::write_error("popd", &err, stderr_writer))
+}
+
+fn popd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> io::Result<i32> {
+    let no_cd = args.iter().any(|a| a == "-n");
+    let operands: Vec<&String> = args.iter().filter(|a| *a != "-n").collect();
+    let mut stack = full_stack();
+    if stack.len() < 2 {
+        let _ = writeln!(stderr_writer, "popd: directory stack empty");
+        return Ok(1);
+    }
+
+    let removed = match operands.as_slice() {
+        // With -n the current directory stays and the next entry goes instead.
+        [] => usize::from(no_cd),
+        [spec] if is_index(spec) => match resolve_index(spec, stack.len()) {
+            Some(n) => n,
+            None => {
+                let _ = writeln!(stderr_writer, "popd: {}: directory stack index out of range", spec);
+                return Ok(1);
+            }
+        },
+        [arg] => {
+            let _ = writeln!(stderr_writer, "popd: {}: invalid argument", arg);
+            return Ok(1);
+        }
+        _ => {
+            let _ = writeln!(stderr_writer, "popd: too many arguments");
+            return Ok(1);
+        }
+    };
+    stack.remove(removed);
+
+    // Only removing the top entry changes directory.
+    if apply(stack, no_cd || removed != 0, "popd", stderr_writer).is_err() {
+        return Ok(1);
+    }
+    print_stack(stdout_writer)?;
+    Ok(0)
+}
+
+pub(crate) fn dirs_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    dirs(args, stdout_writer, stderr_writer).unwrap_or_else(|err| utils::write_error("dirs", &err, stderr_writer))
+}
+
+fn dirs(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> io::Result<i32> {
+    let mut long = false;
+    let mut per_line = false;
+    let mut numbered = false;
+    let mut clear = false;
+    let mut selected = None;
+
+    for arg in args {
+        if is_index(arg) {
+            selected = Some(arg.as_str());
+            continue;
+        }
+        let Some(flags) = arg.strip_prefix('-') else {
+            let _ = writeln!(stderr_writer, "dirs: {}: invalid argument", arg);
+            return Ok(1);
+        };
+        for flag in flags.chars() {
+            match flag {
+                'c' => clear = true,
+                'l' => long = true,
+                'p' => per_line = true,
+                'v' => {
+                    per_line = true;
+                    numbered = true;
+                }
+                _ => {
+                    let _ = writeln!(stderr_writer, "dirs: -{}: invalid option", flag);
+                    let _ = writeln!(stderr_writer, "dirs: usage: dirs [-clpv] [+N] [-N]");
+                    return Ok(2);
+                }
+            }
+        }
+    }
+    if clear {
+        STATE.write().unwrap().dir_stack.clear();
+        return Ok(0);
+    }
+
+    let stack = full_stack();
+    let show = |dir: &PathBuf| {
+        let dir = dir.to_string_lossy();
+        if long { dir.into_owned() } else { utils::abbreviate_home(&dir) }
+    };
+
+    if let Some(spec) = selected {
+        match resolve_index(spec, stack.len()) {
+            Some(n) => writeln!(stdout_writer, "{}", show(&stack[n]))?,
+            None => {
+                let _ = writeln!(stderr_writer, "dirs: {}: directory stack index out of range", spec);
+                return Ok(1);
+            }
+        }
+        return Ok(0);
+    }
+
+    if per_line {
+        for (i, dir) in stack.iter().enumerate() {
+            if numbered {
+                writeln!(stdout_writer, "{:2}  {}", i, show(dir))?;
+            } else {
+                writeln!(stdout_writer, "{}", show(dir))?;
+            }
+        }
+    } else {
+        let line: Vec<String> = stack.iter().map(show).collect();
+        writeln!(stdout_writer, "{}", line.join(" "))?;
+    }
+    Ok(0)
+}
+
+pub(crate) fn parse_pushd_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Pushd { args, redirections })
+}
+
+pub(crate) fn parse_popd_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Popd { args, redirections })
+}
+
+pub(crate) fn parse_dirs_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Dirs { args, redirections })
+}
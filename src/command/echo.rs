@@ -1,14 +1,52 @@
-use super::{Command, CommandError, Redirection};
+use super::printf::{self, EscapeMode};
+use super::shopt::{self, ShoptOption};
+use super::{utils, Command, CommandError, Redirection};
 use std::io::Write;
 
-pub(crate) fn echo_cmd(message: &str, writer: &mut dyn Write) {
-    // Write to the provided writer.
-    writeln!(writer, "{}", message).unwrap();
+// Splits off the leading option words. Only words made entirely of `n`, `e` and
+// `E` after a `-` are options; the first other word starts the message.
+fn parse_options(args: &[String]) -> (bool, bool, &[String]) {
+    let mut newline = true;
+    // With xpg_echo, escapes are interpreted unless `-E` says otherwise.
+    let mut escapes = shopt::enabled(ShoptOption::XpgEcho);
+    let mut count = 0;
+    for arg in args {
+        let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty() && f.chars().all(|c| "neE".contains(c))) else {
+            break;
+        };
+        for flag in flags.chars() {
+            match flag {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+        count += 1;
+    }
+    (newline, escapes, &args[count..])
 }
 
-pub(crate) fn parse_echo_cmd(args: &str, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
-    Ok(Command::Echo {
-        message: args.to_owned(),
-        redirections,
-    })
-}
\ No newline at end of file
+pub(crate) fn echo_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    let (mut newline, escapes, words) = parse_options(args);
+    let message = words.join(" ");
+    let mut out = Vec::with_capacity(message.len() + 1);
+    if escapes {
+        // `\c` drops the rest of the output, newline included.
+        if !printf::unescape(&message, EscapeMode::Echo, &mut out) {
+            newline = false;
+        }
+    } else {
+        out.extend_from_slice(message.as_bytes());
+    }
+    if newline {
+        out.push(b'\n');
+    }
+    match stdout_writer.write_all(&out).and_then(|()| stdout_writer.flush()) {
+        Ok(()) => 0,
+        Err(err) => utils::write_error("echo", &err, stderr_writer),
+    }
+}
+
+pub(crate) fn parse_echo_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Echo { args, redirections })
+}
@@ -6,4 +6,43 @@ pub enum CommandError {
     NotFound(String),
     #[error("Invalid arguments for command '{0}'")]
     InvalidArguments(String),
-}
\ No newline at end of file
+    #[error("{0}: unbound variable")]
+    Unbound(String),
+    // `${name?word}` with `name` unset, and the message to report.
+    #[error("{0}: {1}")]
+    Unset(String, String),
+    #[error("${{{0}}}: bad substitution")]
+    BadSubstitution(String),
+    #[error("{0}: {1}")]
+    NullParameter(String, String),
+    #[error("syntax error near unexpected token `{0}'")]
+    Syntax(String),
+}
+
+impl CommandError {
+    // Exit status reported for a command that failed before running.
+    pub fn status(&self) -> i32 {
+        match self {
+            CommandError::NotFound(_) => 127,
+            CommandError::InvalidArguments(_) | CommandError::Syntax(_) => 2,
+            CommandError::Unbound(_) | CommandError::Unset(..) | CommandError::BadSubstitution(_) | CommandError::NullParameter(..) => 1,
+        }
+    }
+
+    // Exit status of a non-interactive shell aborted by the error: as in bash,
+    // a parameter that is not set exits like a command that was not found.
+    pub fn exit_status(&self) -> i32 {
+        match self {
+            CommandError::Unbound(_) | CommandError::Unset(..) => 127,
+            _ => self.status(),
+        }
+    }
+
+    // Expansion errors abort a non-interactive shell.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            CommandError::Unbound(_) | CommandError::Unset(..) | CommandError::BadSubstitution(_) | CommandError::NullParameter(..)
+        )
+    }
+}
@@ -0,0 +1,254 @@
+// The `exec` builtin. With a command, it replaces the shell with that command;
+// without one, its redirections (`>log`, `2>&1`, `3<file`, `3>&-`...) apply to
+// the shell itself and stay in place for every command that follows.
+use std::io::{self, Write};
+use std::os::fd::{AsRawFd, IntoRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::Command as StdCommand;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+use super::state::STATE;
+use super::parser::{self, Token};
+use super::{coproc, exit, external, procsub, signals, utils, variables};
+use super::{Command, CommandError, Redirection, RedirectionMode, RedirectionTarget};
+
+const USAGE: &str = "exec: usage: exec [-cl] [-a name] [command [argument ...]] [redirection ...]";
+
+// Redirection operators `exec` takes, and the mode each opens its file with.
+const OPERATORS: &[(&str, RedirectionMode)] = &[
+    ("<&", RedirectionMode::Duplicate),
+    (">&", RedirectionMode::Duplicate),
+    (">>", RedirectionMode::Append),
+    (">|", RedirectionMode::Clobber),
+    ("<", RedirectionMode::Read),
+    (">", RedirectionMode::Overwrite),
+];
+
+// Held by the shell's other threads, such as the PATH scan for completion and the
+// git prompt worker, while they have files open. Their descriptors could be taken
+// for the user's, so `exec` holds it exclusively while it rearranges descriptors.
+static DESCRIPTORS: RwLock<()> = RwLock::new(());
+
+/// Keeps `exec` from rearranging the shell's descriptors while the guard lives.
+pub(crate) fn hold_descriptors() -> RwLockReadGuard<'static, ()> {
+    DESCRIPTORS.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+// A redirection of any of the shell's descriptors, not only the standard streams.
+#[derive(Debug)]
+pub(crate) struct FdRedirection {
+    fd: RawFd,
+    // What to open on `fd`, or None to close it.
+    source: Option<Redirection>,
+}
+
+// Whether `input` is an `exec` command.
+pub(crate) fn is_exec(input: &str) -> bool {
+    let input = input.trim_start();
+    input.strip_prefix("exec").is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+}
+
+pub(crate) fn parse_exec(input: &str) -> Result<Command, CommandError> {
+    let input = input.trim();
+    let tokens = parser::tokenize(input).map_err(|_| CommandError::Syntax("newline".to_string()))?;
+    let mut redirections = Vec::new();
+    let mut words = Vec::new();
+    // Skips the `exec` itself.
+    let mut i = 1;
+    while let Some((token, span)) = tokens.get(i) {
+        i += 1;
+        // A number right before a redirection operator is the descriptor it redirects.
+        let (number, op) = match (token, tokens.get(i)) {
+            (Token::Word(word), Some((Token::Operator(op), next)))
+                if next.start == span.end && parser::is_redirection(op) && word.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                i += 1;
+                (word.parse::<RawFd>().ok(), *op)
+            }
+            (Token::Word(word), _) => {
+                words.push(word.clone());
+                continue;
+            }
+            (Token::Operator(op), _) => (None, *op),
+            (Token::Newline, _) => return Err(CommandError::Syntax("newline".to_string())),
+        };
+        let Some(&(op, mode)) = OPERATORS.iter().find(|(known, _)| *known == op) else {
+            return Err(CommandError::Syntax(op.to_string()));
+        };
+        let target = match tokens.get(i) {
+            Some((Token::Word(target), _)) => target,
+            Some((Token::Operator(op), _)) => return Err(CommandError::Syntax(op.to_string())),
+            _ => return Err(CommandError::Syntax("newline".to_string())),
+        };
+        i += 1;
+        let file = utils::expand_word(target)?;
+        let fd = number.unwrap_or(if op.starts_with('<') { 0 } else { 1 });
+        let target = match fd {
+            0 => RedirectionTarget::Stdin,
+            2 => RedirectionTarget::Stderr,
+            _ if op.starts_with('<') => RedirectionTarget::Stdin,
+            _ => RedirectionTarget::Stdout,
+        };
+        let source = match mode {
+            RedirectionMode::Duplicate if file == "-" => None,
+            // `>&file` sends both stdout and stderr to file.
+            RedirectionMode::Duplicate if op == ">&" && fd == 1 && !file.chars().all(|c| c.is_ascii_digit()) => {
+                let stdout = Redirection { target, file, mode: RedirectionMode::Overwrite };
+                let stderr = Redirection {
+                    target: RedirectionTarget::Stderr,
+                    file: "1".to_string(),
+                    mode: RedirectionMode::Duplicate,
+                };
+                redirections.push(FdRedirection { fd: 1, source: Some(stdout) });
+                redirections.push(FdRedirection { fd: 2, source: Some(stderr) });
+                continue;
+            }
+            RedirectionMode::Duplicate if file.is_empty() || !file.chars().all(|c| c.is_ascii_digit()) => {
+                return Err(CommandError::NullParameter(file, "ambiguous redirect".to_string()));
+            }
+            _ => Some(Redirection { target, file, mode }),
+        };
+        redirections.push(FdRedirection { fd, source });
+    }
+    let args = utils::split_args(&words.join(" "))?;
+    Ok(Command::Exec { args, redirections })
+}
+
+/// Has `command` inherit the descriptors above 2 that `exec` opened.
+pub(crate) fn pass_descriptors(command: &mut StdCommand) {
+    let fds = STATE.read().unwrap().exec_fds.iter().copied().collect();
+    utils::inherit_fds(command, fds);
+}
+
+// Closes the shell's descriptor `fd`. A closed descriptor is not an error.
+fn close(fd: RawFd) {
+    STATE.write().unwrap().exec_fds.remove(&fd);
+    match coproc::take_fd(fd) {
+        Some(owned) => drop(owned),
+        // SAFETY: nothing else in the shell owns `fd`.
+        None => unsafe {
+            libc::close(fd);
+        },
+    }
+}
+
+// Makes the shell's descriptor `fd` what `redirection` says, for good. Unlike
+// the files the shell opens for itself, it is inherited by the commands it runs:
+// the standard streams directly, and others through `pass_descriptors`.
+fn apply(redirection: &FdRedirection) -> Result<(), (String, io::Error)> {
+    let fd = redirection.fd;
+    // What was written to the old stdout or stderr must not end up in the new one.
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    let Some(source) = &redirection.source else {
+        close(fd);
+        return Ok(());
+    };
+    let file = utils::open_file_for_redirection(source).map_err(|err| (source.file.clone(), err))?;
+    let opened = file.as_raw_fd();
+    if opened == fd {
+        // The file took the free descriptor itself, and is kept open.
+        let raw = file.into_raw_fd();
+        if raw > 2 {
+            STATE.write().unwrap().exec_fds.insert(raw);
+        } else {
+            // SAFETY: `raw` is the descriptor just opened, now owned by no one.
+            unsafe { libc::fcntl(raw, libc::F_SETFD, 0) };
+        }
+        return Ok(());
+    }
+    // A coprocess pipe on `fd` is replaced, so it must not be closed later.
+    let replaced = coproc::take_fd(fd).map(IntoRawFd::into_raw_fd);
+    // SAFETY: dup2 only replaces `fd`, which nothing else owns any more.
+    if unsafe { libc::dup2(opened, fd) } < 0 {
+        let err = io::Error::last_os_error();
+        if let Some(raw) = replaced {
+            // SAFETY: the pipe was not replaced after all, and nothing owns it.
+            unsafe { libc::close(raw) };
+        }
+        return Err((fd.to_string(), err));
+    }
+    if fd > 2 {
+        // SAFETY: `fd` was just made a copy that the shell owns.
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+        STATE.write().unwrap().exec_fds.insert(fd);
+    }
+    Ok(())
+}
+
+pub(crate) fn exec_cmd(args: &[String], redirections: &[FdRedirection]) -> i32 {
+    let (mut name, mut clear, mut login) = (None, false, false);
+    let mut operands = args;
+    while let Some(option) = operands.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+        operands = &operands[1..];
+        if option == "--" {
+            break;
+        }
+        for flag in option.chars().skip(1) {
+            match flag {
+                'c' => clear = true,
+                'l' => login = true,
+                'a' => {
+                    let Some((arg0, rest)) = operands.split_first() else {
+                        eprintln!("exec: -a: option requires an argument");
+                        eprintln!("{}", USAGE);
+                        return 2;
+                    };
+                    name = Some(arg0.clone());
+                    operands = rest;
+                }
+                _ => {
+                    eprintln!("exec: -{}: invalid option", flag);
+                    eprintln!("{}", USAGE);
+                    return 2;
+                }
+            }
+        }
+    }
+    // The command must be found before the shell's descriptors are touched.
+    let path = match operands.first() {
+        None => None,
+        Some(cmd) if cmd.contains('/') => Some(cmd.clone()),
+        Some(cmd) => match external::find_in_path(cmd) {
+            Some(path) => Some(path),
+            None => {
+                eprintln!("exec: {}: not found", cmd);
+                return cannot_run(127);
+            }
+        },
+    };
+    let _descriptors = DESCRIPTORS.write().unwrap_or_else(PoisonError::into_inner);
+    for redirection in redirections {
+        if let Err((file, err)) = apply(redirection) {
+            eprintln!("{}: {}", file, utils::describe_io_error(&err));
+            return 1;
+        }
+    }
+    let (Some(path), Some((cmd, args))) = (path, operands.split_first()) else {
+        return 0;
+    };
+    let mut arg0 = name.unwrap_or_else(|| cmd.clone());
+    if login {
+        arg0.insert(0, '-');
+    }
+    let mut command = StdCommand::new(path);
+    command.arg0(arg0).args(args);
+    variables::export_to(&mut command);
+    if clear {
+        command.env_clear();
+    }
+    pass_descriptors(&mut command);
+    procsub::pass_pipes(&mut command);
+    signals::restore_default_signals(&mut command);
+    // exec only returns if the command could not be run.
+    let err = command.exec();
+    eprintln!("exec: {}: {}", cmd, utils::describe_io_error(&err));
+    cannot_run(if err.kind() == io::ErrorKind::NotFound { 127 } else { 126 })
+}
+
+// As in bash, a shell that is not interactive exits when exec cannot run its command.
+fn cannot_run(status: i32) -> i32 {
+    if !STATE.read().unwrap().interactive {
+        exit::exit_cmd(status);
+    }
+    status
+}
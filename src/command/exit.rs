@@ -1,12 +1,15 @@
-use super::{Command, CommandError};
+use super::state::STATE;
+use super::{trap, Command, CommandError};
 
-pub(crate) fn exit_cmd(code: i32) {
+pub(crate) fn exit_cmd(code: i32) -> ! {
+    trap::run_exit_trap(code);
     std::process::exit(code);
 }
 
 pub(crate) fn parse_exit_cmd(args: &str) -> Result<Command, CommandError> {
     let value = match args.is_empty() {
-        true => 0,
+        // A bare `exit` keeps the status of the last command.
+        true => STATE.read().unwrap().last_status,
         false => args.parse::<i32>().unwrap_or(1),
     };
     Ok(Command::Exit(value))
@@ -0,0 +1,673 @@
+use super::pattern::Pattern;
+use super::printf::{self, EscapeMode};
+use super::state::STATE;
+use super::variables::{self, Attribute};
+use super::{arithmetic, declare, dirstack, exec, set, shopt, signals, special, utils, CommandError};
+use super::set::ShellOption;
+use super::shopt::ShoptOption;
+use std::os::unix::process::CommandExt;
+use std::process::{Command as StdCommand, Stdio};
+
+// A child shell that runs `cmd` with the options, variables, positional
+// parameters and directory stack of this one.
+pub(crate) fn subshell(cmd: &str) -> Option<StdCommand> {
+    let shell = std::env::current_exe().ok()?;
+    let mut command = StdCommand::new(shell);
+    let name = special::parameter("0").unwrap_or_default();
+    let positional = STATE.read().unwrap().positional.clone();
+    variables::export_to(&mut command);
+    command
+        .arg0(&name)
+        .envs([special::subshell_env(), declare::subshell_env(), dirstack::subshell_env()])
+        .args(set::option_args())
+        .args(shopt::option_args())
+        .arg("-c")
+        .arg(cmd)
+        .arg(name)
+        .args(positional)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    exec::pass_descriptors(&mut command);
+    signals::restore_default_signals(&mut command);
+    Some(command)
+}
+
+// Runs `cmd` in a child shell and returns what it printed, minus trailing newlines.
+pub(crate) fn command_substitution(cmd: &str) -> String {
+    let Some(mut command) = subshell(cmd) else {
+        return String::new();
+    };
+    let output = command.output();
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .trim_end_matches('\n')
+            .to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+// Reads the name of the parameter following a `$` at `chars[i]`.
+// Returns the name and the index just past it, or None if no parameter starts there.
+pub(crate) fn parameter_name(chars: &[char], i: usize) -> Option<(String, usize)> {
+    match chars.get(i) {
+        Some('{') => {
+            // Braces may nest, as in `${a:-${b}}`.
+            let mut depth = 0;
+            let end = chars[i..].iter().position(|&c| {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })? + i;
+            Some((chars[i + 1..end].iter().collect(), end + 1))
+        }
+        Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+            let len = chars[i..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                .count();
+            Some((chars[i..i + len].iter().collect(), i + len))
+        }
+        Some(c) if c.is_ascii_digit() || "?$!#@*-".contains(*c) => Some((c.to_string(), i + 1)),
+        _ => None,
+    }
+}
+
+// The value of a parameter, or None if it is unset.
+pub(crate) fn parameter_value(name: &str) -> Option<String> {
+    match name {
+        "-" => return Some(set::flags()),
+        "$" | "!" | "0" => return special::parameter(name),
+        _ if variables::valid_name(name) => return variables::get(name),
+        _ => {}
+    }
+    let shell = STATE.read().unwrap();
+    match name {
+        "?" => Some(shell.last_status.to_string()),
+        "#" => Some(shell.positional.len().to_string()),
+        "@" => Some(shell.positional.join(" ")),
+        "*" => Some(join_star(&shell.positional)),
+        _ => match name.parse::<usize>() {
+            Ok(n) if n > 0 => shell.positional.get(n - 1).cloned(),
+            _ => None,
+        },
+    }
+}
+
+// `"$*"` and `"${name[*]}"` join their values with the first character of IFS.
+fn join_star(values: &[String]) -> String {
+    let ifs = variables::get("IFS").unwrap_or_else(|| " ".to_string());
+    values.join(&ifs.chars().next().map(String::from).unwrap_or_default())
+}
+
+/// Like `parameter_value`, but `name` may also be an array element `name[subscript]`.
+pub(crate) fn lookup(name: &str) -> Result<Option<String>, CommandError> {
+    let Some((array, subscript)) = variables::split_subscript(name) else {
+        return Ok(parameter_value(name));
+    };
+    let values = variables::values(array);
+    if matches!(subscript, "@" | "*") && values.is_empty() && !variables::is_array(array) {
+        return Ok(None);
+    }
+    match subscript {
+        "@" => Ok(Some(values.join(" "))),
+        "*" => Ok(Some(join_star(&values))),
+        // A bad subscript is reported but, unlike other expansion errors, does not stop the command.
+        _ => match variables::element(array, &utils::expand_word(subscript)?) {
+            Ok(value) => Ok(value),
+            Err(err) => {
+                eprintln!("{}", err);
+                Ok(None)
+            }
+        },
+    }
+}
+
+// Like `lookup`, but an unset parameter is an error under `set -u`.
+fn checked_value(name: &str) -> Result<Option<String>, CommandError> {
+    let value = lookup(name)?;
+    let all = matches!(name, "@" | "*") || name.ends_with("[@]") || name.ends_with("[*]");
+    if value.is_none() && !all && set::enabled(ShellOption::Nounset) {
+        return Err(CommandError::Unbound(name.to_string()));
+    }
+    Ok(value)
+}
+
+// Length of the parameter name at the start of `expr`, including an array subscript.
+fn name_length(expr: &str) -> usize {
+    match expr.chars().next() {
+        Some(c) if c.is_ascii_digit() => expr.chars().take_while(char::is_ascii_digit).count(),
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            let len = expr.chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '_').count();
+            match expr[len..].strip_prefix('[').and_then(|rest| rest.find(']')) {
+                Some(close) => len + close + 2,
+                None => len,
+            }
+        }
+        Some(c) if "?$!#@*-".contains(c) => 1,
+        _ => 0,
+    }
+}
+
+// An arithmetic operand of `${name:offset:length}`.
+fn integer(raw: &str) -> Result<i64, CommandError> {
+    arithmetic::evaluate(&utils::expand_word(raw)?)
+}
+
+// The `offset:length` of `${name:offset:length}`, if `rest` is one: `${name:-word}`
+// and the like are other operators, and a bare `${name:}` is a bad substitution.
+fn substring_spec(rest: &str) -> Option<&str> {
+    rest.strip_prefix(':').filter(|spec| !spec.is_empty() && !spec.starts_with(['-', '=', '+', '?']))
+}
+
+fn slice_bounds(spec: &str) -> Result<(i64, Option<i64>), CommandError> {
+    match spec.split_once(':') {
+        Some((offset, length)) => Ok((integer(offset)?, Some(integer(length)?))),
+        None => Ok((integer(spec)?, None)),
+    }
+}
+
+fn negative_length(length: i64) -> CommandError {
+    CommandError::NullParameter(length.to_string(), "substring expression < 0".to_string())
+}
+
+// `${name:offset:length}` of a string, counting characters. A negative offset
+// counts back from the end, and a negative length leaves that many characters off it.
+fn substring(value: &str, spec: &str) -> Result<String, CommandError> {
+    let (offset, length) = slice_bounds(spec)?;
+    let chars: Vec<char> = value.chars().collect();
+    let len = chars.len() as i64;
+    let start = if offset < 0 { len + offset } else { offset };
+    if start < 0 || start > len {
+        return Ok(String::new());
+    }
+    let end = match length {
+        Some(length) if length < 0 => len + length,
+        Some(length) => (start + length).min(len),
+        None => len,
+    };
+    if end < start {
+        return Err(negative_length(end - len));
+    }
+    Ok(chars[start as usize..end as usize].iter().collect())
+}
+
+// `${@:offset:length}` selects from `$0` and the positional parameters.
+fn positional_slice(spec: &str) -> Result<Vec<String>, CommandError> {
+    let (offset, length) = slice_bounds(spec)?;
+    let mut values = vec![special::parameter("0").unwrap_or_default()];
+    values.extend(STATE.read().unwrap().positional.iter().cloned());
+    let start = if offset < 0 { values.len() as i64 + offset } else { offset };
+    if start < 0 {
+        return Ok(Vec::new());
+    }
+    let selected = values.into_iter().skip(start as usize);
+    match length {
+        Some(length) if length < 0 => Err(negative_length(length)),
+        Some(length) => Ok(selected.take(length as usize).collect()),
+        None => Ok(selected.collect()),
+    }
+}
+
+// Compiles the pattern of an operator such as `${name#pattern}`; quoted parts match literally.
+fn compile(raw: &str) -> Result<Option<Pattern>, CommandError> {
+    let pattern = utils::expand_pattern(raw)?;
+    let extglob = shopt::enabled(ShoptOption::Extglob);
+    Ok((!pattern.is_empty()).then(|| Pattern::new(&pattern, extglob, false)))
+}
+
+// Splits `text` at the first `separator` that is not quoted, escaped or inside a nested expansion.
+fn split_unquoted(text: &str, separator: char) -> (&str, Option<&str>) {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut depth = 0usize;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if quote != Some('\'') => escaped = true,
+            '\'' | '"' if quote.is_none() => quote = Some(c),
+            _ if quote == Some(c) => quote = None,
+            '{' if quote != Some('\'') => depth += 1,
+            '}' if quote != Some('\'') => depth = depth.saturating_sub(1),
+            _ if c == separator && quote.is_none() && depth == 0 => {
+                return (&text[..i], Some(&text[i + c.len_utf8()..]));
+            }
+            _ => {}
+        }
+    }
+    (text, None)
+}
+
+// `${name#pattern}`, `##`, `%` and `%%`: removes the shortest or longest prefix
+// or suffix that matches.
+fn remove_affix(value: &str, operator: &str, raw: &str) -> Result<String, CommandError> {
+    let pattern = compile(raw)?;
+    let chars: Vec<char> = value.chars().collect();
+    let n = chars.len();
+    let matches = |start: usize, end: usize| {
+        let text: String = chars[start..end].iter().collect();
+        pattern.as_ref().map_or(text.is_empty(), |pattern| pattern.matches(&text))
+    };
+    let cut = match operator {
+        "#" => (0..=n).find(|&end| matches(0, end)).map(|end| (end, n)),
+        "##" => (0..=n).rev().find(|&end| matches(0, end)).map(|end| (end, n)),
+        "%" => (0..=n).rev().find(|&start| matches(start, n)).map(|start| (0, start)),
+        _ => (0..=n).find(|&start| matches(start, n)).map(|start| (0, start)),
+    };
+    Ok(match cut {
+        Some((start, end)) => chars[start..end].iter().collect(),
+        None => value.to_string(),
+    })
+}
+
+// `${name/pattern/string}` replaces the longest match of the pattern: `//`
+// replaces every match, and `/#` and `/%` only one at the start or the end. An
+// unquoted `&` in the string stands for the matched text.
+fn substitute(value: &str, body: &str) -> Result<String, CommandError> {
+    let (anchor, body) = match body.chars().next() {
+        Some(c @ ('/' | '#' | '%')) => (Some(c), &body[1..]),
+        _ => (None, body),
+    };
+    let (raw, replacement) = split_unquoted(body, '/');
+    let pattern = compile(raw)?;
+    let mut pieces = Vec::new();
+    let mut rest = Some(replacement.unwrap_or_default());
+    while let Some(text) = rest {
+        let (piece, next) = split_unquoted(text, '&');
+        pieces.push(utils::expand_word(piece)?);
+        rest = next;
+    }
+    let chars: Vec<char> = value.chars().collect();
+    let n = chars.len();
+    let text = |start: usize, end: usize| chars[start..end].iter().collect::<String>();
+    let matches = |start: usize, end: usize| {
+        pattern.as_ref().map_or(start == end, |pattern| pattern.matches(&text(start, end)))
+    };
+    match anchor {
+        Some('#') => Ok(match (0..=n).rev().find(|&end| matches(0, end)) {
+            Some(end) => pieces.join(&text(0, end)) + &text(end, n),
+            None => value.to_string(),
+        }),
+        Some('%') => Ok(match (0..=n).find(|&start| matches(start, n)) {
+            Some(start) => text(0, start) + &pieces.join(&text(start, n)),
+            None => value.to_string(),
+        }),
+        _ if pattern.is_none() => Ok(value.to_string()),
+        _ => {
+            let mut out = String::new();
+            let mut i = 0;
+            while i < n {
+                match (i + 1..=n).rev().find(|&end| matches(i, end)) {
+                    Some(end) => {
+                        out.push_str(&pieces.join(&text(i, end)));
+                        i = end;
+                        if anchor.is_none() {
+                            break;
+                        }
+                    }
+                    None => {
+                        out.push(chars[i]);
+                        i += 1;
+                    }
+                }
+            }
+            Ok(out + &text(i, n))
+        }
+    }
+}
+
+// `${name^pattern}` and `${name,pattern}` convert the first character to upper
+// or lower case if it matches the pattern (any character by default); `^^` and
+// `,,` convert every matching character.
+fn modify_case(value: &str, operator: &str, raw: &str) -> Result<String, CommandError> {
+    let pattern = compile(raw)?;
+    let upper = operator.starts_with('^');
+    let all = operator.len() == 2;
+    Ok(value
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let selected = (all || i == 0) && pattern.as_ref().map_or(true, |pattern| pattern.matches(&c.to_string()));
+            match selected {
+                true if upper => c.to_uppercase().collect(),
+                true => c.to_lowercase().collect(),
+                false => c.to_string(),
+            }
+        })
+        .collect())
+}
+
+// `${name@Q}` quotes the value for reuse as input, `@E` expands backslash escapes
+// as `$'...'` does, `@U` and `@L` convert it to upper or lower case, and `@u`
+// converts its first character to upper case.
+fn transform(value: &str, letter: &str, expr: &str) -> Result<String, CommandError> {
+    match letter {
+        "Q" if value.chars().any(char::is_control) => Ok(printf::quote(value)),
+        "Q" => Ok(format!("'{}'", value.replace('\'', "'\\''"))),
+        "E" => {
+            let mut out = Vec::new();
+            printf::unescape(value, EscapeMode::Ansi, &mut out);
+            Ok(String::from_utf8_lossy(&out).into_owned())
+        }
+        "U" => Ok(value.to_uppercase()),
+        "L" => Ok(value.to_lowercase()),
+        "u" => modify_case(value, "^", ""),
+        _ => Err(CommandError::BadSubstitution(expr.to_string())),
+    }
+}
+
+// Applies an operator that changes a value: pattern removal, substitution, case
+// modification or a transformation. `expr` is the whole expansion, for errors.
+fn operate(value: &str, rest: &str, expr: &str) -> Result<String, CommandError> {
+    if let Some(body) = rest.strip_prefix('/') {
+        return substitute(value, body);
+    }
+    if let Some(letter) = rest.strip_prefix('@') {
+        return transform(value, letter, expr);
+    }
+    for operator in ["##", "#", "%%", "%"] {
+        if let Some(raw) = rest.strip_prefix(operator) {
+            return remove_affix(value, operator, raw);
+        }
+    }
+    for operator in ["^^", "^", ",,", ","] {
+        if let Some(raw) = rest.strip_prefix(operator) {
+            return modify_case(value, operator, raw);
+        }
+    }
+    Err(CommandError::BadSubstitution(expr.to_string()))
+}
+
+fn is_operator(rest: &str) -> bool {
+    rest.starts_with(['/', '#', '%', '^', ',', '@'])
+}
+
+// For `$@`, `$*`, `${name[@]}` and `${name[*]}`, the values selected by `rest`:
+// all of them, a slice, or each changed by an operator. None for other names,
+// and for the `-`, `=`, `+` and `?` operators, which apply to the joined value.
+fn each_value(name: &str, rest: &str, expr: &str) -> Result<Option<Vec<String>>, CommandError> {
+    let positional = matches!(name, "@" | "*");
+    let array = variables::split_subscript(name).filter(|(_, subscript)| matches!(*subscript, "@" | "*"));
+    if !positional && array.is_none() {
+        return Ok(None);
+    }
+    if let Some(spec) = substring_spec(rest) {
+        return match array {
+            Some((array, _)) => {
+                let (offset, length) = slice_bounds(spec)?;
+                Ok(Some(variables::slice(array, offset, length)?))
+            }
+            None => Ok(Some(positional_slice(spec)?)),
+        };
+    }
+    if !rest.is_empty() && !is_operator(rest) {
+        return Ok(None);
+    }
+    let values = match array {
+        Some((array, _)) => variables::values(array),
+        None => STATE.read().unwrap().positional.clone(),
+    };
+    if rest.is_empty() {
+        return Ok(Some(values));
+    }
+    values.iter().map(|value| operate(value, rest, expr)).collect::<Result<_, _>>().map(Some)
+}
+
+// For `${!prefix*}` and `${!prefix@}`, the names of the variables that start with the prefix.
+fn prefixed_names(target: &str) -> Option<(Vec<String>, bool)> {
+    let prefix = target.strip_suffix(['*', '@']).filter(|prefix| variables::valid_name(prefix))?;
+    let names = variables::names().into_iter().filter(|name| name.starts_with(prefix)).collect();
+    Some((names, target.ends_with('*')))
+}
+
+// `${!name...}` expands `${value...}` instead, where value is that of `name`.
+fn indirect(target: &str) -> Result<String, CommandError> {
+    let length = name_length(target);
+    if length == 0 {
+        return Err(CommandError::BadSubstitution(format!("!{}", target)));
+    }
+    let (name, rest) = target.split_at(length);
+    match checked_value(name)? {
+        Some(reference) if name_length(&reference) == reference.len() && !reference.is_empty() => Ok(reference + rest),
+        Some(reference) if !reference.is_empty() => Err(CommandError::BadSubstitution(reference)),
+        _ => Err(CommandError::NullParameter(name.to_string(), "invalid indirect expansion".to_string())),
+    }
+}
+
+/// The values of an expansion that stands for several words: `$@`, `${name[@]}`,
+/// `${!name[@]}`, `${!prefix@}` and their slices and operators, and, unless
+/// quoted, the `*` forms. None for any other expansion.
+pub(crate) fn expand_fields(expr: &str, quoted: bool) -> Result<Option<Vec<String>>, CommandError> {
+    if let Some(target) = expr.strip_prefix('!').filter(|target| !target.is_empty()) {
+        if let Some((array, subscript @ ("@" | "*"))) = variables::split_subscript(target) {
+            return Ok((subscript == "@" || !quoted).then(|| variables::keys(array)));
+        }
+        if let Some((names, star)) = prefixed_names(target) {
+            return Ok((!star || !quoted).then_some(names));
+        }
+        if variables::has_attribute(target, Attribute::Nameref) {
+            return Ok(None);
+        }
+        return expand_fields(&indirect(target)?, quoted);
+    }
+    let length = name_length(expr);
+    let (name, rest) = expr.split_at(length);
+    if quoted && (name == "*" || name.ends_with("[*]")) {
+        return Ok(None);
+    }
+    each_value(name, rest, expr)
+}
+
+/// Expands the inside of `${...}` (or a plain parameter name): the value itself,
+/// `${#name}`, array subscripts, slices and substrings, indirection, pattern
+/// removal and substitution, case modification, `@` transformations, and the
+/// POSIX `-`, `=`, `+` and `?` operators, with or without `:`.
+pub(crate) fn expand_parameter(expr: &str) -> Result<String, CommandError> {
+    if let Some(name) = expr.strip_prefix('#').filter(|name| !name.is_empty()) {
+        if name_length(name) != name.len() {
+            return Err(CommandError::BadSubstitution(expr.to_string()));
+        }
+        if matches!(name, "@" | "*") {
+            return Ok(STATE.read().unwrap().positional.len().to_string());
+        }
+        if let Some((array, "@" | "*")) = variables::split_subscript(name) {
+            return Ok(variables::values(array).len().to_string());
+        }
+        return Ok(checked_value(name)?.unwrap_or_default().chars().count().to_string());
+    }
+    if let Some(target) = expr.strip_prefix('!').filter(|target| !target.is_empty()) {
+        // `${!name[@]}` lists the indices or keys of an array.
+        if let Some((array, "@" | "*")) = variables::split_subscript(target) {
+            return Ok(variables::keys(array).join(" "));
+        }
+        if let Some((names, star)) = prefixed_names(target) {
+            return Ok(if star { join_star(&names) } else { names.join(" ") });
+        }
+        // For a `declare -n` reference, `${!name}` is the name it refers to.
+        if variables::has_attribute(target, Attribute::Nameref) {
+            return Ok(variables::resolve(target));
+        }
+        return expand_parameter(&indirect(target)?);
+    }
+
+    let length = name_length(expr);
+    if length == 0 {
+        return Err(CommandError::BadSubstitution(expr.to_string()));
+    }
+    let (name, rest) = expr.split_at(length);
+    if rest.is_empty() {
+        return Ok(checked_value(name)?.unwrap_or_default());
+    }
+    if let Some(values) = each_value(name, rest, expr)? {
+        let star = name == "*" || name.ends_with("[*]");
+        return Ok(if star { join_star(&values) } else { values.join(" ") });
+    }
+    if let Some(spec) = substring_spec(rest) {
+        return substring(&checked_value(name)?.unwrap_or_default(), spec);
+    }
+    if is_operator(rest) {
+        return operate(&checked_value(name)?.unwrap_or_default(), rest, expr);
+    }
+
+    let (check_null, rest) = match rest.strip_prefix(':') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let mut chars = rest.chars();
+    let operator = chars.next();
+    let word = chars.as_str();
+    let value = lookup(name)?;
+    // With `:` an empty value counts as unset.
+    let set = value.as_ref().is_some_and(|value| !(check_null && value.is_empty()));
+
+    match operator {
+        Some('-') if set => Ok(value.unwrap_or_default()),
+        Some('-') => utils::expand_word(word),
+        Some('=') if set => Ok(value.unwrap_or_default()),
+        Some('=') => {
+            if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                return Err(CommandError::NullParameter(format!("${}", name), "cannot assign in this way".to_string()));
+            }
+            let word = utils::expand_word(word)?;
+            match variables::split_subscript(name) {
+                Some((array, subscript)) => variables::set_element(array, &utils::expand_word(subscript)?, &word, false)?,
+                None => variables::set(name, &word)?,
+            }
+            Ok(word)
+        }
+        Some('+') if set => utils::expand_word(word),
+        Some('+') => Ok(String::new()),
+        Some('?') if set => Ok(value.unwrap_or_default()),
+        Some('?') => {
+            let message = match utils::expand_word(word)? {
+                message if message.is_empty() => "parameter null or not set".to_string(),
+                message => message,
+            };
+            Err(CommandError::Unset(name.to_string(), message))
+        }
+        _ => Err(CommandError::BadSubstitution(expr.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(expr: &str) -> String {
+        expand_parameter(expr).unwrap()
+    }
+
+    fn error(expr: &str) -> String {
+        expand_parameter(expr).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn pattern_removal() {
+        variables::set("pe_test_path", "/usr/lib/libfoo.so.1").unwrap();
+        assert_eq!(expand("pe_test_path#*/"), "usr/lib/libfoo.so.1");
+        assert_eq!(expand("pe_test_path##*/"), "libfoo.so.1");
+        assert_eq!(expand("pe_test_path%.*"), "/usr/lib/libfoo.so");
+        assert_eq!(expand("pe_test_path%%.*"), "/usr/lib/libfoo");
+        assert_eq!(expand("pe_test_path#x"), "/usr/lib/libfoo.so.1");
+        assert_eq!(expand("pe_test_path#"), "/usr/lib/libfoo.so.1");
+        assert_eq!(expand("pe_test_path##'/usr'"), "/lib/libfoo.so.1");
+    }
+
+    #[test]
+    fn substitution() {
+        variables::set("pe_test_sub", "/usr/lib/libfoo.so.1").unwrap();
+        assert_eq!(expand("pe_test_sub/lib/LIB"), "/usr/LIB/libfoo.so.1");
+        assert_eq!(expand("pe_test_sub//lib/LIB"), "/usr/LIB/LIBfoo.so.1");
+        assert_eq!(expand("pe_test_sub/#\\/usr/"), "/lib/libfoo.so.1");
+        assert_eq!(expand("pe_test_sub/%1/2"), "/usr/lib/libfoo.so.2");
+        assert_eq!(expand("pe_test_sub/lib"), "/usr//libfoo.so.1");
+        assert_eq!(expand("pe_test_sub//[ol]/<&>"), "/usr/<l>ib/<l>ibf<o><o>.s<o>.1");
+        assert_eq!(expand("pe_test_sub//o/\\&"), "/usr/lib/libf&&.s&.1");
+    }
+
+    #[test]
+    fn case_modification_and_transformations() {
+        variables::set("pe_test_case", "hello world").unwrap();
+        assert_eq!(expand("pe_test_case^"), "Hello world");
+        assert_eq!(expand("pe_test_case^^"), "HELLO WORLD");
+        assert_eq!(expand("pe_test_case^^[lo]"), "heLLO wOrLd");
+        assert_eq!(expand("pe_test_case@u"), "Hello world");
+        assert_eq!(expand("pe_test_case@U"), "HELLO WORLD");
+        assert_eq!(expand("pe_test_case@Q"), "'hello world'");
+        variables::set("pe_test_upper", "HELLO").unwrap();
+        assert_eq!(expand("pe_test_upper,"), "hELLO");
+        assert_eq!(expand("pe_test_upper,,"), "hello");
+        assert_eq!(expand("pe_test_upper@L"), "hello");
+        variables::set("pe_test_quote", "it's").unwrap();
+        assert_eq!(expand("pe_test_quote@Q"), "'it'\\''s'");
+        variables::set("pe_test_escape", "a\\tb").unwrap();
+        assert_eq!(expand("pe_test_escape@E"), "a\tb");
+        assert_eq!(error("pe_test_case@x"), "${pe_test_case@x}: bad substitution");
+    }
+
+    #[test]
+    fn substrings_and_lengths() {
+        variables::set("pe_test_sub_str", "hello world").unwrap();
+        assert_eq!(expand("pe_test_sub_str:6"), "world");
+        assert_eq!(expand("pe_test_sub_str: -5:3"), "wor");
+        assert_eq!(expand("pe_test_sub_str:1:-2"), "ello wor");
+        assert_eq!(expand("pe_test_sub_str:20"), "");
+        assert_eq!(expand("pe_test_sub_str:1+1:2*2"), "llo ");
+        assert_eq!(expand("#pe_test_sub_str"), "11");
+        assert_eq!(error("pe_test_sub_str:1:-20"), "-20: substring expression < 0");
+    }
+
+    #[test]
+    fn defaults_alternatives_and_errors() {
+        variables::set("pe_test_empty", "").unwrap();
+        variables::set("pe_test_full", "x").unwrap();
+        assert_eq!(expand("pe_test_unset-def"), "def");
+        assert_eq!(expand("pe_test_empty-def"), "");
+        assert_eq!(expand("pe_test_empty:-def"), "def");
+        assert_eq!(expand("pe_test_full:+alt"), "alt");
+        assert_eq!(expand("pe_test_empty+alt"), "alt");
+        assert_eq!(expand("pe_test_empty:+alt"), "");
+        assert_eq!(expand("pe_test_unset+alt"), "");
+        assert_eq!(expand("pe_test_assigned:=set"), "set");
+        assert_eq!(variables::get("pe_test_assigned").as_deref(), Some("set"));
+        assert_eq!(error("pe_test_unset:?"), "pe_test_unset: parameter null or not set");
+        assert_eq!(error("pe_test_empty:?gone"), "pe_test_empty: gone");
+        assert_eq!(expand("pe_test_empty?gone"), "");
+        assert_eq!(error("1=x"), "$1: cannot assign in this way");
+        assert_eq!(error("pe_test_full:"), "${pe_test_full:}: bad substitution");
+        // As in bash, a script stops with 127 on an unset parameter, but 1 on a bad substitution.
+        assert_eq!(expand_parameter("pe_test_unset?").unwrap_err().exit_status(), 127);
+        assert_eq!(expand_parameter("pe_test_full:").unwrap_err().exit_status(), 1);
+    }
+
+    #[test]
+    fn indirection() {
+        variables::set("pe_test_target", "hello").unwrap();
+        variables::set("pe_test_ref", "pe_test_target").unwrap();
+        variables::set("pe_test_bad_ref", "not a name").unwrap();
+        assert_eq!(expand("!pe_test_ref"), "hello");
+        assert_eq!(expand("!pe_test_ref^^"), "HELLO");
+        assert_eq!(error("!pe_test_bad_ref"), "${not a name}: bad substitution");
+        assert_eq!(error("!pe_test_unset_ref"), "pe_test_unset_ref: invalid indirect expansion");
+    }
+
+    #[test]
+    fn operators_apply_to_each_array_value() {
+        let values = ["one", "two", "three"].map(String::from).to_vec();
+        variables::set_array("pe_test_array", variables::indexed(values)).unwrap();
+        let fields = |expr: &str| expand_fields(expr, true).unwrap().unwrap();
+        assert_eq!(fields("pe_test_array[@]^"), ["One", "Two", "Three"]);
+        assert_eq!(fields("pe_test_array[@]/o/0"), ["0ne", "tw0", "three"]);
+        assert_eq!(fields("pe_test_array[@]: -1"), ["three"]);
+        assert_eq!(fields("!pe_test_array[@]"), ["0", "1", "2"]);
+        assert_eq!(expand_fields("pe_test_array[*]", true).unwrap(), None);
+        assert_eq!(expand("pe_test_array[*]:1"), "two three");
+        assert_eq!(expand("#pe_test_array[@]"), "3");
+        assert_eq!(expand("#pe_test_array[1]"), "3");
+    }
+}
@@ -1,56 +1,60 @@
 use super::Command::{self, *};
-use super::{utils, Redirection, RedirectionTarget};
-use std::{io, io::Write, process::{Command as StdCommand, Stdio}};
+use super::{exec, procsub, signals, utils, variables, Redirection, RedirectionTarget};
+use std::process::{Command as StdCommand, Stdio};
 use std::os::unix::fs::PermissionsExt;
 use std::os::unix::process::CommandExt;
 
-pub(crate) fn external_cmd(cmd: &Command) {
+pub(crate) fn external_cmd(cmd: &Command) -> i32 {
     let External { cmd: cmd_name, args, path, redirections } = cmd else {
         eprintln!("Unexpected error occurred while executing external command");
-        return;
+        return 1;
     };
 
     let mut command = StdCommand::new(path);
     command.arg0(cmd_name).args(args.iter());
+    variables::export_to(&mut command);
 
-    let mut stdout_redirected = false;
-    let mut stderr_redirected = false;
-
-    for redirection in redirections {
-        let file = utils::open_file_for_redirection(redirection)
-            .expect("Failed to open redirection file");
-
+    let opened = match utils::open_redirections(redirections) {
+        Ok(opened) => opened,
+        Err((redirection, err)) => {
+            eprintln!("{}: {}", redirection.file, utils::describe_io_error(&err));
+            return 1;
+        }
+    };
+    for (target, file) in opened {
         let stdio = Stdio::from(file);
-
-        match redirection.target {
+        match target {
+            RedirectionTarget::Stdin => {
+                command.stdin(stdio);
+            }
             RedirectionTarget::Stdout => {
                 command.stdout(stdio);
-                stdout_redirected = true;
             }
             RedirectionTarget::Stderr => {
                 command.stderr(stdio);
-                stderr_redirected = true;
             }
         }
     }
 
-    // Fallback to piped output if not redirected, so you can print to console
-    if !stdout_redirected { command.stdout(Stdio::piped()); }
-    if !stderr_redirected { command.stderr(Stdio::piped()); }
-
-
-    let output = command.output().expect("failed to execute process");
+    exec::pass_descriptors(&mut command);
+    procsub::pass_pipes(&mut command);
+    // The child shares the terminal, so it must react to ^C and ^Z itself.
+    signals::restore_default_signals(&mut command);
+    let child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("{}: {}", cmd_name, err);
+            return 126;
+        }
+    };
 
-    if !stdout_redirected {
-        io::stdout().write_all(&output.stdout).unwrap();
-    }
-    if !stderr_redirected {
-        io::stderr().write_all(&output.stderr).unwrap();
-    }
+    let description = std::iter::once(cmd_name).chain(args.iter()).cloned().collect::<Vec<_>>().join(" ");
+    signals::wait_foreground(child.id(), &description)
 }
 
-pub(crate) fn parse_external_cmd(cmd: &str, args_tokens: Vec<String>, redirections: Vec<Redirection>) -> Option<Command> {
-    let path_env = std::env::var_os("PATH")?;
+// Full path of the executable `cmd` in PATH.
+pub(crate) fn find_in_path(cmd: &str) -> Option<String> {
+    let path_env = variables::get("PATH")?;
     std::env::split_paths(&path_env)
         .map(|p| p.join(cmd))
         .find(|full_path| {
@@ -58,10 +62,14 @@ pub(crate) fn parse_external_cmd(cmd: &str, args_tokens: Vec<String>, redirectio
                 .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
                 .unwrap_or(false)
         })
-        .map(|path_buf| External {
-            cmd: cmd.to_string(),
-            args: args_tokens,
-            path: path_buf.to_string_lossy().to_string(),
-            redirections,
-        })
+        .map(|path_buf| path_buf.to_string_lossy().to_string())
+}
+
+pub(crate) fn parse_external_cmd(cmd: &str, args_tokens: Vec<String>, redirections: Vec<Redirection>) -> Option<Command> {
+    find_in_path(cmd).map(|path| External {
+        cmd: cmd.to_string(),
+        args: args_tokens,
+        path,
+        redirections,
+    })
 }
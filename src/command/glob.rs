@@ -0,0 +1,98 @@
+// Pathname expansion of words containing pattern characters.
+use std::fs;
+use std::path::Path;
+use super::pattern::{self, Pattern};
+use super::shopt::{self, ShoptOption};
+
+fn join(base: &str, name: &str) -> String {
+    if base.is_empty() {
+        name.to_string()
+    } else if base.ends_with('/') {
+        format!("{}{}", base, name)
+    } else {
+        format!("{}/{}", base, name)
+    }
+}
+
+fn entries(dir: &str) -> Vec<String> {
+    let dir = if dir.is_empty() { "." } else { dir };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries.flatten().filter_map(|e| e.file_name().into_string().ok()).collect();
+    names.sort();
+    names
+}
+
+fn is_dir(path: &str) -> bool {
+    Path::new(if path.is_empty() { "." } else { path }).is_dir()
+}
+
+// What `**` matches under `base`: every file and directory when it ends the
+// pattern, otherwise `base` itself and every directory below it.
+fn globstar(base: &str, last: bool, dotglob: bool, found: &mut Vec<String>) {
+    for name in entries(base) {
+        if name.starts_with('.') && !dotglob {
+            continue;
+        }
+        let path = join(base, &name);
+        // Symbolic links to directories are not followed, so that loops end.
+        let is_real_dir = Path::new(&path).symlink_metadata().is_ok_and(|m| m.is_dir());
+        if last || is_real_dir {
+            found.push(path.clone());
+        }
+        if is_real_dir {
+            globstar(&path, last, dotglob, found);
+        }
+    }
+}
+
+/// Expands `pattern` to the paths it matches, sorted. Returns an empty list if
+/// nothing matches; the caller decides what to do then (see nullglob).
+pub(crate) fn expand(pattern: &str) -> Vec<String> {
+    let extglob = shopt::enabled(ShoptOption::Extglob);
+    let dotglob = shopt::enabled(ShoptOption::Dotglob);
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+    let trailing_slash = pattern.ends_with('/');
+
+    let mut paths = vec![if pattern.starts_with('/') { "/".to_string() } else { String::new() }];
+    for (index, component) in components.iter().enumerate() {
+        let last = index + 1 == components.len() && !trailing_slash;
+        let mut next = Vec::new();
+        for base in &paths {
+            if !pattern::has_magic(component, extglob) {
+                next.push(join(base, &pattern::unescape(component)));
+            } else if *component == "**" && shopt::enabled(ShoptOption::Globstar) {
+                if !last {
+                    next.push(base.clone());
+                }
+                globstar(base, last, dotglob, &mut next);
+            } else {
+                let matcher = Pattern::new(component, extglob, false);
+                let show_hidden = dotglob || component.starts_with('.');
+                for name in entries(base) {
+                    if name.starts_with('.') && !show_hidden {
+                        continue;
+                    }
+                    let path = join(base, &name);
+                    if matcher.matches(&name) && (last || is_dir(&path)) {
+                        next.push(path);
+                    }
+                }
+            }
+        }
+        paths = next;
+    }
+
+    // Literal components were not checked on the way.
+    paths.retain(|path| !path.is_empty() && Path::new(path).symlink_metadata().is_ok());
+    if trailing_slash {
+        paths.retain(|path| is_dir(path));
+        for path in &mut paths {
+            path.push('/');
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+}
@@ -0,0 +1,324 @@
+use std::io::Write;
+use std::time::Duration;
+use super::coproc;
+use super::signals::{self, WaitStatus};
+use super::state::STATE;
+use super::{Command, CommandError, Redirection};
+
+const WAIT_USAGE: &str = "wait: usage: wait [-n] [id ...]";
+const DISOWN_USAGE: &str = "disown: usage: disown [-h] [-ar] [jobspec ... | pid ...]";
+const SUSPEND_USAGE: &str = "suspend: usage: suspend [-f]";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum JobState {
+    Running,
+    Stopped,
+}
+
+// A process the shell is not waiting for in the foreground.
+#[derive(Debug, Clone)]
+pub(crate) struct Job {
+    pub id: usize,
+    pub pid: u32,
+    pub command: String,
+    pub state: JobState,
+}
+
+// Adds a job under the lowest free job number and returns that number.
+pub(crate) fn add_job(pid: u32, command: &str, state: JobState) -> usize {
+    let mut shell = STATE.write().unwrap();
+    let id = (1..).find(|id| shell.jobs.iter().all(|job| job.id != *id)).unwrap();
+    shell.jobs.push(Job { id, pid, command: command.to_string(), state });
+    id
+}
+
+pub(crate) fn count() -> usize {
+    STATE.read().unwrap().jobs.len()
+}
+
+// Polls the jobs for state changes, reporting and forgetting the finished ones.
+// Called before each prompt.
+pub(crate) fn reap() {
+    let mut shell = STATE.write().unwrap();
+    let mut finished = Vec::new();
+    shell.jobs.retain_mut(|job| {
+        let options = libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED;
+        let status = signals::wait_pid(job.pid, options);
+        let running = match status {
+            Ok(None) => true,
+            Ok(Some(WaitStatus::Stopped(_))) => {
+                job.state = JobState::Stopped;
+                true
+            }
+            Ok(Some(WaitStatus::Continued)) => {
+                job.state = JobState::Running;
+                true
+            }
+            Ok(Some(WaitStatus::Exited(0))) => {
+                eprintln!("[{}]+  Done                    {}", job.id, job.command);
+                false
+            }
+            Ok(Some(WaitStatus::Exited(code))) => {
+                eprintln!("[{}]+  Exit {:<18} {}", job.id, code, job.command);
+                false
+            }
+            Ok(Some(WaitStatus::Signaled { signal, .. })) => {
+                eprintln!("[{}]+  {:<23} {}", job.id, signals::describe(signal), job.command);
+                false
+            }
+            // Not our child any more.
+            Err(_) => false,
+        };
+        if let (false, Ok(Some(status))) = (running, &status) {
+            finished.push((job.pid, status.code()));
+        }
+        running
+    });
+    shell.finished.extend_from_slice(&finished);
+    drop(shell);
+    for (pid, _) in finished {
+        coproc::reaped(pid);
+    }
+}
+
+/// Finds the job named by a job spec: `%N`, `%%` or `%+` (the current job,
+/// the newest), `%-` (the one before it), `%name` (whose command starts with
+/// name) or `%?text` (whose command contains text).
+pub(crate) fn find_job(spec: &str) -> Result<Job, &'static str> {
+    let shell = STATE.read().unwrap();
+    let jobs = &shell.jobs;
+    let found: Vec<&Job> = match spec.strip_prefix('%').unwrap_or(spec) {
+        "" | "%" | "+" => jobs.last().into_iter().collect(),
+        "-" => jobs.iter().rev().nth(1).or(jobs.last()).into_iter().collect(),
+        number if number.chars().all(|c| c.is_ascii_digit()) => {
+            jobs.iter().filter(|job| number.parse() == Ok(job.id)).collect()
+        }
+        text => match text.strip_prefix('?') {
+            Some(text) => jobs.iter().filter(|job| job.command.contains(text)).collect(),
+            None => jobs.iter().filter(|job| job.command.starts_with(text)).collect(),
+        },
+    };
+    match found[..] {
+        [job] => Ok(job.clone()),
+        [] => Err("no such job"),
+        _ => Err("ambiguous job spec"),
+    }
+}
+
+// Forgets a process that has been waited for.
+fn forget(pid: u32) {
+    STATE.write().unwrap().jobs.retain(|job| job.pid != pid);
+    coproc::reaped(pid);
+}
+
+// Takes the saved status of `pid` if it was reaped before being waited for.
+fn saved_status(pid: u32) -> Option<i32> {
+    let mut shell = STATE.write().unwrap();
+    let index = shell.finished.iter().position(|&(finished, _)| finished == pid)?;
+    Some(shell.finished.remove(index).1)
+}
+
+// Waits for `pid` to exit or stop. Returns its status, or None if it is not a child of the shell.
+fn wait_for(pid: u32) -> Option<i32> {
+    if let Some(status) = saved_status(pid) {
+        return Some(status);
+    }
+    loop {
+        match signals::wait_pid(pid, libc::WUNTRACED) {
+            Ok(Some(WaitStatus::Stopped(signal))) => {
+                if let Some(job) = STATE.write().unwrap().jobs.iter_mut().find(|job| job.pid == pid) {
+                    job.state = JobState::Stopped;
+                }
+                return Some(128 + signal);
+            }
+            Ok(Some(WaitStatus::Continued)) | Ok(None) => {}
+            Ok(Some(status)) => {
+                forget(pid);
+                return Some(status.code());
+            }
+            Err(_) => {
+                forget(pid);
+                return None;
+            }
+        }
+    }
+}
+
+// Waits for whichever of `pids` exits first. Returns its status, or None if none is a child.
+fn wait_any(mut pids: Vec<u32>) -> Option<i32> {
+    if let Some(status) = pids.iter().find_map(|&pid| saved_status(pid)) {
+        return Some(status);
+    }
+    loop {
+        let mut finished = None;
+        pids.retain(|&pid| match signals::wait_pid(pid, libc::WNOHANG) {
+            Ok(Some(WaitStatus::Stopped(_) | WaitStatus::Continued)) | Ok(None) => true,
+            Ok(Some(status)) => {
+                finished = finished.or(Some((pid, status.code())));
+                false
+            }
+            Err(_) => false,
+        });
+        if let Some((pid, status)) = finished {
+            forget(pid);
+            return Some(status);
+        }
+        if pids.is_empty() {
+            return None;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+// The pid a `wait`, `kill` or `disown` operand names: a job spec or a number.
+// Errors are the messages to report after the operand.
+fn operand_pid(operand: &str) -> Result<u32, &'static str> {
+    if operand.starts_with('%') {
+        return find_job(operand).map(|job| job.pid);
+    }
+    operand.parse().map_err(|_| "not a pid or valid job spec")
+}
+
+pub(crate) fn wait_cmd(args: &[String], stderr_writer: &mut dyn Write) -> i32 {
+    let mut next = false;
+    let mut operands = args;
+    while let Some(option) = operands.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+        operands = &operands[1..];
+        match option.as_str() {
+            "--" => break,
+            "-n" => next = true,
+            _ => {
+                let _ = writeln!(stderr_writer, "wait: {}: invalid option", option);
+                let _ = writeln!(stderr_writer, "{}", WAIT_USAGE);
+                return 2;
+            }
+        }
+    }
+    let mut status = 0;
+    let mut pids = Vec::new();
+    for operand in operands {
+        match operand_pid(operand) {
+            Ok(pid) => pids.push(pid),
+            Err(message) if operand.starts_with('%') => {
+                let _ = writeln!(stderr_writer, "wait: {}: {}", operand, message);
+                status = 127;
+            }
+            Err(message) => {
+                let _ = writeln!(stderr_writer, "wait: `{}': {}", operand, message);
+                status = 1;
+            }
+        }
+    }
+    let jobs: Vec<u32> = STATE.read().unwrap().jobs.iter().map(|job| job.pid).collect();
+    if next {
+        let candidates = match operands.is_empty() {
+            true => STATE.read().unwrap().finished.iter().map(|&(pid, _)| pid).chain(jobs).collect(),
+            false => pids,
+        };
+        return wait_any(candidates).unwrap_or(127);
+    }
+    // On its own, `wait` waits for every job and succeeds.
+    if operands.is_empty() {
+        for pid in jobs {
+            wait_for(pid);
+        }
+        STATE.write().unwrap().finished.clear();
+        return 0;
+    }
+    for pid in pids {
+        status = wait_for(pid).unwrap_or_else(|| {
+            let _ = writeln!(stderr_writer, "wait: pid {} is not a child of this shell", pid);
+            127
+        });
+    }
+    status
+}
+
+pub(crate) fn parse_wait_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Wait { args, redirections })
+}
+
+// `disown` removes jobs from the table, so the shell no longer reports or waits
+// for them. The shell never sends SIGHUP to its jobs, so with `-h`, which
+// would only spare them that, they are simply kept.
+pub(crate) fn disown_cmd(args: &[String], stderr_writer: &mut dyn Write) -> i32 {
+    let (mut keep, mut all, mut running) = (false, false, false);
+    let mut operands = args;
+    while let Some(option) = operands.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+        operands = &operands[1..];
+        if option == "--" {
+            break;
+        }
+        for flag in option.chars().skip(1) {
+            match flag {
+                'h' => keep = true,
+                'a' => all = true,
+                'r' => running = true,
+                _ => {
+                    let _ = writeln!(stderr_writer, "disown: -{}: invalid option", flag);
+                    let _ = writeln!(stderr_writer, "{}", DISOWN_USAGE);
+                    return 2;
+                }
+            }
+        }
+    }
+    let mut pids = Vec::new();
+    let mut status = 0;
+    if operands.is_empty() && (all || running) {
+        let shell = STATE.read().unwrap();
+        pids.extend(shell.jobs.iter().filter(|job| !running || job.state == JobState::Running).map(|job| job.pid));
+    } else if operands.is_empty() {
+        match find_job("%+") {
+            Ok(job) => pids.push(job.pid),
+            Err(message) => {
+                let _ = writeln!(stderr_writer, "disown: current: {}", message);
+                return 1;
+            }
+        }
+    }
+    for operand in operands {
+        let found = operand_pid(operand).ok().filter(|pid| STATE.read().unwrap().jobs.iter().any(|job| job.pid == *pid));
+        match found {
+            Some(pid) => pids.push(pid),
+            None => {
+                let _ = writeln!(stderr_writer, "disown: {}: no such job", operand);
+                status = 1;
+            }
+        }
+    }
+    if !keep {
+        STATE.write().unwrap().jobs.retain(|job| !pids.contains(&job.pid));
+    }
+    status
+}
+
+pub(crate) fn parse_disown_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Disown { args, redirections })
+}
+
+// `suspend` stops the shell until it gets SIGCONT. A login shell, whose name
+// starts with `-`, needs `-f`.
+pub(crate) fn suspend_cmd(args: &[String], stderr_writer: &mut dyn Write) -> i32 {
+    let mut force = false;
+    for arg in args {
+        match arg.as_str() {
+            "-f" => force = true,
+            _ => {
+                let _ = writeln!(stderr_writer, "suspend: {}: invalid option", arg);
+                let _ = writeln!(stderr_writer, "{}", SUSPEND_USAGE);
+                return 2;
+            }
+        }
+    }
+    if !force && STATE.read().unwrap().shell_name.starts_with('-') {
+        let _ = writeln!(stderr_writer, "suspend: cannot suspend a login shell");
+        return 1;
+    }
+    // SAFETY: sending a signal to this process has no memory-safety requirements.
+    unsafe { libc::kill(libc::getpid(), libc::SIGSTOP) };
+    0
+}
+
+pub(crate) fn parse_suspend_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Suspend { args, redirections })
+}
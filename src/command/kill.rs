@@ -0,0 +1,132 @@
+// The `kill` builtin. It is a builtin rather than /bin/kill so that it can
+// address jobs as `%N`, and so that `kill -l` lists the signals the shell knows.
+use std::io::{self, Write};
+use super::jobs::{self, JobState};
+use super::{signals, utils};
+use super::state::STATE;
+use super::{Command, CommandError, Redirection};
+
+const USAGE: &str = "kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]";
+
+// Parses a signal to send: a name or number as for `trap`, or 0 to only check the process exists.
+fn signal_spec(spec: &str) -> Option<libc::c_int> {
+    if spec == "0" {
+        return Some(0);
+    }
+    signals::signal_number(spec)
+}
+
+// `kill -l [sigspec...]`: names for numbers (an exit status above 128 stands
+// for the signal that caused it), numbers for names.
+fn list(specs: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> io::Result<i32> {
+    if specs.is_empty() {
+        signals::list_signals(stdout_writer)?;
+        return Ok(0);
+    }
+    let mut status = 0;
+    for spec in specs {
+        let translated = match spec.parse::<libc::c_int>() {
+            Ok(number) => {
+                let number = if number > 128 { number - 128 } else { number };
+                signals::signal_number(&number.to_string())
+                    .map(|signal| signals::signal_name(signal).trim_start_matches("SIG").to_string())
+            }
+            Err(_) => signals::signal_number(spec).map(|signal| signal.to_string()),
+        };
+        match translated {
+            Some(text) => writeln!(stdout_writer, "{}", text)?,
+            None => {
+                let _ = writeln!(stderr_writer, "kill: {}: invalid signal specification", spec);
+                status = 1;
+            }
+        }
+    }
+    Ok(status)
+}
+
+// Sends `signal` to the process or job named by `operand`.
+fn send(operand: &str, signal: libc::c_int, stderr_writer: &mut dyn Write) -> bool {
+    let (pid, stopped) = if operand.starts_with('%') {
+        match jobs::find_job(operand) {
+            Ok(job) => (job.pid as libc::pid_t, job.state == JobState::Stopped),
+            Err(message) => {
+                let _ = writeln!(stderr_writer, "kill: {}: {}", operand, message);
+                return false;
+            }
+        }
+    } else {
+        match operand.parse::<libc::pid_t>() {
+            Ok(pid) => (pid, false),
+            Err(_) => {
+                let _ = writeln!(stderr_writer, "kill: {}: arguments must be process or job IDs", operand);
+                return false;
+            }
+        }
+    };
+    // SAFETY: kill(2) has no memory-safety requirements.
+    if unsafe { libc::kill(pid, signal) } < 0 {
+        let err = std::io::Error::last_os_error();
+        let message = match err.raw_os_error() {
+            Some(libc::ESRCH) => "No such process".to_string(),
+            Some(libc::EPERM) => "Operation not permitted".to_string(),
+            _ => err.to_string(),
+        };
+        let _ = writeln!(stderr_writer, "kill: ({}) - {}", pid, message);
+        return false;
+    }
+    // A stopped job would only see the signal once continued.
+    if stopped && matches!(signal, libc::SIGTERM | libc::SIGHUP) {
+        // SAFETY: as above.
+        unsafe { libc::kill(pid, libc::SIGCONT) };
+        let mut shell = STATE.write().unwrap();
+        if let Some(job) = shell.jobs.iter_mut().find(|job| job.pid as libc::pid_t == pid) {
+            job.state = JobState::Running;
+        }
+    }
+    true
+}
+
+pub(crate) fn kill_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    let mut signal = libc::SIGTERM;
+    let mut operands = args;
+    while let Some(option) = operands.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+        operands = &operands[1..];
+        let spec = match option.as_str() {
+            "--" => break,
+            "-l" | "-L" => {
+                return list(operands, stdout_writer, stderr_writer).unwrap_or_else(|err| utils::write_error("kill", &err, stderr_writer));
+            }
+            "-s" | "-n" => {
+                let Some((spec, rest)) = operands.split_first() else {
+                    let _ = writeln!(stderr_writer, "kill: {}: option requires an argument", option);
+                    return 1;
+                };
+                operands = rest;
+                spec.as_str()
+            }
+            _ => &option[1..],
+        };
+        match signal_spec(spec) {
+            Some(number) => signal = number,
+            None => {
+                let _ = writeln!(stderr_writer, "kill: {}: invalid signal specification", spec);
+                return 1;
+            }
+        }
+    }
+    if operands.is_empty() {
+        let _ = writeln!(stderr_writer, "{}", USAGE);
+        return 2;
+    }
+    let mut status = 0;
+    for operand in operands {
+        if !send(operand, signal, stderr_writer) {
+            status = 1;
+        }
+    }
+    status
+}
+
+pub(crate) fn parse_kill_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Kill { args, redirections })
+}
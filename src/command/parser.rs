@@ -0,0 +1,700 @@
+// The shell's lexer, and the structural checks built on it: decides whether a
+// line can be run as-is, needs more lines (PS2), or is a syntax error, and
+// splits it into commands. Other parsers find words and groups through it too.
+use std::ops::Range;
+
+// Why the input cannot be run yet.
+#[derive(Debug, PartialEq)]
+pub enum Pending {
+    SingleQuote,
+    DoubleQuote,
+    // `$(...)`, backquotes or another group waiting for its closing character.
+    Substitution(char),
+    Backslash,
+    // A trailing `&&` or `||` (or `|` in a `case` pattern) waiting for its right-hand side.
+    Operator(&'static str),
+    // An open compound command waiting for its closing word (`fi`, `done`, `}`...).
+    Construct(&'static str),
+    HereDoc(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Token {
+    Word(String),
+    Operator(&'static str),
+    Newline,
+}
+
+// Longest operators first so that `&&` wins over `&`.
+const OPERATORS: &[&str] = &[
+    "<<-", "<<<", "&>>", ";;&", ";;", ";&", "&&", "||", "<<", ">>", ">&", "<&", ">|", "&>", "|", "&", ";", "(", ")", "<",
+    ">",
+];
+
+// Operators that end an arm of a `case`.
+const CASE_TERMINATORS: &[&str] = &[";;", ";&", ";;&"];
+
+pub(crate) fn is_redirection(op: &str) -> bool {
+    op.contains('<') || op.contains('>')
+}
+
+fn closing_word(opening: &str) -> Option<&'static str> {
+    match opening {
+        "case" => Some("esac"),
+        "{" => Some("}"),
+        _ => None,
+    }
+}
+
+// Compound commands the shell cannot run yet. Continuing them with PS2 would
+// only have their lines run one by one as simple commands. A `{ ... }` group
+// is only run as the body of `coproc`.
+fn unsupported(word: &str) -> bool {
+    matches!(word, "if" | "for" | "while" | "until" | "select" | "{")
+}
+
+// Operators of constructs the shell cannot run yet, and what to report for them.
+// A `(` only starts a subshell in command position: after a name it defines a function.
+fn unsupported_operator(op: &str, command_position: bool) -> Option<&'static str> {
+    match op {
+        "|" => Some("pipelines are not supported"),
+        "&" => Some("background jobs are not supported"),
+        "(" if command_position => Some("subshells are not supported"),
+        _ => None,
+    }
+}
+
+// Consumes a `'...'` string starting at `i` (the opening quote) into `word`.
+fn read_single_quoted(chars: &[char], mut i: usize, word: &mut String) -> Result<usize, Pending> {
+    word.push('\'');
+    i += 1;
+    while i < chars.len() {
+        word.push(chars[i]);
+        if chars[i] == '\'' {
+            return Ok(i + 1);
+        }
+        i += 1;
+    }
+    Err(Pending::SingleQuote)
+}
+
+// Consumes a `$'...'` string starting at `i` (the `$`) into `word`. Unlike in
+// `'...'`, a backslash escapes the next character.
+fn read_ansi_quoted(chars: &[char], mut i: usize, word: &mut String) -> Result<usize, Pending> {
+    word.push_str("$'");
+    i += 2;
+    while i < chars.len() {
+        word.push(chars[i]);
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                word.push(chars[i + 1]);
+                i += 1;
+            }
+            '\'' => return Ok(i + 1),
+            _ => {}
+        }
+        i += 1;
+    }
+    Err(Pending::SingleQuote)
+}
+
+// Consumes a `"..."` string starting at `i` (the opening quote) into `word`.
+fn read_double_quoted(chars: &[char], mut i: usize, word: &mut String) -> Result<usize, Pending> {
+    word.push('"');
+    i += 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                word.push(chars[i]);
+                word.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+            '"' => {
+                word.push('"');
+                return Ok(i + 1);
+            }
+            '$' if chars.get(i + 1) == Some(&'(') => {
+                i = read_substitution(chars, i, word)?;
+                continue;
+            }
+            c => word.push(c),
+        }
+        i += 1;
+    }
+    Err(Pending::DoubleQuote)
+}
+
+// Consumes a `$(...)`, `<(...)` or `>(...)` starting at `i` (the `$`, `<` or `>`),
+// or a backquoted command starting at `i` (the backquote).
+fn read_substitution(chars: &[char], mut i: usize, word: &mut String) -> Result<usize, Pending> {
+    if chars[i] == '`' {
+        word.push('`');
+        i += 1;
+        while i < chars.len() {
+            word.push(chars[i]);
+            match chars[i] {
+                '\\' if i + 1 < chars.len() => {
+                    word.push(chars[i + 1]);
+                    i += 1;
+                }
+                '`' => return Ok(i + 1),
+                _ => {}
+            }
+            i += 1;
+        }
+        return Err(Pending::Substitution('`'));
+    }
+
+    word.push(chars[i]);
+    read_group(chars, i + 1, word)
+}
+
+// Consumes a `(...)` starting at `i` (the `(`), with the groups nested in it.
+fn read_group(chars: &[char], mut i: usize, word: &mut String) -> Result<usize, Pending> {
+    word.push('(');
+    i += 1;
+    let mut depth = 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\'' => {
+                i = read_single_quoted(chars, i, word)?;
+                continue;
+            }
+            '$' if chars.get(i + 1) == Some(&'\'') => {
+                i = read_ansi_quoted(chars, i, word)?;
+                continue;
+            }
+            '"' => {
+                i = read_double_quoted(chars, i, word)?;
+                continue;
+            }
+            '\\' if i + 1 < chars.len() => {
+                word.push(chars[i]);
+                i += 1;
+            }
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    word.push(')');
+                    return Ok(i + 1);
+                }
+            }
+            _ => {}
+        }
+        word.push(chars[i]);
+        i += 1;
+    }
+    Err(Pending::Substitution(')'))
+}
+
+/// Reads the `(...)` whose `(` is at `chars[i]`, as the lexer does: quoted text
+/// and nested groups do not end it. Returns the text inside and the index just
+/// past the `)`.
+pub(crate) fn group(chars: &[char], i: usize) -> Option<(String, usize)> {
+    let mut word = String::new();
+    let end = read_group(chars, i, &mut word).ok()?;
+    Some((chars[i + 1..end - 1].iter().collect(), end))
+}
+
+/// Reads the backquoted command whose opening backquote is at `chars[i]`.
+/// Returns the command, with the backslashes that quoted `$`, `` ` `` and `\`
+/// removed, and the index just past the closing backquote.
+pub(crate) fn backquoted(chars: &[char], i: usize) -> Option<(String, usize)> {
+    let mut word = String::new();
+    let end = read_substitution(chars, i, &mut word).ok()?;
+    let mut command = String::new();
+    let mut inner = chars[i + 1..end - 1].iter().peekable();
+    while let Some(&c) = inner.next() {
+        match inner.peek() {
+            Some(&&next) if c == '\\' && matches!(next, '$' | '`' | '\\') => {
+                command.push(next);
+                inner.next();
+            }
+            _ => command.push(c),
+        }
+    }
+    Some((command, end))
+}
+
+// Whether a `(` right after `word` belongs to it: an extglob group such as
+// `@(a|b)`, or the list of a compound assignment `name=(...)`.
+fn opens_group(word: &str) -> bool {
+    word.ends_with(['?', '*', '+', '@', '!', '='])
+}
+
+// Pushes the word being built, registering it as a here-document delimiter if one is expected.
+fn finish_word(
+    word: &mut String,
+    word_start: &mut Option<usize>,
+    end: usize,
+    expect_delimiter: &mut Option<bool>,
+    heredocs: &mut Vec<(String, bool)>,
+    tokens: &mut Vec<(Token, Range<usize>)>,
+) {
+    let Some(start) = word_start.take() else {
+        return;
+    };
+    let finished = std::mem::take(word);
+    if let Some(strip_tabs) = expect_delimiter.take() {
+        let delimiter = finished.chars().filter(|c| !matches!(c, '\'' | '"' | '\\')).collect();
+        heredocs.push((delimiter, strip_tabs));
+    }
+    tokens.push((Token::Word(finished), start..end));
+}
+
+/// Splits `input` into shell tokens, each with its byte range in `input`.
+/// Words keep their quotes; comments, line continuations and here-document
+/// bodies are dropped.
+pub fn tokenize(input: &str) -> Result<Vec<(Token, Range<usize>)>, Pending> {
+    let chars: Vec<char> = input.chars().collect();
+    // Byte offset of each character, and of the end of the input.
+    let offsets: Vec<usize> = input.char_indices().map(|(offset, _)| offset).chain([input.len()]).collect();
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    // Where the word being built starts, if one is.
+    let mut word_start: Option<usize> = None;
+    // Delimiters of here-documents whose body starts after the next newline.
+    let mut heredocs: Vec<(String, bool)> = Vec::new();
+    let mut expect_delimiter: Option<bool> = None;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let ch = chars[i];
+        match ch {
+            ' ' | '\t' => {
+                finish_word(&mut word, &mut word_start, offsets[i], &mut expect_delimiter, &mut heredocs, &mut tokens);
+                i += 1;
+            }
+            '\n' => {
+                finish_word(&mut word, &mut word_start, offsets[i], &mut expect_delimiter, &mut heredocs, &mut tokens);
+                tokens.push((Token::Newline, offsets[i]..offsets[i + 1]));
+                i += 1;
+                // Skip over the bodies of here-documents introduced on this line.
+                for (delimiter, strip_tabs) in heredocs.drain(..) {
+                    loop {
+                        if i >= chars.len() {
+                            return Err(Pending::HereDoc(delimiter));
+                        }
+                        let end = chars[i..].iter().position(|&c| c == '\n').map_or(chars.len(), |p| i + p);
+                        let line: String = chars[i..end].iter().collect();
+                        let line = if strip_tabs { line.trim_start_matches('\t') } else { line.as_str() };
+                        let found = line == delimiter;
+                        i = end + 1;
+                        if found {
+                            break;
+                        }
+                    }
+                }
+            }
+            '#' if word_start.is_none() => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '\\' => match chars.get(i + 1) {
+                None => return Err(Pending::Backslash),
+                Some('\n') => i += 2,
+                Some(&next) => {
+                    word_start.get_or_insert(offsets[i]);
+                    word.push('\\');
+                    word.push(next);
+                    i += 2;
+                }
+            },
+            '\'' => {
+                word_start.get_or_insert(offsets[i]);
+                i = read_single_quoted(&chars, i, &mut word)?;
+            }
+            '"' => {
+                word_start.get_or_insert(offsets[i]);
+                i = read_double_quoted(&chars, i, &mut word)?;
+            }
+            '`' => {
+                word_start.get_or_insert(offsets[i]);
+                i = read_substitution(&chars, i, &mut word)?;
+            }
+            '$' if chars.get(i + 1) == Some(&'\'') => {
+                word_start.get_or_insert(offsets[i]);
+                i = read_ansi_quoted(&chars, i, &mut word)?;
+            }
+            '$' if chars.get(i + 1) == Some(&'(') => {
+                word_start.get_or_insert(offsets[i]);
+                i = read_substitution(&chars, i, &mut word)?;
+            }
+            // Process substitution, rather than a redirection.
+            '<' | '>' if word_start.is_none() && chars.get(i + 1) == Some(&'(') => {
+                word_start = Some(offsets[i]);
+                i = read_substitution(&chars, i, &mut word)?;
+            }
+            '(' if word_start.is_some() && opens_group(&word) => {
+                i = read_group(&chars, i, &mut word)?;
+            }
+            '|' | '&' | ';' | '(' | ')' | '<' | '>' => {
+                finish_word(&mut word, &mut word_start, offsets[i], &mut expect_delimiter, &mut heredocs, &mut tokens);
+                let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+                let op = OPERATORS.iter().find(|op| rest.starts_with(**op)).unwrap();
+                match *op {
+                    "<<" => expect_delimiter = Some(false),
+                    "<<-" => expect_delimiter = Some(true),
+                    _ => {}
+                }
+                tokens.push((Token::Operator(op), offsets[i]..offsets[i + op.len()]));
+                i += op.len();
+            }
+            c => {
+                word_start.get_or_insert(offsets[i]);
+                word.push(c);
+                i += 1;
+            }
+        }
+    }
+    finish_word(&mut word, &mut word_start, input.len(), &mut expect_delimiter, &mut heredocs, &mut tokens);
+
+    if let Some((delimiter, _)) = heredocs.into_iter().next() {
+        return Err(Pending::HereDoc(delimiter));
+    }
+    Ok(tokens)
+}
+
+const HEREDOC_UNSUPPORTED: &str = "here-documents are not supported";
+const HERESTRING_UNSUPPORTED: &str = "here-strings are not supported";
+
+/// Checks whether `input` is a complete command.
+/// Returns `Ok(Some(..))` when more lines are needed and `Err` with a message on syntax errors.
+pub fn pending(input: &str) -> Result<Option<Pending>, String> {
+    let tokens = match tokenize(input) {
+        Ok(tokens) => tokens,
+        Err(Pending::HereDoc(_)) => return Err(HEREDOC_UNSUPPORTED.to_string()),
+        Err(pending) => return Ok(Some(pending)),
+    };
+
+    let unexpected = |token: &str| format!("syntax error near unexpected token `{}'", token);
+    let mut open: Vec<&'static str> = Vec::new();
+    let mut command_position = true;
+    let mut after_redirection = false;
+    let mut last_operator: Option<&'static str> = None;
+    let mut seen_word = false;
+    // Inside `[[ ... ]]`, where `(`, `)` and `|` belong to the expression.
+    let mut conditional = false;
+
+    let tokens: Vec<Token> = tokens.into_iter().map(|(token, _)| token).collect();
+    for (idx, token) in tokens.iter().enumerate() {
+        // `[[ ... ]]` and `case` patterns have their own uses for `(` and `|`.
+        if let Token::Operator(op) = token {
+            let own_use = *op != "&" && (conditional || open.contains(&"esac"));
+            if let Some(message) = unsupported_operator(op, command_position).filter(|_| !own_use) {
+                return Err(message.to_string());
+            }
+        }
+        match token {
+            Token::Word(word) => {
+                if after_redirection {
+                    after_redirection = false;
+                } else if command_position {
+                    let after_coproc = |back: usize| idx >= back && tokens[idx - back] == Token::Word("coproc".to_string());
+                    let coproc_name = after_coproc(1) && tokens.get(idx + 1) == Some(&Token::Word("{".to_string()));
+                    let coproc_body = word == "{" && (after_coproc(1) || after_coproc(2));
+                    if word == "coproc" || coproc_name {
+                        // `coproc` and the NAME of `coproc NAME { ... }` are followed by a command.
+                    } else if unsupported(word) && !coproc_body {
+                        return Err(format!("{}: compound command not supported", word));
+                    } else if let Some(closer) = closing_word(word) {
+                        open.push(closer);
+                        // `case WORD in` is not followed by a command.
+                        command_position = word != "case";
+                    } else if matches!(word.as_str(), "then" | "do" | "else" | "elif" | "fi" | "done") {
+                        return Err(unexpected(word));
+                    } else if matches!(word.as_str(), "esac" | "}") {
+                        if open.last() != Some(&word.as_str()) {
+                            return Err(unexpected(word));
+                        }
+                        open.pop();
+                        command_position = false;
+                    } else {
+                        conditional |= word == "[[";
+                        command_position = word == "!";
+                    }
+                } else if word == "]]" && conditional {
+                    conditional = false;
+                } else if word == "in" && open.last() == Some(&"esac") {
+                    command_position = false;
+                }
+                seen_word = true;
+                last_operator = None;
+            }
+            Token::Operator("<<" | "<<-") => return Err(HEREDOC_UNSUPPORTED.to_string()),
+            Token::Operator("<<<") => return Err(HERESTRING_UNSUPPORTED.to_string()),
+            Token::Operator(op) if is_redirection(op) => {
+                after_redirection = true;
+                last_operator = None;
+            }
+            Token::Operator("(") => {
+                open.push(")");
+                command_position = true;
+                last_operator = None;
+            }
+            Token::Operator(")") => {
+                match open.last() {
+                    Some(&")") => {
+                        open.pop();
+                        // `name()` is followed by the function body.
+                        command_position = idx > 0 && tokens[idx - 1] == Token::Operator("(");
+                    }
+                    // The end of a `case` pattern.
+                    Some(&"esac") => command_position = true,
+                    _ => return Err(unexpected(")")),
+                }
+                last_operator = None;
+            }
+            Token::Operator(op) => {
+                let inside_case = open.last() == Some(&"esac");
+                let dangling = !seen_word || (last_operator.is_some() && !inside_case);
+                // `;;` and friends may only end a `case` arm.
+                let misplaced = if CASE_TERMINATORS.contains(op) { !inside_case } else { dangling };
+                if misplaced {
+                    return Err(unexpected(op));
+                }
+                command_position = true;
+                last_operator = Some(op);
+            }
+            Token::Newline => {
+                command_position = true;
+                if last_operator.is_some_and(|op| op == ";" || op == "&" || CASE_TERMINATORS.contains(&op)) {
+                    last_operator = None;
+                }
+                if last_operator.is_none() {
+                    // A new line may not start with an operator.
+                    seen_word = false;
+                }
+            }
+        }
+    }
+
+    if let Some(op @ ("|" | "&&" | "||")) = last_operator {
+        return Ok(Some(Pending::Operator(op)));
+    }
+    if after_redirection {
+        return Err(unexpected("newline"));
+    }
+    Ok(open.last().map(|closer| Pending::Construct(closer)))
+}
+
+// How a command in a list is joined to the one before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    Sequence,
+    And,
+    Or,
+}
+
+// Whether a word after `previous` would be in command position.
+pub(crate) fn at_command_start(previous: Option<&Token>) -> bool {
+    match previous {
+        None | Some(Token::Newline) => true,
+        Some(Token::Operator(op)) => !is_redirection(op),
+        Some(Token::Word(_)) => false,
+    }
+}
+
+/// Splits `input` at the top-level `;`, `&&`, `||` and newlines.
+/// Each command comes with the operator that precedes it and the line of
+/// `input` it starts on, counting from 0. A `case ... esac`
+/// stays in one piece, `;;` and all, and so does a `[[ ... ]]` with `&&` or `||` inside.
+/// Input the lexer cannot read, such as an unterminated quote, is one command.
+pub fn split_list(input: &str) -> Vec<(String, Connector, usize)> {
+    let line = |start: usize| input[..start].matches('\n').count();
+    let Ok(tokens) = tokenize(input) else {
+        let start = input.len() - input.trim_start().len();
+        return vec![(input.trim().to_string(), Connector::Sequence, line(start))];
+    };
+    let mut list = Vec::new();
+    // Where the command being read starts and ends: its first token to its last.
+    let mut current: Option<Range<usize>> = None;
+    let mut connector = Connector::Sequence;
+    let mut depth = 0usize;
+    let mut cases = 0;
+    // The `{ ... }` of a `coproc` stays in one piece: its list runs in the coprocess.
+    let mut coproc = false;
+    let mut braces = 0;
+    let mut conditional = false;
+    let mut previous: Option<&Token> = None;
+
+    for (token, span) in &tokens {
+        let command_start = at_command_start(previous);
+        let after_in = matches!(previous, Some(Token::Word(word)) if word == "in");
+        let nested = depth > 0 || cases > 0 || braces > 0 || conditional;
+        previous = Some(token);
+        match token {
+            Token::Newline | Token::Operator(";" | ";;") if !nested => {
+                // A newline after `&&` or `||` continues the list.
+                if let Some(command) = current.take() {
+                    list.push((input[command.clone()].to_string(), connector, line(command.start)));
+                    connector = Connector::Sequence;
+                }
+                continue;
+            }
+            Token::Operator(op @ ("&&" | "||")) if !nested => {
+                if let Some(command) = current.take() {
+                    list.push((input[command.clone()].to_string(), connector, line(command.start)));
+                }
+                connector = if *op == "&&" { Connector::And } else { Connector::Or };
+                continue;
+            }
+            Token::Operator("(") => depth += 1,
+            // Patterns in a `case` end with an unmatched `)`.
+            Token::Operator(")") => depth = depth.saturating_sub(1),
+            Token::Word(word) => {
+                if current.is_none() {
+                    coproc = word == "coproc";
+                }
+                match word.as_str() {
+                    "case" if command_start => cases += 1,
+                    "esac" if cases > 0 && (command_start || after_in) => cases -= 1,
+                    "[[" if command_start => conditional = true,
+                    "]]" if conditional => conditional = false,
+                    "{" if coproc => braces += 1,
+                    "}" if braces > 0 && command_start => braces -= 1,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        current = Some(current.map_or(span.start, |command| command.start)..span.end);
+    }
+    if let Some(command) = current {
+        list.push((input[command.clone()].to_string(), connector, line(command.start)));
+    }
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complete_input_is_not_pending() {
+        for input in ["echo hi", "a && b || c", "echo 'a|b' \"c;d\"", "case x in x) echo;; esac", "echo $(echo ')')"] {
+            assert_eq!(pending(input), Ok(None), "{}", input);
+        }
+        // `(` and `|` in `[[ ... ]]` and `case` patterns, and the braces of `coproc`.
+        for input in ["[[ (a) && ! (b) || c =~ d|e ]]", "case x in (a|b) c;; (d) e;; esac", "coproc { a; }", "coproc n { a; }"] {
+            assert_eq!(pending(input), Ok(None), "{}", input);
+        }
+    }
+
+    #[test]
+    fn open_quotes_and_groups_are_pending() {
+        assert_eq!(pending("echo 'a"), Ok(Some(Pending::SingleQuote)));
+        assert_eq!(pending("echo $'a\\'"), Ok(Some(Pending::SingleQuote)));
+        assert_eq!(pending("echo \"a"), Ok(Some(Pending::DoubleQuote)));
+        assert_eq!(pending("echo $(a"), Ok(Some(Pending::Substitution(')'))));
+        assert_eq!(pending("echo `a"), Ok(Some(Pending::Substitution('`'))));
+        assert_eq!(pending("echo a\\"), Ok(Some(Pending::Backslash)));
+        assert_eq!(pending("a &&"), Ok(Some(Pending::Operator("&&"))));
+        assert_eq!(pending("case x in a |"), Ok(Some(Pending::Operator("|"))));
+        assert_eq!(pending("case x in"), Ok(Some(Pending::Construct("esac"))));
+        assert_eq!(pending("coproc { a;"), Ok(Some(Pending::Construct("}"))));
+    }
+
+    #[test]
+    fn syntax_errors_name_the_token() {
+        assert_eq!(pending("&& a"), Err("syntax error near unexpected token `&&'".to_string()));
+        assert_eq!(pending("a ;; b"), Err("syntax error near unexpected token `;;'".to_string()));
+        assert_eq!(pending("echo )"), Err("syntax error near unexpected token `)'".to_string()));
+        assert_eq!(pending("esac"), Err("syntax error near unexpected token `esac'".to_string()));
+        assert_eq!(pending("echo >"), Err("syntax error near unexpected token `newline'".to_string()));
+    }
+
+    #[test]
+    fn unsupported_constructs_are_errors() {
+        assert_eq!(pending("if true; then a; fi"), Err("if: compound command not supported".to_string()));
+        assert_eq!(pending("while true"), Err("while: compound command not supported".to_string()));
+        assert_eq!(pending("cat <<EOF"), Err(HEREDOC_UNSUPPORTED.to_string()));
+        assert_eq!(pending("cat <<EOF\nbody\nEOF"), Err(HEREDOC_UNSUPPORTED.to_string()));
+        assert_eq!(pending("cat <<< word"), Err(HERESTRING_UNSUPPORTED.to_string()));
+        assert_eq!(pending("{ a; }"), Err("{: compound command not supported".to_string()));
+        assert_eq!(pending("a |"), Err("pipelines are not supported".to_string()));
+        assert_eq!(pending("a | b"), Err("pipelines are not supported".to_string()));
+        assert_eq!(pending("a &"), Err("background jobs are not supported".to_string()));
+        assert_eq!(pending("(a)"), Err("subshells are not supported".to_string()));
+        assert_eq!(pending("a && (b"), Err("subshells are not supported".to_string()));
+    }
+
+    #[test]
+    fn tokens_keep_quotes_and_spans() {
+        let input = "a='x y' >&2 <(b)#c\necho";
+        let tokens = tokenize(input).unwrap();
+        let words: Vec<(&Token, &str)> = tokens.iter().map(|(token, span)| (token, &input[span.clone()])).collect();
+        assert_eq!(
+            words,
+            [
+                (&Token::Word("a='x y'".to_string()), "a='x y'"),
+                (&Token::Operator(">&"), ">&"),
+                (&Token::Word("2".to_string()), "2"),
+                (&Token::Word("<(b)#c".to_string()), "<(b)#c"),
+                (&Token::Newline, "\n"),
+                (&Token::Word("echo".to_string()), "echo"),
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_after_extglob_and_assignments_stay_in_the_word() {
+        let words = |input| tokenize(input).unwrap().into_iter().map(|(token, _)| token).collect::<Vec<_>>();
+        assert_eq!(words("@(a|b)"), [Token::Word("@(a|b)".to_string())]);
+        assert_eq!(words("x=(1 2)"), [Token::Word("x=(1 2)".to_string())]);
+        assert_eq!(words("(a)"), [Token::Operator("("), Token::Word("a".to_string()), Token::Operator(")")]);
+    }
+
+    #[test]
+    fn group_and_backquoted_readers() {
+        let chars: Vec<char> = "(a ')' \\) (b))rest".chars().collect();
+        assert_eq!(group(&chars, 0), Some(("a ')' \\) (b)".to_string(), 14)));
+        let chars: Vec<char> = "`echo \\`x\\` \\$y`z".chars().collect();
+        assert_eq!(backquoted(&chars, 0), Some(("echo `x` $y".to_string(), 16)));
+        assert_eq!(group(&"(open".chars().collect::<Vec<_>>(), 0), None);
+    }
+
+    #[test]
+    fn lists_split_at_top_level_operators() {
+        assert_eq!(
+            split_list("a; b && c || d\ne"),
+            [
+                ("a".to_string(), Connector::Sequence, 0),
+                ("b".to_string(), Connector::Sequence, 0),
+                ("c".to_string(), Connector::And, 0),
+                ("d".to_string(), Connector::Or, 0),
+                ("e".to_string(), Connector::Sequence, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_newline_after_and_or_keeps_the_connector() {
+        assert_eq!(
+            split_list("a &&\n\nb"),
+            [("a".to_string(), Connector::Sequence, 0), ("b".to_string(), Connector::And, 2)]
+        );
+    }
+
+    #[test]
+    fn compound_commands_stay_whole() {
+        let case = "case x in x) a;; y) b && c;; esac";
+        assert_eq!(
+            split_list(&format!("{}; d", case)),
+            [(case.to_string(), Connector::Sequence, 0), ("d".to_string(), Connector::Sequence, 0)]
+        );
+        let conditional = "[[ a && b || (c) ]]";
+        assert_eq!(split_list(conditional), [(conditional.to_string(), Connector::Sequence, 0)]);
+        let coproc = "coproc { a; b; }";
+        assert_eq!(split_list(coproc), [(coproc.to_string(), Connector::Sequence, 0)]);
+        assert_eq!(split_list("echo 'a;b' $(c; d)"), [("echo 'a;b' $(c; d)".to_string(), Connector::Sequence, 0)]);
+    }
+
+    #[test]
+    fn comments_and_empty_commands_are_dropped() {
+        assert_eq!(split_list("# only\n\n;a # b; c\n"), [("a".to_string(), Connector::Sequence, 2)]);
+    }
+}
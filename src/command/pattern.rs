@@ -0,0 +1,360 @@
+// Shell pattern matching for pathname expansion, `case` and `[[ == ]]`:
+// `*`, `?`, bracket expressions and, with extglob, `?(..)`, `*(..)`, `+(..)`, `@(..)`, `!(..)`.
+// A backslash makes the next character literal; callers escape quoted characters that way.
+
+#[derive(Debug)]
+enum Token {
+    Literal(char),
+    Any,
+    Star,
+    Bracket { negated: bool, items: Vec<BracketItem> },
+    Extended { kind: char, alternatives: Vec<Vec<Token>> },
+}
+
+#[derive(Debug)]
+enum BracketItem {
+    Char(char),
+    Range(char, char),
+    Class(String),
+}
+
+#[derive(Debug)]
+pub(crate) struct Pattern {
+    tokens: Vec<Token>,
+    ignore_case: bool,
+}
+
+// Index of the `)` closing the `(` at `chars[open]`, skipping nested groups and escapes.
+fn closing_paren(chars: &[char], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = open;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+// Splits the inside of an extglob group at its top-level `|`.
+fn split_alternatives(chars: &[char]) -> Vec<&[char]> {
+    let mut alternatives = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '|' if depth == 0 => {
+                alternatives.push(&chars[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    alternatives.push(&chars[start..]);
+    alternatives
+}
+
+// Parses the bracket expression starting after the `[` at `chars[i]`.
+// Returns None if it is not closed, in which case the `[` is literal.
+fn parse_bracket(chars: &[char], mut i: usize) -> Option<(Token, usize)> {
+    let negated = matches!(chars.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut items = Vec::new();
+    let mut first = true;
+    loop {
+        let c = *chars.get(i)?;
+        match c {
+            ']' if !first => return Some((Token::Bracket { negated, items }, i + 1)),
+            '[' if chars.get(i + 1) == Some(&':') => {
+                let rest: String = chars[i + 2..].iter().collect();
+                let end = rest.find(":]")?;
+                items.push(BracketItem::Class(rest[..end].to_string()));
+                i += 2 + rest[..end].chars().count() + 2;
+            }
+            _ => {
+                let (c, next) = if c == '\\' { (*chars.get(i + 1)?, i + 2) } else { (c, i + 1) };
+                if chars.get(next) == Some(&'-') && chars.get(next + 1).is_some_and(|&e| e != ']') {
+                    let (end, after) = match chars[next + 1] {
+                        '\\' => (*chars.get(next + 2)?, next + 3),
+                        end => (end, next + 2),
+                    };
+                    items.push(BracketItem::Range(c, end));
+                    i = after;
+                } else {
+                    items.push(BracketItem::Char(c));
+                    i = next;
+                }
+            }
+        }
+        first = false;
+    }
+}
+
+fn compile(chars: &[char], extglob: bool) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if extglob && "?*+@!".contains(c) && chars.get(i + 1) == Some(&'(') {
+            if let Some(close) = closing_paren(chars, i + 1) {
+                let alternatives = split_alternatives(&chars[i + 2..close])
+                    .into_iter()
+                    .map(|alternative| compile(alternative, extglob))
+                    .collect();
+                tokens.push(Token::Extended { kind: c, alternatives });
+                i = close + 1;
+                continue;
+            }
+        }
+        match c {
+            '\\' if i + 1 < chars.len() => {
+                tokens.push(Token::Literal(chars[i + 1]));
+                i += 2;
+            }
+            '?' => {
+                tokens.push(Token::Any);
+                i += 1;
+            }
+            '*' => {
+                // Consecutive stars match the same as one.
+                if !matches!(tokens.last(), Some(Token::Star)) {
+                    tokens.push(Token::Star);
+                }
+                i += 1;
+            }
+            '[' => match parse_bracket(chars, i + 1) {
+                Some((token, next)) => {
+                    tokens.push(token);
+                    i = next;
+                }
+                None => {
+                    tokens.push(Token::Literal('['));
+                    i += 1;
+                }
+            },
+            _ => {
+                tokens.push(Token::Literal(c));
+                i += 1;
+            }
+        }
+    }
+    tokens
+}
+
+fn in_class(class: &str, c: char) -> bool {
+    match class {
+        "alnum" => c.is_alphanumeric(),
+        "alpha" => c.is_alphabetic(),
+        "blank" => c == ' ' || c == '\t',
+        "cntrl" => c.is_control(),
+        "digit" => c.is_ascii_digit(),
+        "graph" => !c.is_whitespace() && !c.is_control(),
+        "lower" => c.is_lowercase(),
+        "print" => !c.is_control(),
+        "punct" => c.is_ascii_punctuation(),
+        "space" => c.is_whitespace(),
+        "upper" => c.is_uppercase(),
+        "xdigit" => c.is_ascii_hexdigit(),
+        _ => false,
+    }
+}
+
+fn fold(c: char, ignore_case: bool) -> char {
+    if ignore_case { c.to_lowercase().next().unwrap_or(c) } else { c }
+}
+
+impl Pattern {
+    pub fn new(pattern: &str, extglob: bool, ignore_case: bool) -> Pattern {
+        let chars: Vec<char> = pattern.chars().collect();
+        Pattern { tokens: compile(&chars, extglob), ignore_case }
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        self.match_tokens(&self.tokens, &text)
+    }
+
+    fn match_char(&self, token: &Token, c: char) -> bool {
+        let c = fold(c, self.ignore_case);
+        match token {
+            Token::Literal(l) => fold(*l, self.ignore_case) == c,
+            Token::Any => true,
+            Token::Bracket { negated, items } => {
+                let found = items.iter().any(|item| match item {
+                    BracketItem::Char(l) => fold(*l, self.ignore_case) == c,
+                    BracketItem::Range(start, end) => {
+                        (fold(*start, self.ignore_case)..=fold(*end, self.ignore_case)).contains(&c)
+                    }
+                    BracketItem::Class(class) => in_class(class, c),
+                });
+                found != *negated
+            }
+            Token::Star | Token::Extended { .. } => false,
+        }
+    }
+
+    // Whether `text` is one or more matches of the alternatives in a row.
+    fn repeats(&self, alternatives: &[Vec<Token>], text: &[char]) -> bool {
+        text.is_empty()
+            || (1..=text.len()).any(|end| {
+                alternatives.iter().any(|alt| self.match_tokens(alt, &text[..end])) && self.repeats(alternatives, &text[end..])
+            })
+    }
+
+    fn match_tokens(&self, tokens: &[Token], text: &[char]) -> bool {
+        let Some((token, rest)) = tokens.split_first() else {
+            return text.is_empty();
+        };
+        match token {
+            Token::Star => (0..=text.len()).any(|skip| self.match_tokens(rest, &text[skip..])),
+            Token::Extended { kind, alternatives } => (0..=text.len()).any(|end| {
+                let segment = &text[..end];
+                let any = || alternatives.iter().any(|alt| self.match_tokens(alt, segment));
+                let matched = match kind {
+                    '?' => segment.is_empty() || any(),
+                    '@' => any(),
+                    '*' => self.repeats(alternatives, segment),
+                    '+' => !segment.is_empty() && self.repeats(alternatives, segment),
+                    _ => !any(),
+                };
+                matched && self.match_tokens(rest, &text[end..])
+            }),
+            _ => match text.split_first() {
+                Some((&c, text)) => self.match_char(token, c) && self.match_tokens(rest, text),
+                None => false,
+            },
+        }
+    }
+}
+
+/// Whether `pattern` contains an unescaped special character, i.e. whether
+/// it needs matching at all rather than being used as it is.
+pub(crate) fn has_magic(pattern: &str, extglob: bool) -> bool {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '*' | '?' => return true,
+            '[' if parse_bracket(&chars, i + 1).is_some() => return true,
+            '+' | '@' | '!' if extglob && chars.get(i + 1) == Some(&'(') => return true,
+            _ => {}
+        }
+        i += 1;
+    }
+    false
+}
+
+// Removes the backslashes that protect special characters.
+pub(crate) fn unescape(pattern: &str) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push(chars.next().unwrap_or('\\')),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Pattern::new(pattern, true, false).matches(text)
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*", ""));
+        assert!(matches("a*c", "abbc"));
+        assert!(matches("a?c", "abc"));
+        assert!(!matches("a?c", "ac"));
+        assert!(matches("**b", "aab"));
+        assert!(!matches("a*", "ba"));
+    }
+
+    #[test]
+    fn bracket_expressions() {
+        assert!(matches("[abc]", "b"));
+        assert!(matches("[a-c]x", "bx"));
+        assert!(matches("[!a-c]", "d"));
+        assert!(matches("[^a-c]", "d"));
+        assert!(!matches("[!a-c]", "a"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[a-]", "-"));
+        assert!(matches("[[:digit:][:upper:]]*", "Xy"));
+        assert!(!matches("[[:digit:]]", "x"));
+        // An unclosed bracket is literal.
+        assert!(matches("[ab", "[ab"));
+    }
+
+    #[test]
+    fn escapes_are_literal() {
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "a"));
+        assert!(matches("a\\?", "a?"));
+        assert!(matches("\\[a]", "[a]"));
+    }
+
+    #[test]
+    fn extended_patterns() {
+        assert!(matches("?(a|b)c", "c"));
+        assert!(matches("?(a|b)c", "bc"));
+        assert!(!matches("?(a|b)c", "abc"));
+        assert!(matches("@(foo|bar)", "bar"));
+        assert!(!matches("@(foo|bar)", "foobar"));
+        assert!(matches("*(ab)", ""));
+        assert!(matches("*(ab|c)", "abcab"));
+        assert!(!matches("+(ab)", ""));
+        assert!(matches("+(a|b)x", "abbax"));
+        assert!(matches("!(*.rs)", "main.c"));
+        assert!(!matches("!(*.rs)", "main.rs"));
+        assert!(matches("@(a|+([0-9]))", "123"));
+    }
+
+    #[test]
+    fn extended_patterns_need_extglob() {
+        let pattern = Pattern::new("@(a|b)", false, false);
+        assert!(pattern.matches("@(a|b)"));
+        assert!(!pattern.matches("a"));
+        // Without extglob `?(` is an ordinary `?` followed by a group.
+        assert!(!Pattern::new("?(a)", false, false).matches("a"));
+    }
+
+    #[test]
+    fn ignoring_case() {
+        let pattern = Pattern::new("[a-c]*.TXT", false, true);
+        assert!(pattern.matches("Bar.txt"));
+        assert!(!Pattern::new("[a-c]*.TXT", false, false).matches("Bar.txt"));
+    }
+
+    #[test]
+    fn magic_and_unescape() {
+        assert!(has_magic("a*", false));
+        assert!(has_magic("[ab]", false));
+        assert!(!has_magic("[ab", false));
+        assert!(!has_magic("a\\*", false));
+        assert!(!has_magic("@(a)", false));
+        assert!(has_magic("@(a)", true));
+        assert_eq!(unescape("a\\*b\\\\"), "a*b\\");
+    }
+}
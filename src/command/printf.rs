@@ -0,0 +1,654 @@
+// The `printf` builtin, and the backslash escapes it shares with `echo -e`.
+use std::ffi::CString;
+use std::io::{self, Write};
+use super::{utils, variables, Command, CommandError, Redirection};
+
+const USAGE: &str = "printf: usage: printf [-v var] format [arguments]";
+
+// Where backslash escapes are being decoded; each accepts a slightly different set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum EscapeMode {
+    // The format string: `\NNN` octal, `\"`, `\'` and `\?`; `\c` is literal.
+    Format,
+    // A `%b` argument: `\0NNN` and `\NNN` octal, and `\c` ends the output.
+    Argument,
+    // `echo -e`: only `\0NNN` octal, and `\c` ends the output.
+    Echo,
+    // `$'...'` strings: like the format string, but `\cX` is the control character for X.
+    Ansi,
+}
+
+// Reads up to `max` digits of `radix` from `chars[i..]`. Returns the value, or
+// None if it does not fit in a u32, and the number of digits.
+fn digits(chars: &[char], i: usize, radix: u32, max: usize) -> (Option<u32>, usize) {
+    let mut value = Some(0u32);
+    let mut count = 0;
+    while count < max {
+        let Some(digit) = chars.get(i + count).and_then(|c| c.to_digit(radix)) else {
+            break;
+        };
+        value = value.and_then(|value| value.checked_mul(radix)?.checked_add(digit));
+        count += 1;
+    }
+    (value, count)
+}
+
+fn push_char(out: &mut Vec<u8>, c: char) {
+    let mut buf = [0; 4];
+    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+}
+
+/// Decodes the backslash escape at `chars[i]` (the backslash) into `out`.
+/// Returns the index after it, or None for a `\c` that ends the output.
+pub(crate) fn escape_at(chars: &[char], i: usize, mode: EscapeMode, out: &mut Vec<u8>) -> Option<usize> {
+    let Some(&c) = chars.get(i + 1) else {
+        out.push(b'\\');
+        return Some(i + 1);
+    };
+    let byte = match c {
+        'a' => 0x07,
+        'b' => 0x08,
+        'e' | 'E' => 0x1b,
+        'f' => 0x0c,
+        'n' => b'\n',
+        'r' => b'\r',
+        't' => b'\t',
+        'v' => 0x0b,
+        '\\' => b'\\',
+        '"' if mode != EscapeMode::Echo => b'"',
+        '\'' | '?' if matches!(mode, EscapeMode::Format | EscapeMode::Ansi) => c as u8,
+        'c' if mode == EscapeMode::Ansi => {
+            let Some(&control) = chars.get(i + 2) else {
+                out.extend_from_slice(b"\\c");
+                return Some(i + 2);
+            };
+            out.push(if control == '?' { 0x7f } else { control as u8 & 0x1f });
+            return Some(i + 3);
+        }
+        'c' if mode != EscapeMode::Format => return None,
+        '0' if matches!(mode, EscapeMode::Argument | EscapeMode::Echo) => {
+            let (value, count) = digits(chars, i + 2, 8, 3);
+            out.push(value.unwrap_or_default() as u8);
+            return Some(i + 2 + count);
+        }
+        '0'..='7' if mode != EscapeMode::Echo => {
+            let (value, count) = digits(chars, i + 1, 8, 3);
+            out.push(value.unwrap_or_default() as u8);
+            return Some(i + 1 + count);
+        }
+        'x' | 'u' | 'U' => {
+            let max = match c {
+                'x' => 2,
+                'u' => 4,
+                _ => 8,
+            };
+            let (value, count) = digits(chars, i + 2, 16, max);
+            let value = value.unwrap_or_default();
+            if count == 0 {
+                out.extend_from_slice(&[b'\\', c as u8]);
+            } else if c == 'x' {
+                out.push(value as u8);
+            } else {
+                push_char(out, char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+            return Some(i + 2 + count);
+        }
+        _ => {
+            out.push(b'\\');
+            push_char(out, c);
+            return Some(i + 2);
+        }
+    };
+    out.push(byte);
+    Some(i + 2)
+}
+
+/// Decodes the backslash escapes in `text` into `out`. Returns false if a `\c`
+/// ended the output.
+pub(crate) fn unescape(text: &str, mode: EscapeMode, out: &mut Vec<u8>) -> bool {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\' {
+            match escape_at(&chars, i, mode, out) {
+                Some(next) => i = next,
+                None => return false,
+            }
+        } else {
+            push_char(out, chars[i]);
+            i += 1;
+        }
+    }
+    true
+}
+
+// Quotes `text` so that the shell reads it back as the same word, for `%q`.
+pub(crate) fn quote(text: &str) -> String {
+    if text.is_empty() {
+        return "''".to_string();
+    }
+    if text.chars().any(|c| c.is_control()) {
+        let mut out = String::from("$'");
+        for c in text.chars() {
+            match c {
+                '\n' => out.push_str("\\n"),
+                '\t' => out.push_str("\\t"),
+                '\r' => out.push_str("\\r"),
+                '\x07' => out.push_str("\\a"),
+                '\x08' => out.push_str("\\b"),
+                '\x0b' => out.push_str("\\v"),
+                '\x0c' => out.push_str("\\f"),
+                '\x1b' => out.push_str("\\E"),
+                '\'' | '\\' => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                c if c.is_control() => out.push_str(&format!("\\{:03o}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('\'');
+        return out;
+    }
+    let mut out = String::with_capacity(text.len());
+    for (i, c) in text.chars().enumerate() {
+        if " '\"\\|&;()<>!{}*?[]^$`,".contains(c) || (i == 0 && "#~".contains(c)) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// The value of a leading `'c` or `"c` argument: the code of the character.
+fn char_code(arg: &str) -> Option<u32> {
+    let mut chars = arg.chars();
+    matches!(chars.next(), Some('\'' | '"')).then(|| chars.next().map_or(0, u32::from))
+}
+
+// Parses an integer like strtoimax(3): leading blanks, a sign, and a `0x` or `0` prefix.
+// Returns the value, whether all of `arg` was used, and whether it overflowed.
+fn parse_integer(arg: &str) -> (i64, bool, bool) {
+    let chars: Vec<char> = arg.trim_start().chars().collect();
+    let mut i = 0;
+    let negative = chars.first() == Some(&'-');
+    if matches!(chars.first(), Some('-' | '+')) {
+        i += 1;
+    }
+    let radix = match (chars.get(i), chars.get(i + 1)) {
+        (Some('0'), Some('x' | 'X')) if chars.get(i + 2).is_some_and(char::is_ascii_hexdigit) => {
+            i += 2;
+            16
+        }
+        (Some('0'), _) => 8,
+        _ => 10,
+    };
+    let start = i;
+    let mut value: i64 = 0;
+    let mut overflow = false;
+    while let Some(digit) = chars.get(i).and_then(|c| c.to_digit(radix)) {
+        let next = value.checked_mul(i64::from(radix)).and_then(|v| {
+            if negative { v.checked_sub(i64::from(digit)) } else { v.checked_add(i64::from(digit)) }
+        });
+        match next {
+            Some(next) => value = next,
+            None => {
+                overflow = true;
+                value = if negative { i64::MIN } else { i64::MAX };
+            }
+        }
+        i += 1;
+    }
+    (value, i > start && i == chars.len(), overflow)
+}
+
+// Parses a floating-point number like strtod(3): the longest prefix that is one.
+fn parse_float(arg: &str) -> (f64, bool) {
+    let text = arg.trim_start();
+    for end in (1..=text.len()).rev() {
+        if !text.is_char_boundary(end) {
+            continue;
+        }
+        if let Ok(value) = text[..end].parse::<f64>() {
+            return (value, end == text.len());
+        }
+    }
+    (0.0, false)
+}
+
+// A number to format with the C library.
+#[derive(Clone, Copy)]
+enum CValue {
+    Int(libc::c_longlong),
+    Float(libc::c_double),
+}
+
+// Formats one value with the C library's printf, from a spec such as `%-5.2f`.
+fn c_format(spec: &str, value: CValue) -> Vec<u8> {
+    let Ok(spec) = CString::new(spec) else {
+        return Vec::new();
+    };
+    // SAFETY: `spec` holds a single conversion matching the type of the value, and
+    // callers pass either a null buffer with a size of 0 or a buffer of `size` bytes.
+    let print = |buf: *mut libc::c_char, size: usize| match value {
+        CValue::Int(v) => unsafe { libc::snprintf(buf, size, spec.as_ptr(), v) },
+        CValue::Float(v) => unsafe { libc::snprintf(buf, size, spec.as_ptr(), v) },
+    };
+    let Ok(len) = usize::try_from(print(std::ptr::null_mut(), 0)) else {
+        return Vec::new();
+    };
+    let mut buf = vec![0u8; len + 1];
+    print(buf.as_mut_ptr().cast(), buf.len());
+    buf.truncate(len);
+    buf
+}
+
+// Pads a string conversion to `width`, after cutting it to `precision` bytes.
+fn pad(mut text: Vec<u8>, left: bool, width: usize, precision: Option<usize>) -> Vec<u8> {
+    if let Some(precision) = precision {
+        text.truncate(precision);
+    }
+    if text.len() >= width {
+        return text;
+    }
+    let fill = vec![b' '; width - text.len()];
+    if left {
+        text.extend(fill);
+        text
+    } else {
+        [fill, text].concat()
+    }
+}
+
+struct Printf<'a, 'w> {
+    args: &'a [String],
+    next: usize,
+    status: i32,
+    out: Vec<u8>,
+    // None with `-v`, where the output goes to a variable instead.
+    stdout_writer: Option<&'w mut dyn Write>,
+    stderr_writer: &'w mut dyn Write,
+    // The first error writing the output, after which nothing more is written.
+    write_error: Option<io::Error>,
+}
+
+impl Printf<'_, '_> {
+    // Writes what was formatted so far, so that it comes before an error message.
+    fn flush(&mut self) {
+        if let Some(writer) = self.stdout_writer.as_mut() {
+            if let Err(err) = writer.write_all(&self.out).and_then(|()| writer.flush()) {
+                self.write_error = Some(err);
+                self.stdout_writer = None;
+            }
+            self.out.clear();
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        self.flush();
+        let _ = writeln!(self.stderr_writer, "printf: {}", message);
+    }
+
+    fn next_arg(&mut self) -> Option<&str> {
+        let arg = self.args.get(self.next)?;
+        self.next += 1;
+        Some(arg)
+    }
+
+    fn integer(&mut self) -> i64 {
+        let Some(arg) = self.next_arg().map(str::to_string) else {
+            return 0;
+        };
+        if let Some(code) = char_code(&arg) {
+            return i64::from(code);
+        }
+        let (value, complete, overflow) = parse_integer(&arg);
+        if overflow {
+            self.error(format!("warning: {}: Numerical result out of range", arg));
+        } else if !complete && !arg.is_empty() {
+            self.error(format!("{}: invalid number", arg));
+            self.status = 1;
+        }
+        value
+    }
+
+    fn float(&mut self) -> f64 {
+        let Some(arg) = self.next_arg().map(str::to_string) else {
+            return 0.0;
+        };
+        if let Some(code) = char_code(&arg) {
+            return f64::from(code);
+        }
+        let (value, complete) = parse_float(&arg);
+        if !complete && !arg.is_empty() {
+            self.error(format!("{}: invalid number", arg));
+            self.status = 1;
+        }
+        value
+    }
+
+    // Runs the format once. Returns false if the output must stop: after `\c` in
+    // a `%b` argument, or on an invalid conversion.
+    fn run(&mut self, format: &[char]) -> bool {
+        let mut i = 0;
+        while i < format.len() {
+            match format[i] {
+                '\\' => {
+                    i = escape_at(format, i, EscapeMode::Format, &mut self.out).unwrap_or(i + 2);
+                    continue;
+                }
+                '%' if format.get(i + 1) == Some(&'%') => {
+                    self.out.push(b'%');
+                    i += 2;
+                    continue;
+                }
+                '%' => match self.conversion(format, i + 1) {
+                    Some(next) => i = next,
+                    None => return false,
+                },
+                c => {
+                    push_char(&mut self.out, c);
+                    i += 1;
+                }
+            }
+        }
+        true
+    }
+
+    // A width or precision, which must fit in a C int. `text` is how it was given.
+    fn field_size(&mut self, value: Option<i64>, text: String, what: &str) -> Option<i64> {
+        match value.filter(|&value| i32::try_from(value).is_ok()) {
+            Some(value) => Some(value),
+            None => {
+                self.error(format!("{}: invalid {}", text, what));
+                self.status = 1;
+                None
+            }
+        }
+    }
+
+    // Formats the conversion whose spec starts at `format[i]`, just after the `%`.
+    fn conversion(&mut self, format: &[char], mut i: usize) -> Option<usize> {
+        let start = i - 1;
+        let mut flags = String::new();
+        while let Some(&c) = format.get(i).filter(|c| "-+ #0".contains(**c)) {
+            flags.push(c);
+            i += 1;
+        }
+        let mut width: Option<i64> = None;
+        if format.get(i) == Some(&'*') {
+            let value = self.integer();
+            width = Some(self.field_size(Some(value), value.to_string(), "field width")?);
+            i += 1;
+        } else {
+            let (value, count) = digits(format, i, 10, usize::MAX);
+            if count > 0 {
+                width = Some(self.field_size(value.map(i64::from), format[i..i + count].iter().collect(), "field width")?);
+            }
+            i += count;
+        }
+        let mut precision: Option<i64> = None;
+        if format.get(i) == Some(&'.') {
+            i += 1;
+            if format.get(i) == Some(&'*') {
+                let value = self.integer();
+                precision = Some(self.field_size(Some(value), value.to_string(), "precision")?);
+                i += 1;
+            } else {
+                let (value, count) = digits(format, i, 10, usize::MAX);
+                precision = Some(self.field_size(value.map(i64::from), format[i..i + count].iter().collect(), "precision")?);
+                i += count;
+            }
+        }
+        // A negative width from `*` left-justifies; a negative precision is ignored.
+        if width.is_some_and(|w| w < 0) {
+            flags.push('-');
+        }
+        let width = width.map(i64::unsigned_abs);
+        let precision = precision.and_then(|p| u64::try_from(p).ok());
+        while format.get(i).is_some_and(|c| "hlLjzt".contains(*c)) {
+            i += 1;
+        }
+
+        let Some(&conversion) = format.get(i) else {
+            self.error(format!("`{}': missing format character", format[start..].iter().collect::<String>()));
+            self.status = 1;
+            return None;
+        };
+        let mut spec = format!("%{}", flags);
+        if let Some(width) = width {
+            spec.push_str(&width.to_string());
+        }
+        if let Some(precision) = precision {
+            spec.push_str(&format!(".{}", precision));
+        }
+        let left = flags.contains('-');
+        let width = width.unwrap_or(0) as usize;
+        let precision = precision.map(|p| p as usize);
+        match conversion {
+            'd' | 'i' | 'u' | 'o' | 'x' | 'X' => {
+                let value = self.integer();
+                let out = c_format(&format!("{}ll{}", spec, conversion), CValue::Int(value));
+                self.out.extend(out);
+            }
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'a' | 'A' => {
+                let value = self.float();
+                let out = c_format(&format!("{}{}", spec, conversion), CValue::Float(value));
+                self.out.extend(out);
+            }
+            's' => {
+                let text = self.next_arg().unwrap_or_default().as_bytes().to_vec();
+                self.out.extend(pad(text, left, width, precision));
+            }
+            'c' => {
+                // Like C, an empty or missing argument gives its terminating NUL.
+                let text = self.next_arg().and_then(|arg| arg.chars().next()).unwrap_or('\0').to_string();
+                self.out.extend(pad(text.into_bytes(), left, width, None));
+            }
+            'q' => {
+                let text = quote(self.next_arg().unwrap_or_default());
+                self.out.extend(pad(text.into_bytes(), left, width, precision));
+            }
+            'b' => {
+                let arg = self.next_arg().unwrap_or_default().to_string();
+                let mut text = Vec::new();
+                let complete = unescape(&arg, EscapeMode::Argument, &mut text);
+                self.out.extend(pad(text, left, width, precision));
+                if !complete {
+                    return None;
+                }
+            }
+            c => {
+                self.error(format!("`{}': invalid format character", c));
+                self.status = 1;
+                return None;
+            }
+        }
+        Some(i + 1)
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub(crate) fn printf_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    printf(args, stdout_writer, stderr_writer).unwrap_or_else(|err| utils::write_error("printf", &err, stderr_writer))
+}
+
+fn printf(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> io::Result<i32> {
+    let mut variable = None;
+    let mut args = args;
+    loop {
+        match args.first().map(String::as_str) {
+            Some("--") => {
+                args = &args[1..];
+                break;
+            }
+            Some("-v") => {
+                let Some(name) = args.get(1) else {
+                    let _ = writeln!(stderr_writer, "printf: -v: option requires an argument");
+                    let _ = writeln!(stderr_writer, "{}", USAGE);
+                    return Ok(2);
+                };
+                if !valid_name(name) {
+                    let _ = writeln!(stderr_writer, "printf: `{}': not a valid identifier", name);
+                    return Ok(2);
+                }
+                variable = Some(name.clone());
+                args = &args[2..];
+            }
+            Some(arg) if arg.len() > 1 && arg.starts_with('-') => {
+                let _ = writeln!(stderr_writer, "printf: {}: invalid option", &arg[..2]);
+                let _ = writeln!(stderr_writer, "{}", USAGE);
+                return Ok(2);
+            }
+            _ => break,
+        }
+    }
+    let Some((format, args)) = args.split_first() else {
+        let _ = writeln!(stderr_writer, "{}", USAGE);
+        return Ok(2);
+    };
+
+    let format: Vec<char> = format.chars().collect();
+    let capture = variable.is_some();
+    let stdout_writer = if capture { None } else { Some(stdout_writer as &mut dyn Write) };
+    let mut printf = Printf { args, next: 0, status: 0, out: Vec::new(), stdout_writer, stderr_writer, write_error: None };
+    // The format is reused while arguments remain, as long as it consumes some.
+    loop {
+        let before = printf.next;
+        if !printf.run(&format) || printf.next == before || printf.next >= args.len() {
+            break;
+        }
+    }
+
+    match variable {
+        Some(name) => {
+            if let Err(err) = variables::set(&name, &String::from_utf8_lossy(&printf.out)) {
+                printf.error(err.to_string());
+                printf.status = 1;
+            }
+        }
+        None => printf.flush(),
+    }
+    match printf.write_error {
+        Some(err) => Err(err),
+        None => Ok(printf.status),
+    }
+}
+
+pub(crate) fn parse_printf_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Printf { args, redirections })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs `printf` with `args`, returning its output, error output and status.
+    fn run(args: &[&str]) -> (String, String, i32) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let status = printf_cmd(&args, &mut out, &mut err);
+        (String::from_utf8_lossy(&out).into_owned(), String::from_utf8_lossy(&err).into_owned(), status)
+    }
+
+    fn output(args: &[&str]) -> String {
+        run(args).0
+    }
+
+    fn unescaped(text: &str, mode: EscapeMode) -> (Vec<u8>, bool) {
+        let mut out = Vec::new();
+        let complete = unescape(text, mode, &mut out);
+        (out, complete)
+    }
+
+    #[test]
+    fn escapes_depend_on_the_mode() {
+        assert_eq!(unescaped("a\\tb\\x41\\u00e9", EscapeMode::Format), ("a\tbA\u{e9}".as_bytes().to_vec(), true));
+        assert_eq!(unescaped("\\101\\0101", EscapeMode::Format), (b"A\x081".to_vec(), true));
+        assert_eq!(unescaped("\\101\\0101", EscapeMode::Argument), (b"AA".to_vec(), true));
+        assert_eq!(unescaped("\\101\\0101", EscapeMode::Echo), (b"\\101A".to_vec(), true));
+        assert_eq!(unescaped("\\'\\?\\q", EscapeMode::Format), (b"'?\\q".to_vec(), true));
+        assert_eq!(unescaped("\\'", EscapeMode::Echo), (b"\\'".to_vec(), true));
+        assert_eq!(unescaped("\\cA\\c?", EscapeMode::Ansi), (b"\x01\x7f".to_vec(), true));
+        assert_eq!(unescaped("a\\cb", EscapeMode::Format), (b"a\\cb".to_vec(), true));
+        assert_eq!(unescaped("a\\cb", EscapeMode::Argument), (b"a".to_vec(), false));
+        assert_eq!(unescaped("\\x\\", EscapeMode::Format), (b"\\x\\".to_vec(), true));
+    }
+
+    #[test]
+    fn quoting_reads_back_as_the_same_word() {
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote("a b'c"), "a\\ b\\'c");
+        assert_eq!(quote("~a#b"), "\\~a#b");
+        assert_eq!(quote("x\ty'\x01"), "$'x\\ty\\'\\001'");
+    }
+
+    #[test]
+    fn integers_parse_like_strtoimax() {
+        assert_eq!(parse_integer(" 42"), (42, true, false));
+        assert_eq!(parse_integer("-0x1f"), (-31, true, false));
+        assert_eq!(parse_integer("010"), (8, true, false));
+        assert_eq!(parse_integer("0x"), (0, false, false));
+        assert_eq!(parse_integer("12abc"), (12, false, false));
+        assert_eq!(parse_integer("99999999999999999999"), (i64::MAX, true, true));
+        assert_eq!(parse_integer("-99999999999999999999"), (i64::MIN, true, true));
+        assert_eq!(parse_float("2.5e1x"), (25.0, false));
+        assert_eq!(parse_float("nope"), (0.0, false));
+    }
+
+    #[test]
+    fn padding_cuts_to_the_precision_first() {
+        assert_eq!(pad(b"ab".to_vec(), false, 4, None), b"  ab");
+        assert_eq!(pad(b"ab".to_vec(), true, 4, None), b"ab  ");
+        assert_eq!(pad(b"abcdef".to_vec(), false, 4, Some(3)), b" abc");
+        assert_eq!(c_format("%05.1f", CValue::Float(1.25)), b"001.2");
+        assert_eq!(c_format("%-4llx|", CValue::Int(255)), b"ff  |");
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(output(&["[%5s|%-5s|%.2s]", "ab", "cd", "efgh"]), "[   ab|cd   |ef]");
+        assert_eq!(output(&["%d %x %o %5.1f", "0x1f", "255", "8", "1.75"]), "31 ff 10   1.8");
+        assert_eq!(output(&["%*d|%-*d|", "4", "1", "3", "2"]), "   1|2  |");
+        assert_eq!(output(&["%d %d", "'A", "\"é"]), "65 233");
+        assert_eq!(output(&["%c%c", "hello"]), "h\0");
+        assert_eq!(output(&["%q %q", "a b", ""]), "a\\ b ''");
+        assert_eq!(output(&["%%\\101\\n"]), "%A\n");
+    }
+
+    #[test]
+    fn the_format_is_reused_for_remaining_arguments() {
+        assert_eq!(output(&["%s,%s;", "a", "b", "c"]), "a,b;c,;");
+        assert_eq!(output(&["x", "a", "b"]), "x");
+    }
+
+    #[test]
+    fn backslash_c_in_an_argument_stops_the_output() {
+        assert_eq!(run(&["%b|%s\\n", "a\\tb\\c", "ignored"]), ("a\tb".to_string(), String::new(), 0));
+    }
+
+    #[test]
+    fn errors() {
+        let (out, err, status) = run(&["%d|", "12abc", "7"]);
+        assert_eq!((out.as_str(), status), ("12|7|", 1));
+        assert_eq!(err, "printf: 12abc: invalid number\n");
+        let (out, err, status) = run(&["%d", "99999999999999999999"]);
+        assert_eq!((out.as_str(), status), ("9223372036854775807", 0));
+        assert_eq!(err, "printf: warning: 99999999999999999999: Numerical result out of range\n");
+        assert_eq!(run(&["a%5z"]), ("a".to_string(), "printf: `%5z': missing format character\n".to_string(), 1));
+        assert_eq!(run(&["%y"]), (String::new(), "printf: `y': invalid format character\n".to_string(), 1));
+        assert_eq!(run(&["-x"]).2, 2);
+        assert_eq!(run(&[]).2, 2);
+        assert_eq!(run(&["-v", "1a", "x"]).1, "printf: `1a': not a valid identifier\n");
+    }
+
+    #[test]
+    fn dash_v_assigns_the_output() {
+        assert_eq!(run(&["-v", "printf_test_var", "%03d", "7"]), (String::new(), String::new(), 0));
+        assert_eq!(variables::get("printf_test_var").as_deref(), Some("007"));
+    }
+}
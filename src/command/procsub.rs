@@ -0,0 +1,167 @@
+// Process substitution: `<(cmd)` and `>(cmd)` run cmd with its output or input
+// connected to a pipe, and stand for a path to the other end: `/dev/fd/N` where
+// the system provides it, and a named FIFO otherwise.
+use std::ffi::{CString, OsString};
+use std::fs;
+use std::io;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command as StdCommand, Stdio};
+use super::state::STATE;
+use super::{expand, signals, utils};
+
+// Tells a child shell which FIFO to open, as `fd:path`.
+const FIFO_VAR: &str = "__SHELL_FIFO";
+
+// A process substitution, kept until its process has been reaped.
+pub(crate) struct Substitution {
+    pid: u32,
+    // The shell's end of the pipe, open until the command using it finishes.
+    pipe: Option<OwnedFd>,
+    // The FIFO used instead of a pipe, and whether the command reads from it.
+    fifo: Option<(PathBuf, bool)>,
+}
+
+// Connects `command` to a pipe whose other end the shell keeps as fd N.
+fn through_pipe(mut command: StdCommand, reading: bool) -> io::Result<(Substitution, String)> {
+    if reading {
+        command.stdout(Stdio::piped());
+    } else {
+        command.stdin(Stdio::piped());
+    }
+    let mut child = command.spawn()?;
+    let pipe: OwnedFd = match (child.stdout.take(), child.stdin.take()) {
+        (Some(stdout), _) => stdout.into(),
+        (None, Some(stdin)) => stdin.into(),
+        (None, None) => unreachable!("one end of the child was piped"),
+    };
+    // The pipe stays close-on-exec: only the command given the path inherits it, through `pass_pipes`.
+    let path = format!("/dev/fd/{}", pipe.as_raw_fd());
+    Ok((Substitution { pid: child.id(), pipe: Some(pipe), fifo: None }, path))
+}
+
+// Makes a FIFO in a new temporary directory and has `command` open it.
+fn through_fifo(mut command: StdCommand, reading: bool) -> io::Result<(Substitution, String)> {
+    let mut template = std::env::temp_dir().join("sh-np-XXXXXX").into_os_string().into_vec();
+    template.push(0);
+    // SAFETY: `template` is a writable, NUL-terminated buffer ending in XXXXXX.
+    if unsafe { libc::mkdtemp(template.as_mut_ptr().cast()) }.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    template.pop();
+    let dir = PathBuf::from(OsString::from_vec(template));
+    let fifo = dir.join("fifo");
+    let path = CString::new(fifo.as_os_str().as_bytes())?;
+    // SAFETY: `path` is a valid NUL-terminated string.
+    if unsafe { libc::mkfifo(path.as_ptr(), 0o600) } < 0 {
+        let err = io::Error::last_os_error();
+        let _ = fs::remove_dir(&dir);
+        return Err(err);
+    }
+    let target = if reading { libc::STDOUT_FILENO } else { libc::STDIN_FILENO };
+    let child = match command.env(FIFO_VAR, format!("{}:{}", target, fifo.display())).spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            let _ = fs::remove_dir_all(&dir);
+            return Err(err);
+        }
+    };
+    let path = fifo.to_string_lossy().into_owned();
+    Ok((Substitution { pid: child.id(), pipe: None, fifo: Some((fifo, reading)) }, path))
+}
+
+/// In a child shell started for a process substitution through a FIFO, opens
+/// the FIFO as its input or output. This is left to the child because opening
+/// a FIFO blocks until its other end is opened too.
+pub(crate) fn init() {
+    let Ok(value) = std::env::var(FIFO_VAR) else {
+        return;
+    };
+    std::env::remove_var(FIFO_VAR);
+    let Some((target, path)) = value.split_once(':') else {
+        return;
+    };
+    let (target, flags) = match target {
+        "0" => (libc::STDIN_FILENO, libc::O_RDONLY),
+        _ => (libc::STDOUT_FILENO, libc::O_WRONLY),
+    };
+    let Ok(path) = CString::new(path) else {
+        return;
+    };
+    // SAFETY: `path` is a valid NUL-terminated string, and `fd` is closed once duplicated.
+    unsafe {
+        let fd = libc::open(path.as_ptr(), flags);
+        if fd >= 0 {
+            libc::dup2(fd, target);
+            libc::close(fd);
+        }
+    }
+}
+
+/// Starts `cmd` for `<(cmd)` (`reading`, as the command given the path reads
+/// from it) or `>(cmd)`, and returns the path that stands for it. The process
+/// becomes `$!`.
+pub(crate) fn start(cmd: &str, reading: bool) -> Option<String> {
+    let command = expand::subshell(cmd)?;
+    let started = match Path::new("/dev/fd").is_dir() {
+        true => through_pipe(command, reading),
+        false => through_fifo(command, reading),
+    };
+    match started {
+        Ok((substitution, path)) => {
+            let mut shell = STATE.write().unwrap();
+            shell.last_background = Some(substitution.pid);
+            shell.process_substitutions.push(substitution);
+            Some(path)
+        }
+        Err(err) => {
+            eprintln!("cannot make pipe for process substitution: {}", utils::describe_io_error(&err));
+            None
+        }
+    }
+}
+
+/// Has `command`, the one the paths were given to, inherit the shell's ends of
+/// the pipes, which are not passed to any other child.
+pub(crate) fn pass_pipes(command: &mut StdCommand) {
+    let shell = STATE.read().unwrap();
+    let fds = shell.process_substitutions.iter().filter_map(|substitution| substitution.pipe.as_ref());
+    utils::inherit_fds(command, fds.map(AsRawFd::as_raw_fd).collect());
+}
+
+// Opens the other end of a FIFO without blocking, so that a child still
+// waiting to open it goes on (and finds the other end closed) instead of hanging.
+fn release(fifo: &Path, reading: bool) {
+    let Ok(path) = CString::new(fifo.as_os_str().as_bytes()) else {
+        return;
+    };
+    let flags = if reading { libc::O_RDONLY } else { libc::O_WRONLY };
+    // SAFETY: `path` is a valid NUL-terminated string; the descriptor is closed right away.
+    unsafe {
+        let fd = libc::open(path.as_ptr(), flags | libc::O_NONBLOCK);
+        if fd >= 0 {
+            libc::close(fd);
+        }
+    }
+}
+
+/// Closes the shell's ends of the process substitutions once the command they
+/// were given to has finished, and reaps those whose processes have exited.
+pub(crate) fn finish() {
+    let mut shell = STATE.write().unwrap();
+    if shell.process_substitutions.is_empty() {
+        return;
+    }
+    for substitution in &mut shell.process_substitutions {
+        substitution.pipe = None;
+        if let Some((fifo, reading)) = substitution.fifo.take() {
+            release(&fifo, reading);
+            if let Some(dir) = fifo.parent() {
+                let _ = fs::remove_dir_all(dir);
+            }
+        }
+    }
+    // Those still running are checked again after the next command.
+    shell.process_substitutions.retain(|substitution| matches!(signals::wait_pid(substitution.pid, libc::WNOHANG), Ok(None)));
+}
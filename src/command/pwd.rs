@@ -1,26 +1,64 @@
 use std::env::current_dir;
 use std::io::Write;
-use super::{Command, CommandError, Redirection};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use super::{utils, variables, Command, CommandError, Redirection};
 
-pub(crate) fn pwd_cmd(stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) {
-    match current_dir() {
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.metadata(), b.metadata()) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+// The working directory as tracked in PWD, which may go through symlinks.
+// Falls back to the physical directory when PWD is missing or stale.
+pub(crate) fn logical_pwd() -> Option<PathBuf> {
+    let physical = current_dir().ok();
+    if let Some(pwd) = variables::get("PWD").map(PathBuf::from) {
+        if pwd.is_absolute() && physical.as_deref().is_some_and(|p| same_file(&pwd, p)) {
+            return Some(pwd);
+        }
+    }
+    physical
+}
+
+// Makes sure PWD describes the working directory the shell started in.
+pub(crate) fn init_pwd() {
+    if let Some(pwd) = logical_pwd() {
+        let _ = variables::set("PWD", &pwd.to_string_lossy());
+    }
+}
+
+pub(crate) fn pwd_cmd(physical: bool, stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    let path = if physical { current_dir() } else { logical_pwd().ok_or_else(|| std::io::ErrorKind::NotFound.into()) };
+    match path {
         Ok(path) => {
             if let Some(path_str) = path.to_str() {
-                writeln!(stdout_writer, "{}", path_str).unwrap();
+                match writeln!(stdout_writer, "{}", path_str) {
+                    Ok(()) => 0,
+                    Err(err) => utils::write_error("pwd", &err, stderr_writer),
+                }
             } else {
-                writeln!(stderr_writer, "pwd: unable to convert path to string").unwrap();
+                let _ = writeln!(stderr_writer, "pwd: unable to convert path to string");
+                1
             }
         }
         Err(e) => {
-            writeln!(stderr_writer, "pwd: error retrieving current directory: {}", e).unwrap();
+            let _ = writeln!(stderr_writer, "pwd: error retrieving current directory: {}", e);
+            1
         }
     }
 }
 
-pub(crate) fn parse_pwd_cmd(args: &str, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
-    if args.trim().is_empty() {
-        Ok(Command::Pwd { redirections })
-    } else {
-        Err(CommandError::InvalidArguments("pwd".to_string()))
+pub(crate) fn parse_pwd_cmd(args: &[String], redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    let mut physical = false;
+    for arg in args {
+        match arg.as_str() {
+            "-L" => physical = false,
+            "-P" => physical = true,
+            _ => return Err(CommandError::InvalidArguments("pwd".to_string())),
+        }
     }
-}
\ No newline at end of file
+    Ok(Command::Pwd { physical, redirections })
+}
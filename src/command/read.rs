@@ -0,0 +1,488 @@
+// The `read` builtin: reads a line and splits it into variables with IFS.
+use std::fs::File;
+use std::io::{self, BufRead, Read, StdinLock, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::time::{Duration, Instant};
+use super::{utils, variables, Command, CommandError, Redirection};
+
+const USAGE: &str =
+    "read: usage: read [-ers] [-a array] [-d delim] [-i text] [-n nchars] [-N nchars] [-p prompt] [-t timeout] [-u fd] [name ...]";
+
+// Exit status when the timeout expires: 128 + SIGALRM, as bash reports it.
+const TIMEOUT_STATUS: i32 = 128 + libc::SIGALRM;
+
+const DEFAULT_IFS: &str = " \t\n";
+
+#[derive(Default)]
+struct Options {
+    raw: bool,
+    silent: bool,
+    prompt: Option<String>,
+    timeout: Option<f64>,
+    nchars: Option<usize>,
+    // `-N`: exactly `nchars` characters, delimiters included, and no splitting.
+    exact: bool,
+    delim: Option<char>,
+    // `-e`: read the line with line editing, starting from the `-i` text.
+    edit: bool,
+    initial: Option<String>,
+    array: Option<String>,
+    fd: Option<RawFd>,
+    names: Vec<String>,
+}
+
+// Where the input comes from.
+enum Source {
+    // The shell's own standard input when it is not a terminal. It shares the
+    // buffer the shell reads its commands from, so that a script can read the
+    // lines that follow it.
+    Stdin(StdinLock<'static>),
+    // A terminal or a descriptor given with `-u`, read a byte at a time so that
+    // nothing after the delimiter is consumed.
+    Fd(RawFd),
+    // A file redirected with `<`.
+    File(File),
+    // A line already read with line editing, for `-e`.
+    Edited(io::Cursor<Vec<u8>>),
+}
+
+impl Source {
+    fn fd(&self) -> RawFd {
+        match self {
+            Source::Stdin(_) => 0,
+            Source::Fd(fd) => *fd,
+            Source::File(file) => file.as_raw_fd(),
+            Source::Edited(_) => 0,
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0u8; 1];
+        let count = match self {
+            Source::Stdin(stdin) => {
+                let buf = stdin.fill_buf()?;
+                let Some(&first) = buf.first() else {
+                    return Ok(None);
+                };
+                stdin.consume(1);
+                return Ok(Some(first));
+            }
+            // SAFETY: `byte` is a valid buffer of one byte.
+            Source::Fd(fd) => unsafe { libc::read(*fd, byte.as_mut_ptr().cast(), 1) },
+            Source::File(file) => file.read(&mut byte)? as isize,
+            Source::Edited(line) => line.read(&mut byte)? as isize,
+        };
+        match count {
+            0 => Ok(None),
+            n if n < 0 => Err(io::Error::last_os_error()),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    // Whether input arrives before `deadline`.
+    fn wait(&mut self, deadline: Instant) -> bool {
+        // What is already buffered needs no waiting for.
+        let ready = match self {
+            Source::Stdin(stdin) => buffered(stdin),
+            Source::Edited(_) => true,
+            _ => false,
+        };
+        if ready {
+            return true;
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let mut poll = libc::pollfd { fd: self.fd(), events: libc::POLLIN, revents: 0 };
+        let millis = libc::c_int::try_from(remaining.as_millis()).unwrap_or(libc::c_int::MAX);
+        // SAFETY: `poll` is a single valid pollfd.
+        unsafe { libc::poll(&mut poll, 1, millis) > 0 }
+    }
+}
+
+// Whether the shell's input buffer holds anything, or the input is at its end.
+// With the descriptor non-blocking, fill_buf returns what is buffered and fails
+// rather than waiting when there is nothing.
+fn buffered(stdin: &mut StdinLock) -> bool {
+    // SAFETY: F_GETFL only reads the flags of standard input.
+    let flags = unsafe { libc::fcntl(0, libc::F_GETFL) };
+    if flags < 0 {
+        return false;
+    }
+    // SAFETY: F_SETFL only changes the flags of standard input.
+    unsafe { libc::fcntl(0, libc::F_SETFL, flags | libc::O_NONBLOCK) };
+    let result = stdin.fill_buf().map(|_| ());
+    // SAFETY: F_SETFL puts back the flags read above.
+    unsafe { libc::fcntl(0, libc::F_SETFL, flags) };
+    !matches!(result, Err(err) if err.kind() == io::ErrorKind::WouldBlock)
+}
+
+// What ended a read.
+enum End {
+    Delimiter,
+    Count,
+    Eof,
+    Timeout,
+}
+
+// Reads one byte, unless `deadline` passes first. Input that never stops, as
+// from /dev/zero, must not keep the deadline from being noticed.
+fn next_byte(source: &mut Source, deadline: Option<Instant>) -> Result<Option<u8>, End> {
+    if let Some(deadline) = deadline {
+        if Instant::now() >= deadline || !source.wait(deadline) {
+            return Err(End::Timeout);
+        }
+    }
+    Ok(source.read_byte().ok().flatten())
+}
+
+// Reads one UTF-8 character. Invalid bytes become U+FFFD.
+fn read_char(source: &mut Source, deadline: Option<Instant>) -> Result<Option<char>, End> {
+    let Some(first) = next_byte(source, deadline)? else {
+        return Ok(None);
+    };
+    let len = match first {
+        0x00..=0x7f => return Ok(Some(char::from(first))),
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Ok(Some(char::REPLACEMENT_CHARACTER)),
+    };
+    let mut bytes = vec![first];
+    while bytes.len() < len {
+        match next_byte(source, deadline)? {
+            Some(byte) => bytes.push(byte),
+            None => break,
+        }
+    }
+    Ok(Some(std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()).unwrap_or(char::REPLACEMENT_CHARACTER)))
+}
+
+// Reads up to the delimiter. Each character comes with whether a backslash
+// escaped it, which protects it from field splitting.
+fn read_input(source: &mut Source, options: &Options) -> (Vec<(char, bool)>, End) {
+    let delim = options.delim.unwrap_or('\n');
+    let deadline = options.timeout.map(|t| Instant::now() + Duration::from_secs_f64(t));
+    let mut input = Vec::new();
+    let mut escaped = false;
+    loop {
+        if options.nchars.is_some_and(|n| input.len() >= n) {
+            return (input, End::Count);
+        }
+        let c = match read_char(source, deadline) {
+            Ok(Some(c)) => c,
+            Ok(None) => return (input, End::Eof),
+            Err(end) => return (input, end),
+        };
+        if escaped {
+            escaped = false;
+            // A backslash-newline continues the line.
+            if c != '\n' {
+                input.push((c, true));
+            }
+            continue;
+        }
+        if c == '\\' && !options.raw {
+            escaped = true;
+            continue;
+        }
+        if c == delim && !options.exact {
+            return (input, End::Delimiter);
+        }
+        input.push((c, false));
+    }
+}
+
+struct Splitter<'a> {
+    input: &'a [(char, bool)],
+    ifs: &'a str,
+    pos: usize,
+}
+
+impl Splitter<'_> {
+    fn is_delimiter(&self, i: usize) -> bool {
+        self.input.get(i).is_some_and(|&(c, escaped)| !escaped && self.ifs.contains(c))
+    }
+
+    fn is_blank(&self, i: usize) -> bool {
+        self.is_delimiter(i) && matches!(self.input[i].0, ' ' | '\t' | '\n')
+    }
+
+    fn skip_blanks(&mut self) {
+        while self.is_blank(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    // Skips one separator: blanks around at most one other IFS character.
+    fn skip_separator(&mut self) {
+        self.skip_blanks();
+        if self.is_delimiter(self.pos) {
+            self.pos += 1;
+            self.skip_blanks();
+        }
+    }
+
+    fn field(&mut self) -> String {
+        let start = self.pos;
+        while self.pos < self.input.len() && !self.is_delimiter(self.pos) {
+            self.pos += 1;
+        }
+        self.input[start..self.pos].iter().map(|&(c, _)| c).collect()
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+}
+
+// Splits `input` into `count` fields; the last one gets the rest of the line.
+fn split_fields(input: &[(char, bool)], ifs: &str, count: usize) -> Vec<String> {
+    let mut splitter = Splitter { input, ifs, pos: 0 };
+    splitter.skip_blanks();
+    let mut fields = Vec::with_capacity(count);
+    for _ in 1..count {
+        fields.push(splitter.field());
+        splitter.skip_separator();
+    }
+
+    // Trailing IFS blanks are not part of the remainder.
+    let mut end = input.len();
+    while end > splitter.pos && splitter.is_blank(end - 1) {
+        end -= 1;
+    }
+    let rest = &input[splitter.pos..end];
+    // A remainder that is a single field loses its trailing delimiter.
+    let mut last = Splitter { input: rest, ifs, pos: 0 };
+    let field = last.field();
+    last.skip_separator();
+    fields.push(if last.at_end() { field } else { rest.iter().map(|&(c, _)| c).collect() });
+    fields
+}
+
+// Splits `input` into all of its fields, for `-a`.
+fn split_all(input: &[(char, bool)], ifs: &str) -> Vec<String> {
+    let mut splitter = Splitter { input, ifs, pos: 0 };
+    splitter.skip_blanks();
+    let mut fields = Vec::new();
+    while !splitter.at_end() {
+        fields.push(splitter.field());
+        splitter.skip_separator();
+    }
+    fields
+}
+
+fn valid_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_options(args: &[String], stderr_writer: &mut dyn Write) -> Result<Options, i32> {
+    let mut options = Options::default();
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+        if arg == "--" {
+            i += 1;
+            break;
+        }
+        let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+            break;
+        };
+        for (pos, flag) in flags.char_indices() {
+            match flag {
+                'r' => options.raw = true,
+                's' => options.silent = true,
+                'e' => options.edit = true,
+                'p' | 't' | 'n' | 'N' | 'd' | 'a' | 'i' | 'u' => {
+                    // The value is the rest of the word, or the next word.
+                    let rest = &flags[pos + 1..];
+                    let value = if !rest.is_empty() {
+                        rest.to_string()
+                    } else if let Some(next) = args.get(i + 1) {
+                        i += 1;
+                        next.clone()
+                    } else {
+                        let _ = writeln!(stderr_writer, "read: -{}: option requires an argument", flag);
+                        let _ = writeln!(stderr_writer, "{}", USAGE);
+                        return Err(2);
+                    };
+                    match flag {
+                        'p' => options.prompt = Some(value),
+                        't' => match value.parse::<f64>() {
+                            Ok(t) if t >= 0.0 && t.is_finite() => options.timeout = Some(t),
+                            _ => {
+                                let _ = writeln!(stderr_writer, "read: {}: invalid timeout specification", value);
+                                return Err(1);
+                            }
+                        },
+                        'n' | 'N' => match value.parse::<usize>() {
+                            Ok(n) => {
+                                options.nchars = Some(n);
+                                options.exact = flag == 'N';
+                            }
+                            Err(_) => {
+                                let _ = writeln!(stderr_writer, "read: {}: invalid number", value);
+                                return Err(1);
+                            }
+                        },
+                        // An empty delimiter means NUL.
+                        'd' => options.delim = Some(value.chars().next().unwrap_or('\0')),
+                        'a' => options.array = Some(value),
+                        'i' => options.initial = Some(value),
+                        _ => {
+                            // SAFETY: F_GETFD only queries the descriptor.
+                            match value.parse::<RawFd>() {
+                                Ok(fd) if unsafe { libc::fcntl(fd, libc::F_GETFD) } != -1 => options.fd = Some(fd),
+                                _ => {
+                                    let _ = writeln!(stderr_writer, "read: {}: invalid file descriptor: Bad file descriptor", value);
+                                    return Err(1);
+                                }
+                            }
+                        }
+                    }
+                    break;
+                }
+                _ => {
+                    let _ = writeln!(stderr_writer, "read: -{}: invalid option", flag);
+                    let _ = writeln!(stderr_writer, "{}", USAGE);
+                    return Err(2);
+                }
+            }
+        }
+        i += 1;
+    }
+    options.names = args[i..].to_vec();
+    for name in options.names.iter().chain(&options.array) {
+        if !valid_name(name) {
+            let _ = writeln!(stderr_writer, "read: `{}': not a valid identifier", name);
+            return Err(1);
+        }
+    }
+    Ok(options)
+}
+
+// Puts a terminal in the mode `read` needs, and restores it when dropped.
+struct TerminalMode {
+    fd: RawFd,
+    saved: libc::termios,
+}
+
+impl TerminalMode {
+    fn set(fd: RawFd, silent: bool, per_char: bool) -> Option<TerminalMode> {
+        // SAFETY: isatty accepts any descriptor number.
+        if (!silent && !per_char) || unsafe { libc::isatty(fd) } != 1 {
+            return None;
+        }
+        // SAFETY: termios is plain data, filled in by tcgetattr before use.
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: `saved` is a valid termios to fill.
+        if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+            return None;
+        }
+        let mut mode = saved;
+        if silent {
+            mode.c_lflag &= !(libc::ECHO | libc::ECHONL);
+        }
+        // Without canonical mode characters arrive as they are typed, so that
+        // `-n` and `-d` can stop without waiting for a newline.
+        if per_char {
+            mode.c_lflag &= !libc::ICANON;
+            mode.c_cc[libc::VMIN] = 1;
+            mode.c_cc[libc::VTIME] = 0;
+        }
+        // SAFETY: `mode` is a valid termios derived from the current one.
+        unsafe { libc::tcsetattr(fd, libc::TCSANOW, &mode) };
+        Some(TerminalMode { fd, saved })
+    }
+}
+
+impl Drop for TerminalMode {
+    fn drop(&mut self) {
+        // SAFETY: `saved` is the termios read from this descriptor.
+        unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, &self.saved) };
+    }
+}
+
+fn assign(options: &Options, input: &[(char, bool)]) -> Result<(), CommandError> {
+    let ifs = variables::get("IFS").unwrap_or_else(|| DEFAULT_IFS.to_string());
+    // `-N` gives everything to the first name, and nothing to the others.
+    if options.exact {
+        let text: String = input.iter().map(|&(c, _)| c).collect();
+        if let Some(array) = &options.array {
+            return variables::set_array(array, variables::indexed(vec![text]));
+        }
+        let mut values = std::iter::once(text).chain(std::iter::repeat(String::new()));
+        for name in options.names.iter() {
+            variables::set(name, &values.next().unwrap_or_default())?;
+        }
+        if options.names.is_empty() {
+            return variables::set("REPLY", &values.next().unwrap_or_default());
+        }
+        return Ok(());
+    }
+    if let Some(array) = &options.array {
+        return variables::set_array(array, variables::indexed(split_all(input, &ifs)));
+    }
+    if options.names.is_empty() {
+        // REPLY gets the line as it is, blanks included.
+        return variables::set("REPLY", &input.iter().map(|&(c, _)| c).collect::<String>());
+    }
+    for (name, value) in options.names.iter().zip(split_fields(input, &ifs, options.names.len())) {
+        variables::set(name, &value)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_cmd(args: &[String], redirections: &[Redirection], stderr_writer: &mut dyn Write) -> i32 {
+    let options = match parse_options(args, stderr_writer) {
+        Ok(options) => options,
+        Err(status) => return status,
+    };
+    // `-u 0` is standard input, which may be redirected.
+    let mut source = match (options.fd.filter(|&fd| fd != 0), utils::get_input_file(redirections)) {
+        (Some(fd), _) => Source::Fd(fd),
+        (None, Some(Ok(file))) => Source::File(file),
+        (None, Some(Err(_))) => return 1,
+        // SAFETY: isatty accepts any descriptor number.
+        (None, None) if unsafe { libc::isatty(0) } == 1 => Source::Fd(0),
+        (None, None) => Source::Stdin(io::stdin().lock()),
+    };
+
+    // `-t 0` only checks whether input is available.
+    if options.timeout == Some(0.0) {
+        return i32::from(!source.wait(Instant::now()));
+    }
+
+    // SAFETY: isatty accepts any descriptor number.
+    let terminal = unsafe { libc::isatty(source.fd()) } == 1;
+    // Line editing only works on the shell's own terminal.
+    if options.edit && terminal && source.fd() == 0 {
+        let prompt = options.prompt.as_deref().unwrap_or_default();
+        let initial = options.initial.as_deref().unwrap_or_default();
+        let line = rustyline::DefaultEditor::new().and_then(|mut editor| editor.readline_with_initial(prompt, (initial, "")));
+        match line {
+            Ok(line) => source = Source::Edited(io::Cursor::new(format!("{}\n", line).into_bytes())),
+            Err(_) => return 1,
+        }
+    } else if let Some(prompt) = options.prompt.as_ref().filter(|_| terminal) {
+        let _ = write!(stderr_writer, "{}", prompt);
+        let _ = stderr_writer.flush();
+    }
+    let per_char = options.nchars.is_some() || options.delim.is_some_and(|d| d != '\n');
+    let mode = TerminalMode::set(source.fd(), options.silent, per_char);
+    let (input, end) = read_input(&mut source, &options);
+    drop(mode);
+
+    // Whatever was read is assigned, even when the input ended early.
+    if let Err(err) = assign(&options, &input) {
+        let _ = writeln!(stderr_writer, "read: {}", err);
+        return 1;
+    }
+    match end {
+        End::Delimiter | End::Count => 0,
+        End::Eof => 1,
+        End::Timeout => TIMEOUT_STATUS,
+    }
+}
+
+pub(crate) fn parse_read_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Read { args, redirections })
+}
@@ -0,0 +1,161 @@
+// The `set` builtin and the shell options it controls.
+use std::io::{self, Write};
+use super::state::STATE;
+use super::{utils, Command, CommandError, Redirection};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ShellOption {
+    Errexit,
+    Noglob,
+    Noexec,
+    Nounset,
+    Xtrace,
+    Noclobber,
+    Pipefail,
+}
+
+// Long names and single-letter flags, in the order the letters appear in `$-`.
+const OPTIONS: &[(ShellOption, &str, Option<char>)] = &[
+    (ShellOption::Errexit, "errexit", Some('e')),
+    (ShellOption::Noglob, "noglob", Some('f')),
+    (ShellOption::Noexec, "noexec", Some('n')),
+    (ShellOption::Nounset, "nounset", Some('u')),
+    (ShellOption::Xtrace, "xtrace", Some('x')),
+    (ShellOption::Noclobber, "noclobber", Some('C')),
+    (ShellOption::Pipefail, "pipefail", None),
+];
+
+pub(crate) fn enabled(option: ShellOption) -> bool {
+    STATE.read().unwrap().options.contains(&option)
+}
+
+/// Whether the option called `name` is on, for `test -o`.
+pub(crate) fn is_option_set(name: &str) -> bool {
+    OPTIONS.iter().any(|&(option, n, _)| n == name && enabled(option))
+}
+
+fn set_option(option: ShellOption, on: bool) {
+    let mut shell = STATE.write().unwrap();
+    if on {
+        shell.options.insert(option);
+    } else {
+        shell.options.remove(&option);
+    }
+}
+
+/// The value of `$-`: the letters of the enabled options.
+pub(crate) fn flags() -> String {
+    let shell = STATE.read().unwrap();
+    let mut flags = String::new();
+    for &(option, _, letter) in OPTIONS {
+        if let Some(letter) = letter.filter(|_| shell.options.contains(&option)) {
+            flags.push(letter);
+        }
+        // `i` sits between `f` and `n` in bash's table.
+        if option == ShellOption::Noglob && shell.interactive {
+            flags.push('i');
+        }
+    }
+    // Last comes where the commands are read from.
+    flags.push(if shell.command_string { 'c' } else { 's' });
+    flags
+}
+
+/// The options as arguments for a child shell, so that `$(...)` inherits them.
+pub(crate) fn option_args() -> Vec<String> {
+    let shell = STATE.read().unwrap();
+    OPTIONS
+        .iter()
+        .filter(|(option, _, _)| shell.options.contains(option))
+        .flat_map(|&(_, name, _)| ["-o".to_string(), name.to_string()])
+        .collect()
+}
+
+fn print_options(reusable: bool, stdout_writer: &mut dyn Write) -> io::Result<()> {
+    let mut sorted = OPTIONS.to_vec();
+    sorted.sort_by_key(|&(_, name, _)| name);
+    for (option, name, _) in sorted {
+        let on = enabled(option);
+        if reusable {
+            writeln!(stdout_writer, "set {}o {}", if on { '-' } else { '+' }, name)?;
+        } else {
+            writeln!(stdout_writer, "{:<15}\t{}", name, if on { "on" } else { "off" })?;
+        }
+    }
+    Ok(())
+}
+
+fn print_variables(stdout_writer: &mut dyn Write) -> io::Result<()> {
+    let variables = STATE.read().unwrap().scalars.clone();
+    for (name, value) in variables {
+        writeln!(stdout_writer, "{}={}", name, utils::quote_if_needed(&value))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn set_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    if args.is_empty() {
+        return match print_variables(stdout_writer) {
+            Ok(()) => 0,
+            Err(err) => utils::write_error("set", &err, stderr_writer),
+        };
+    }
+
+    let mut args = args.iter();
+    let mut positional = None;
+    while let Some(arg) = args.next() {
+        let on = arg.starts_with('-');
+        match arg.as_str() {
+            "--" => {
+                positional = Some(args.by_ref().cloned().collect());
+                break;
+            }
+            // A lone `-` turns off -x and ends the options.
+            "-" => {
+                set_option(ShellOption::Xtrace, false);
+                positional = Some(args.by_ref().cloned().collect());
+                break;
+            }
+            _ if arg.len() > 1 && (on || arg.starts_with('+')) => {
+                for letter in arg[1..].chars() {
+                    if letter == 'o' {
+                        let Some(name) = args.next() else {
+                            if let Err(err) = print_options(!on, stdout_writer) {
+                                return utils::write_error("set", &err, stderr_writer);
+                            }
+                            continue;
+                        };
+                        match OPTIONS.iter().find(|&&(_, n, _)| n == name) {
+                            Some(&(option, _, _)) => set_option(option, on),
+                            None => {
+                                let _ = writeln!(stderr_writer, "set: {}: invalid option name", name);
+                                return 1;
+                            }
+                        }
+                        continue;
+                    }
+                    match OPTIONS.iter().find(|&&(_, _, l)| l == Some(letter)) {
+                        Some(&(option, _, _)) => set_option(option, on),
+                        None => {
+                            let _ = writeln!(stderr_writer, "set: {}{}: invalid option", &arg[..1], letter);
+                            let _ = writeln!(stderr_writer, "set: usage: set [-efnuxC] [-o option-name] [--] [-] [arg ...]");
+                            return 2;
+                        }
+                    }
+                }
+            }
+            _ => {
+                positional = Some(std::iter::once(arg).chain(args.by_ref()).cloned().collect());
+                break;
+            }
+        }
+    }
+    if let Some(positional) = positional {
+        STATE.write().unwrap().positional = positional;
+    }
+    0
+}
+
+pub(crate) fn parse_set_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Set { args, redirections })
+}
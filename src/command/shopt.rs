@@ -0,0 +1,149 @@
+// The `shopt` builtin: bash's options beyond the POSIX ones of `set`.
+use std::io::Write;
+use super::set;
+use super::state::STATE;
+use super::{utils, Command, CommandError, Redirection};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ShoptOption {
+    Autocd,
+    Cdspell,
+    Checkwinsize,
+    Dotglob,
+    Extglob,
+    Globstar,
+    Histappend,
+    Nocasematch,
+    Nullglob,
+    XpgEcho,
+}
+
+// Sorted by name, the order `shopt` lists them in.
+const OPTIONS: &[(ShoptOption, &str)] = &[
+    (ShoptOption::Autocd, "autocd"),
+    (ShoptOption::Cdspell, "cdspell"),
+    (ShoptOption::Checkwinsize, "checkwinsize"),
+    (ShoptOption::Dotglob, "dotglob"),
+    (ShoptOption::Extglob, "extglob"),
+    (ShoptOption::Globstar, "globstar"),
+    (ShoptOption::Histappend, "histappend"),
+    (ShoptOption::Nocasematch, "nocasematch"),
+    (ShoptOption::Nullglob, "nullglob"),
+    (ShoptOption::XpgEcho, "xpg_echo"),
+];
+
+pub(crate) fn enabled(option: ShoptOption) -> bool {
+    STATE.read().unwrap().shopt.contains(&option)
+}
+
+/// The options that are on as `-O name` arguments for a child shell.
+pub(crate) fn option_args() -> Vec<String> {
+    let shell = STATE.read().unwrap();
+    OPTIONS
+        .iter()
+        .filter(|(option, _)| shell.shopt.contains(option))
+        .flat_map(|&(_, name)| ["-O".to_string(), name.to_string()])
+        .collect()
+}
+
+fn set_option(option: ShoptOption, on: bool) {
+    let mut shell = STATE.write().unwrap();
+    if on {
+        shell.shopt.insert(option);
+    } else {
+        shell.shopt.remove(&option);
+    }
+}
+
+pub(crate) fn shopt_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    let mut enable = None;
+    let mut quiet = false;
+    let mut reusable = false;
+    let mut set_options = false;
+    let mut names = Vec::new();
+    for arg in args {
+        match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() && names.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        's' => enable = Some(true),
+                        'u' => enable = Some(false),
+                        'q' => quiet = true,
+                        'p' => reusable = true,
+                        'o' => set_options = true,
+                        _ => {
+                            let _ = writeln!(stderr_writer, "shopt: -{}: invalid option", flag);
+                            let _ = writeln!(stderr_writer, "shopt: usage: shopt [-pqsu] [-o] [optname ...]");
+                            return 2;
+                        }
+                    }
+                }
+            }
+            _ => names.push(arg.as_str()),
+        }
+    }
+
+    // `-o` works on the options of `set` instead.
+    if set_options {
+        let mut set_args: Vec<String> = Vec::new();
+        match enable {
+            Some(on) => {
+                for name in &names {
+                    set_args.push(if on { "-o" } else { "+o" }.to_string());
+                    set_args.push(name.to_string());
+                }
+            }
+            None => set_args.push(if reusable { "+o" } else { "-o" }.to_string()),
+        }
+        return set::set_cmd(&set_args, stdout_writer, stderr_writer);
+    }
+
+    let mut selected = Vec::new();
+    for name in &names {
+        match OPTIONS.iter().find(|&&(_, n)| n == *name) {
+            Some(&entry) => selected.push(entry),
+            None => {
+                let _ = writeln!(stderr_writer, "shopt: {}: invalid shell option name", name);
+                return 1;
+            }
+        }
+    }
+
+    if let Some(on) = enable {
+        for &(option, _) in &selected {
+            set_option(option, on);
+        }
+        if !selected.is_empty() {
+            return 0;
+        }
+    }
+
+    // Without names, -s and -u list the options that are on or off.
+    let listing = names.is_empty();
+    if listing {
+        selected = OPTIONS.iter().copied().filter(|&(option, _)| enable.map_or(true, |on| on == enabled(option))).collect();
+    }
+    let mut status = 0;
+    for (option, name) in selected {
+        let on = enabled(option);
+        if !on {
+            status = 1;
+        }
+        if quiet {
+            continue;
+        }
+        let written = if reusable {
+            writeln!(stdout_writer, "shopt {} {}", if on { "-s" } else { "-u" }, name)
+        } else {
+            writeln!(stdout_writer, "{:<15}\t{}", name, if on { "on" } else { "off" })
+        };
+        if let Err(err) = written {
+            return utils::write_error("shopt", &err, stderr_writer);
+        }
+    }
+    if listing { 0 } else { status }
+}
+
+pub(crate) fn parse_shopt_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Shopt { args, redirections })
+}
@@ -0,0 +1,191 @@
+use std::ffi::CStr;
+use std::io::{self, Write};
+use std::os::unix::process::CommandExt;
+use std::process::Command as StdCommand;
+use super::jobs::{self, JobState};
+use super::state::STATE;
+use super::trap;
+
+// Signals an interactive shell ignores itself but leaves to its foreground children.
+pub(crate) const INTERACTIVE_SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGQUIT, libc::SIGTSTP];
+
+// Signal names without their SIG prefix, as accepted by `trap`.
+const SIGNALS: &[(&str, libc::c_int)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    ("SYS", libc::SIGSYS),
+];
+
+// Parses a signal given as a number or a name, with or without SIG, in any case.
+pub(crate) fn signal_number(spec: &str) -> Option<libc::c_int> {
+    if let Ok(number) = spec.parse::<libc::c_int>() {
+        return SIGNALS.iter().any(|&(_, n)| n == number).then_some(number);
+    }
+    let upper = spec.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS.iter().find(|&&(n, _)| n == name).map(|&(_, number)| number)
+}
+
+// The full name of a signal, e.g. "SIGINT".
+pub(crate) fn signal_name(signal: libc::c_int) -> String {
+    match SIGNALS.iter().find(|&&(_, n)| n == signal) {
+        Some((name, _)) => format!("SIG{}", name),
+        None => signal.to_string(),
+    }
+}
+
+// Prints the known signals five to a line, the way `trap -l` does.
+pub(crate) fn list_signals(stdout_writer: &mut dyn Write) -> std::io::Result<()> {
+    let mut sorted = SIGNALS.to_vec();
+    sorted.sort_by_key(|&(_, number)| number);
+    for (i, (name, number)) in sorted.iter().enumerate() {
+        let end = if i % 5 == 4 || i + 1 == sorted.len() { "\n" } else { "\t" };
+        write!(stdout_writer, "{:2}) SIG{}{}", number, name, end)?;
+    }
+    Ok(())
+}
+
+// How a waited-for child ended up.
+pub(crate) enum WaitStatus {
+    Exited(i32),
+    Signaled { signal: i32, core_dumped: bool },
+    Stopped(i32),
+    Continued,
+}
+
+impl WaitStatus {
+    // The value of `$?` for this status.
+    pub fn code(&self) -> i32 {
+        match *self {
+            WaitStatus::Exited(code) => code,
+            WaitStatus::Signaled { signal, .. } | WaitStatus::Stopped(signal) => 128 + signal,
+            WaitStatus::Continued => 0,
+        }
+    }
+}
+
+pub(crate) fn ignore_interactive_signals() {
+    STATE.write().unwrap().interactive = true;
+    for signal in INTERACTIVE_SIGNALS {
+        // SAFETY: installing SIG_IGN has no memory-safety requirements.
+        unsafe { libc::signal(signal, libc::SIG_IGN) };
+    }
+}
+
+// Ignored signals stay ignored across exec, so children get the defaults back before running.
+// Signals the user ignored with `trap '' SIG` stay ignored, as POSIX requires.
+pub(crate) fn restore_default_signals(command: &mut StdCommand) {
+    let signals: Vec<libc::c_int> = INTERACTIVE_SIGNALS
+        .into_iter()
+        .filter(|&signal| !trap::is_ignored(signal))
+        .collect();
+    // SAFETY: the closure only calls async-signal-safe functions.
+    unsafe {
+        command.pre_exec(move || {
+            for &signal in &signals {
+                libc::signal(signal, libc::SIG_DFL);
+            }
+            let mut empty: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut empty);
+            libc::sigprocmask(libc::SIG_SETMASK, &empty, std::ptr::null_mut());
+            Ok(())
+        });
+    }
+}
+
+// Description of a signal as printed by strsignal(3), e.g. "Killed".
+pub(crate) fn describe(signal: i32) -> String {
+    // SAFETY: strsignal returns a pointer to a valid, NUL-terminated string.
+    unsafe {
+        let text = libc::strsignal(signal);
+        if text.is_null() {
+            return format!("Signal {}", signal);
+        }
+        CStr::from_ptr(text).to_string_lossy().into_owned()
+    }
+}
+
+// Waits for `pid` to change state. Returns None if `WNOHANG` was given and nothing happened.
+pub(crate) fn wait_pid(pid: u32, options: libc::c_int) -> io::Result<Option<WaitStatus>> {
+    let mut status = 0;
+    loop {
+        // SAFETY: `status` is a valid pointer for the duration of the call.
+        let ret = unsafe { libc::waitpid(pid as libc::pid_t, &mut status, options) };
+        if ret == 0 {
+            return Ok(None);
+        }
+        if ret > 0 {
+            break;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    Ok(Some(if libc::WIFSTOPPED(status) {
+        WaitStatus::Stopped(libc::WSTOPSIG(status))
+    } else if libc::WIFCONTINUED(status) {
+        WaitStatus::Continued
+    } else if libc::WIFSIGNALED(status) {
+        WaitStatus::Signaled {
+            signal: libc::WTERMSIG(status),
+            core_dumped: libc::WCOREDUMP(status),
+        }
+    } else {
+        WaitStatus::Exited(libc::WEXITSTATUS(status))
+    }))
+}
+
+// Waits for a foreground child and reports abnormal terminations the way bash does.
+// A stopped child is moved to the job table.
+pub(crate) fn wait_foreground(pid: u32, command: &str) -> i32 {
+    let status = match wait_pid(pid, libc::WUNTRACED) {
+        Ok(Some(status)) => status,
+        Ok(None) => return 0,
+        Err(err) => {
+            eprintln!("wait: {}", err);
+            return 1;
+        }
+    };
+    match status {
+        WaitStatus::Exited(_) | WaitStatus::Continued => {}
+        // The terminal already echoed ^C; just move to a fresh line.
+        WaitStatus::Signaled { signal: libc::SIGINT, .. } => eprintln!(),
+        WaitStatus::Signaled { signal: libc::SIGPIPE, .. } => {}
+        WaitStatus::Signaled { signal, core_dumped } => {
+            let core = if core_dumped { " (core dumped)" } else { "" };
+            eprintln!("{}{}", describe(signal), core);
+        }
+        WaitStatus::Stopped(_) => {
+            let id = jobs::add_job(pid, command, JobState::Stopped);
+            eprintln!("\n[{}]+  Stopped                 {}", id, command);
+        }
+    }
+    status.code()
+}
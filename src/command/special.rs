@@ -0,0 +1,145 @@
+// Parameters the shell maintains itself: `$$`, `$!`, `$0`, `$PPID`, `$RANDOM`,
+// `$SECONDS`, `$LINENO`, `BASH_COMMAND`, `PIPESTATUS`, `FUNCNAME` and `BASH_SOURCE`.
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use super::state::STATE;
+use super::variables::{self, Attribute};
+
+// Tells a command substitution the pids of the shell it runs for, since `$$`
+// and `$PPID` are those of the top-level shell even in subshells.
+const SUBSHELL_VAR: &str = "__SHELL_PIDS";
+
+// Largest value of `$RANDOM`.
+const RANDOM_MAX: u32 = 0x7fff;
+
+/// Records the values fixed when the shell starts.
+pub(crate) fn init() {
+    let inherited = std::env::var(SUBSHELL_VAR).ok();
+    std::env::remove_var(SUBSHELL_VAR);
+    let pids = inherited.as_deref().and_then(|pids| {
+        let (pid, ppid) = pids.split_once(' ')?;
+        Some((pid.parse().ok()?, ppid.parse().ok()?))
+    });
+    // SAFETY: getpid and getppid cannot fail.
+    let (pid, ppid) = pids.unwrap_or_else(|| unsafe { (libc::getpid() as u32, libc::getppid() as u32) });
+    let mut shell = STATE.write().unwrap();
+    shell.shell_pid = pid;
+    shell.parent_pid = ppid;
+    shell.shell_name = std::env::args().next().unwrap_or_default();
+    shell.seconds = Some((Instant::now(), 0));
+    drop(shell);
+    seed_random();
+    variables::set_attribute("PPID", Attribute::Integer, true);
+    variables::set_attribute("PPID", Attribute::Readonly, true);
+    // There are no functions or sourced files, so the call stack is always
+    // empty, as at the top level of bash.
+    let _ = variables::declare_array("FUNCNAME", false);
+    let _ = variables::set_array("BASH_SOURCE", variables::indexed(Vec::new()));
+}
+
+/// The environment entry that passes `$$` and `$PPID` on to a subshell.
+pub(crate) fn subshell_env() -> (&'static str, String) {
+    let shell = STATE.read().unwrap();
+    (SUBSHELL_VAR, format!("{} {}", shell.shell_pid, shell.parent_pid))
+}
+
+/// Records the command about to run, for `BASH_COMMAND`.
+pub(crate) fn set_current_command(command: &str) {
+    STATE.write().unwrap().current_command = command.trim().to_string();
+}
+
+pub(crate) fn line_number() -> usize {
+    STATE.read().unwrap().line_number
+}
+
+pub(crate) fn set_line_number(line: usize) {
+    STATE.write().unwrap().line_number = line;
+}
+
+/// Sets `PIPESTATUS`. Commands are not joined in pipelines, so it has one element.
+pub(crate) fn set_pipe_status(status: i32) {
+    let _ = variables::set_array("PIPESTATUS", variables::indexed(vec![status.to_string()]));
+}
+
+// bash's generator, so that `RANDOM=seed` gives the same numbers: the
+// Park-Miller minimal standard generator, folded to 15 bits.
+fn next_random(seed: u32) -> u32 {
+    let last = if seed == 0 { 123459876 } else { seed as i64 };
+    let (high, low) = (last / 127773, last % 127773);
+    let mut next = 16807 * low - 2836 * high;
+    if next < 0 {
+        next += 0x7fffffff;
+    }
+    next as u32
+}
+
+fn seed_random() {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    // SAFETY: getpid cannot fail.
+    let pid = unsafe { libc::getpid() } as u32;
+    set_seed(now.as_secs() as u32 ^ now.subsec_micros() ^ pid);
+}
+
+fn set_seed(seed: u32) {
+    let mut shell = STATE.write().unwrap();
+    shell.random_seed = seed;
+    shell.last_random = None;
+}
+
+fn random() -> u32 {
+    let mut shell = STATE.write().unwrap();
+    // The same number never comes twice in a row.
+    loop {
+        shell.random_seed = next_random(shell.random_seed);
+        let value = ((shell.random_seed >> 16) ^ (shell.random_seed & 0xffff)) & RANDOM_MAX;
+        if shell.last_random != Some(value) {
+            shell.last_random = Some(value);
+            return value;
+        }
+    }
+}
+
+// The parameters `dynamic_value` computes.
+const DYNAMIC: &[&str] = &["RANDOM", "SECONDS", "LINENO", "PPID", "BASH_COMMAND"];
+
+/// Whether `name` is computed each time it is used, rather than stored.
+pub(crate) fn is_dynamic(name: &str) -> bool {
+    DYNAMIC.contains(&name)
+}
+
+/// The value of a parameter the shell computes when it is used.
+pub(crate) fn dynamic_value(name: &str) -> Option<String> {
+    match name {
+        "RANDOM" => Some(random().to_string()),
+        "SECONDS" => {
+            let (since, offset) = STATE.read().unwrap().seconds?;
+            Some((offset + since.elapsed().as_secs() as i64).to_string())
+        }
+        "LINENO" => Some(line_number().to_string()),
+        "PPID" => Some(STATE.read().unwrap().parent_pid.to_string()),
+        "BASH_COMMAND" => Some(STATE.read().unwrap().current_command.clone()),
+        _ => None,
+    }
+}
+
+/// Assigns a parameter with a meaning of its own: `RANDOM=n` seeds the
+/// generator and `SECONDS=n` restarts the count at n. Returns false for other names.
+pub(crate) fn assign(name: &str, value: &str) -> bool {
+    let number = value.trim().parse::<i64>().unwrap_or(0);
+    match name {
+        "RANDOM" => set_seed(number as u32),
+        "SECONDS" => STATE.write().unwrap().seconds = Some((Instant::now(), number)),
+        _ => return false,
+    }
+    true
+}
+
+/// `$$`, `$!` and `$0`.
+pub(crate) fn parameter(name: &str) -> Option<String> {
+    let shell = STATE.read().unwrap();
+    match name {
+        "$" => Some(shell.shell_pid.to_string()),
+        "!" => shell.last_background.map(|pid| pid.to_string()),
+        "0" => Some(shell.shell_name.clone()),
+        _ => None,
+    }
+}
@@ -0,0 +1,69 @@
+use super::coproc::Coproc;
+use super::jobs::Job;
+use super::procsub::Substitution;
+use super::set::ShellOption;
+use super::shopt::ShoptOption;
+use super::trap::Condition;
+use super::variables::{Array, Attribute};
+use std::collections::{BTreeMap, BTreeSet};
+use std::os::fd::RawFd;
+use std::path::PathBuf;
+use std::sync::{LazyLock, RwLock};
+use std::time::Instant;
+
+// State shared by the builtins for the lifetime of the shell.
+#[derive(Default)]
+pub(crate) struct ShellState {
+    // Directories saved by pushd, most recent first. The current directory
+    // is not stored here: it is always entry 0 of the stack as shown by `dirs`.
+    pub dir_stack: Vec<PathBuf>,
+    pub jobs: Vec<Job>,
+    // Commands set with `trap`; an empty command means the signal is ignored.
+    pub traps: BTreeMap<Condition, String>,
+    // Exit status of the last command, i.e. `$?`.
+    pub last_status: i32,
+    // Whether the shell reads commands from a terminal, which changes what
+    // `trap - SIG` restores.
+    pub interactive: bool,
+    // Whether the commands come from `-c` rather than standard input.
+    pub command_string: bool,
+    // Options enabled with `set`.
+    pub options: BTreeSet<ShellOption>,
+    // Options enabled with `shopt`.
+    pub shopt: BTreeSet<ShoptOption>,
+    // `$1`, `$2`, ... as set with `set --`.
+    pub positional: Vec<String>,
+    // Plain variables. Only those with the export attribute reach child processes.
+    pub scalars: BTreeMap<String, String>,
+    // Indexed and associative arrays, which are never exported.
+    pub arrays: BTreeMap<String, Array>,
+    // `$$` and `$PPID`, those of the top-level shell even in a subshell.
+    pub shell_pid: u32,
+    pub parent_pid: u32,
+    // `$0`, the name the shell was started with.
+    pub shell_name: String,
+    // `$!`, the process most recently started in the background.
+    pub last_background: Option<u32>,
+    // Statuses of background processes reaped before `wait` asked for them.
+    pub finished: Vec<(u32, i32)>,
+    // Coprocesses started with `coproc` and not yet reaped.
+    pub coprocs: Vec<Coproc>,
+    // Process substitutions whose pipes are open or whose processes are yet to be reaped.
+    pub process_substitutions: Vec<Substitution>,
+    // Descriptors above 2 that `exec` opened for the shell. They are close-on-exec,
+    // and only the commands the user runs inherit them.
+    pub exec_fds: BTreeSet<RawFd>,
+    // `$LINENO`, the input line of the command being run.
+    pub line_number: usize,
+    // `BASH_COMMAND`, the command being run.
+    pub current_command: String,
+    // When `$SECONDS` was the given number: set at startup and by assigning SECONDS.
+    pub seconds: Option<(Instant, i64)>,
+    // State of the `$RANDOM` generator, and its last value, which is never repeated.
+    pub random_seed: u32,
+    pub last_random: Option<u32>,
+    // Attributes given with `declare`, which may outlive a variable's value.
+    pub attributes: BTreeMap<String, BTreeSet<Attribute>>,
+}
+
+pub(crate) static STATE: LazyLock<RwLock<ShellState>> = LazyLock::new(|| RwLock::new(ShellState::default()));
@@ -0,0 +1,529 @@
+// Conditional expressions: the `test` and `[` builtins and the `[[ ... ]]` command.
+use std::ffi::CString;
+use std::fs::{self, Metadata};
+use std::io::Write;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::ops::Range;
+use std::time::SystemTime;
+use super::parser::{self, Token};
+use super::pattern::Pattern;
+use super::set;
+use super::shopt::{self, ShoptOption};
+use super::{utils, variables, Command, CommandError, Redirection};
+
+const UNARY: &[&str] = &[
+    "-a", "-b", "-c", "-d", "-e", "-f", "-g", "-h", "-k", "-n", "-o", "-p", "-r", "-s", "-t", "-u", "-v", "-w", "-x", "-z",
+    "-G", "-L", "-N", "-O", "-S",
+];
+
+const BINARY: &[&str] = &[
+    "=", "==", "!=", "<", ">", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef",
+];
+
+// Why an expression could not be evaluated; reported with exit status 2.
+type EvalError = String;
+
+fn metadata(path: &str, follow: bool) -> Option<Metadata> {
+    if follow { fs::metadata(path).ok() } else { fs::symlink_metadata(path).ok() }
+}
+
+fn accessible(path: &str, mode: libc::c_int) -> bool {
+    let Ok(path) = CString::new(path) else {
+        return false;
+    };
+    // SAFETY: `path` is a valid NUL-terminated string.
+    unsafe { libc::access(path.as_ptr(), mode) == 0 }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    metadata(path, true)?.modified().ok()
+}
+
+fn unary(op: &str, operand: &str) -> bool {
+    let meta = || metadata(operand, true);
+    match op {
+        "-a" | "-e" => meta().is_some(),
+        "-b" => meta().is_some_and(|m| m.file_type().is_block_device()),
+        "-c" => meta().is_some_and(|m| m.file_type().is_char_device()),
+        "-d" => meta().is_some_and(|m| m.is_dir()),
+        "-f" => meta().is_some_and(|m| m.is_file()),
+        "-g" => meta().is_some_and(|m| m.permissions().mode() & 0o2000 != 0),
+        "-h" | "-L" => metadata(operand, false).is_some_and(|m| m.file_type().is_symlink()),
+        "-k" => meta().is_some_and(|m| m.permissions().mode() & 0o1000 != 0),
+        "-p" => meta().is_some_and(|m| m.file_type().is_fifo()),
+        "-r" => accessible(operand, libc::R_OK),
+        "-s" => meta().is_some_and(|m| m.len() > 0),
+        "-S" => meta().is_some_and(|m| m.file_type().is_socket()),
+        // SAFETY: isatty accepts any descriptor number.
+        "-t" => operand.trim().parse::<libc::c_int>().is_ok_and(|fd| unsafe { libc::isatty(fd) } == 1),
+        "-u" => meta().is_some_and(|m| m.permissions().mode() & 0o4000 != 0),
+        "-w" => accessible(operand, libc::W_OK),
+        "-x" => accessible(operand, libc::X_OK),
+        // SAFETY: geteuid and getegid cannot fail.
+        "-O" => meta().is_some_and(|m| m.uid() == unsafe { libc::geteuid() }),
+        "-G" => meta().is_some_and(|m| m.gid() == unsafe { libc::getegid() }),
+        "-N" => meta().is_some_and(|m| m.mtime() > m.atime()),
+        "-n" => !operand.is_empty(),
+        "-z" => operand.is_empty(),
+        "-o" => set::is_option_set(operand),
+        "-v" => super::expand::lookup(operand).ok().flatten().is_some(),
+        _ => false,
+    }
+}
+
+fn integer(operand: &str) -> Result<i64, EvalError> {
+    operand.trim().parse().map_err(|_| format!("{}: integer expression expected", operand))
+}
+
+fn binary(left: &str, op: &str, right: &str) -> Result<bool, EvalError> {
+    Ok(match op {
+        "=" | "==" => left == right,
+        "!=" => left != right,
+        "<" => left < right,
+        ">" => left > right,
+        "-eq" => integer(left)? == integer(right)?,
+        "-ne" => integer(left)? != integer(right)?,
+        "-lt" => integer(left)? < integer(right)?,
+        "-le" => integer(left)? <= integer(right)?,
+        "-gt" => integer(left)? > integer(right)?,
+        "-ge" => integer(left)? >= integer(right)?,
+        "-nt" => match (modified(left), modified(right)) {
+            (Some(l), Some(r)) => l > r,
+            (l, r) => l.is_some() && r.is_none(),
+        },
+        "-ot" => match (modified(left), modified(right)) {
+            (Some(l), Some(r)) => l < r,
+            (l, r) => l.is_none() && r.is_some(),
+        },
+        "-ef" => match (metadata(left, true), metadata(right, true)) {
+            (Some(l), Some(r)) => l.dev() == r.dev() && l.ino() == r.ino(),
+            _ => false,
+        },
+        _ => return Err(format!("{}: binary operator expected", op)),
+    })
+}
+
+// Recursive descent over the arguments of `test`, for the forms with more than four of them:
+//   or := and (-o and)* ; and := not (-a not)* ; not := ! not | ( or ) | primary
+struct TestParser<'a> {
+    args: &'a [String],
+    pos: usize,
+}
+
+impl TestParser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.args.get(self.pos).map(String::as_str)
+    }
+
+    fn or(&mut self) -> Result<bool, EvalError> {
+        let mut value = self.and()?;
+        while self.peek() == Some("-o") {
+            self.pos += 1;
+            value = self.and()? || value;
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<bool, EvalError> {
+        let mut value = self.not()?;
+        while self.peek() == Some("-a") {
+            self.pos += 1;
+            value = self.not()? && value;
+        }
+        Ok(value)
+    }
+
+    fn not(&mut self) -> Result<bool, EvalError> {
+        match self.peek() {
+            Some("!") => {
+                self.pos += 1;
+                Ok(!self.not()?)
+            }
+            Some("(") => {
+                self.pos += 1;
+                let value = self.or()?;
+                if self.peek() != Some(")") {
+                    return Err("`)' expected".to_string());
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<bool, EvalError> {
+        let args = self.args;
+        let Some(first) = args.get(self.pos).map(String::as_str) else {
+            return Err("argument expected".to_string());
+        };
+        let rest = &args[self.pos + 1..];
+        if rest.len() >= 2 && BINARY.contains(&rest[0].as_str()) {
+            self.pos += 3;
+            return binary(first, &rest[0], &rest[1]);
+        }
+        if UNARY.contains(&first) && !rest.is_empty() {
+            self.pos += 2;
+            return Ok(unary(first, &rest[0]));
+        }
+        self.pos += 1;
+        Ok(!first.is_empty())
+    }
+}
+
+// Evaluates the arguments of `test` with the POSIX rules for up to four arguments.
+fn evaluate(args: &[String]) -> Result<bool, EvalError> {
+    let arg = |i: usize| args[i].as_str();
+    match args.len() {
+        0 => Ok(false),
+        1 => Ok(!arg(0).is_empty()),
+        2 if arg(0) == "!" => Ok(arg(1).is_empty()),
+        2 if UNARY.contains(&arg(0)) => Ok(unary(arg(0), arg(1))),
+        2 => Err(format!("{}: unary operator expected", arg(0))),
+        3 if BINARY.contains(&arg(1)) => binary(arg(0), arg(1), arg(2)),
+        3 if arg(1) == "-a" => Ok(!arg(0).is_empty() && !arg(2).is_empty()),
+        3 if arg(1) == "-o" => Ok(!arg(0).is_empty() || !arg(2).is_empty()),
+        3 if arg(0) == "!" => evaluate(&args[1..]).map(|value| !value),
+        3 if arg(0) == "(" && arg(2) == ")" => Ok(!arg(1).is_empty()),
+        3 => Err(format!("{}: binary operator expected", arg(1))),
+        4 if arg(0) == "!" => evaluate(&args[1..]).map(|value| !value),
+        4 if arg(0) == "(" && arg(3) == ")" => evaluate(&args[1..3]),
+        _ => {
+            let mut parser = TestParser { args, pos: 0 };
+            let value = parser.or()?;
+            match parser.peek() {
+                None => Ok(value),
+                Some(extra) => Err(format!("{}: too many arguments", extra)),
+            }
+        }
+    }
+}
+
+pub(crate) fn test_cmd(bracket: bool, args: &[String], stderr_writer: &mut dyn Write) -> i32 {
+    let name = if bracket { "[" } else { "test" };
+    let args = if bracket {
+        match args.split_last() {
+            Some((last, args)) if last == "]" => args,
+            _ => {
+                let _ = writeln!(stderr_writer, "[: missing `]'");
+                return 2;
+            }
+        }
+    } else {
+        args
+    };
+    match evaluate(args) {
+        Ok(value) => i32::from(!value),
+        Err(message) => {
+            let _ = writeln!(stderr_writer, "{}: {}", name, message);
+            2
+        }
+    }
+}
+
+pub(crate) fn parse_test_cmd(name: &str, args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Test { bracket: name == "[", args, redirections })
+}
+
+// ---- [[ ... ]] ----
+
+// Whether `input` starts with the `[[` keyword.
+pub(crate) fn is_conditional(input: &str) -> bool {
+    let input = input.trim_start();
+    input.strip_prefix("[[").is_some_and(|rest| rest.starts_with(char::is_whitespace))
+}
+
+// The words and operators inside `[[ ... ]]`, keeping quotes. After `=~`, the
+// regex runs to the next blank outside parentheses, operators and all.
+fn conditional_tokens(input: &str, tokens: &[(Token, Range<usize>)]) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut i = 0;
+    while let Some((token, span)) = tokens.get(i) {
+        i += 1;
+        if words.last().is_some_and(|word| word == "=~") {
+            let mut end = span.end;
+            let mut depth = 0usize;
+            let mut next = Some(token);
+            while let Some(token) = next {
+                match token {
+                    Token::Operator("(") => depth += 1,
+                    Token::Operator(")") => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                next = match tokens.get(i) {
+                    Some((token, span)) if *token != Token::Newline && (span.start == end || depth > 0) => {
+                        end = span.end;
+                        i += 1;
+                        Some(token)
+                    }
+                    _ => None,
+                };
+            }
+            words.push(input[span.start..end].to_string());
+            continue;
+        }
+        match token {
+            Token::Word(word) => words.push(word.clone()),
+            Token::Operator(op) => words.push(op.to_string()),
+            Token::Newline => {}
+        }
+    }
+    words
+}
+
+// The number of groups in an extended regular expression: its unescaped `(`
+// outside bracket expressions. libc keeps regex_t's own count private.
+fn group_count(regex: &str) -> usize {
+    let chars: Vec<char> = regex.chars().collect();
+    let mut count = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '[' => {
+                // `]` right after `[` or `[^` is part of the set.
+                i += 1;
+                if chars.get(i) == Some(&'^') {
+                    i += 1;
+                }
+                if chars.get(i) == Some(&']') {
+                    i += 1;
+                }
+                while i < chars.len() && chars[i] != ']' {
+                    i += 1;
+                }
+            }
+            '(' => count += 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    count
+}
+
+// Matches `text` against an extended regular expression with regcomp(3).
+// Returns the whole match and the groups, or None if it does not match.
+fn regex_match(regex: &str, text: &str, ignore_case: bool) -> Result<Option<Vec<String>>, EvalError> {
+    let (Ok(c_regex), Ok(c_text)) = (CString::new(regex), CString::new(text)) else {
+        return Ok(None);
+    };
+    let flags = libc::REG_EXTENDED | if ignore_case { libc::REG_ICASE } else { 0 };
+    // SAFETY: `compiled` is only used after regcomp succeeded and freed exactly once;
+    // `groups` has room for the number of groups passed to regexec.
+    unsafe {
+        let mut compiled: libc::regex_t = std::mem::zeroed();
+        if libc::regcomp(&mut compiled, c_regex.as_ptr(), flags) != 0 {
+            return Err(format!("{}: invalid regular expression", regex));
+        }
+        let mut groups = vec![libc::regmatch_t { rm_so: -1, rm_eo: -1 }; group_count(regex) + 1];
+        let status = libc::regexec(&compiled, c_text.as_ptr(), groups.len(), groups.as_mut_ptr(), 0);
+        libc::regfree(&mut compiled);
+        if status != 0 {
+            return Ok(None);
+        }
+        let bytes = text.as_bytes();
+        Ok(Some(
+            groups
+                .iter()
+                .map(|g| match (usize::try_from(g.rm_so), usize::try_from(g.rm_eo)) {
+                    (Ok(start), Ok(end)) => String::from_utf8_lossy(&bytes[start..end]).into_owned(),
+                    _ => String::new(),
+                })
+                .collect(),
+        ))
+    }
+}
+
+fn expanded(raw: &str) -> Result<String, EvalError> {
+    utils::expand_word(raw).map_err(|e| e.to_string())
+}
+
+
+// A parsed `[[ ... ]]` expression, holding its operands unexpanded.
+#[derive(Debug)]
+enum Expr {
+    Word(String),
+    Unary(String, String),
+    Binary(String, String, String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+// Recursive descent over the tokens of `[[ ... ]]`:
+//   or := and (|| and)* ; and := not (&& not)* ; not := ! not | ( or ) | primary
+struct ConditionalParser<'a> {
+    tokens: &'a [String],
+    pos: usize,
+}
+
+impl ConditionalParser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, EvalError> {
+        let mut expr = self.and()?;
+        while self.peek() == Some("||") {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, EvalError> {
+        let mut expr = self.not()?;
+        while self.peek() == Some("&&") {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, EvalError> {
+        match self.peek() {
+            Some("!") => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.not()?)))
+            }
+            Some("(") => {
+                self.pos += 1;
+                let expr = self.or()?;
+                if self.next().as_deref() != Some(")") {
+                    return Err("syntax error in conditional expression".to_string());
+                }
+                Ok(expr)
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, EvalError> {
+        let first = match self.next() {
+            Some(token) if !matches!(token.as_str(), "&&" | "||" | ")") => token,
+            Some(token) => return Err(format!("syntax error near `{}'", token)),
+            None => return Err("unexpected argument to conditional expression".to_string()),
+        };
+        match self.peek() {
+            Some(op) if BINARY.contains(&op) || op == "=~" => {
+                let op = op.to_string();
+                self.pos += 1;
+                match self.next() {
+                    Some(right) => Ok(Expr::Binary(first, op, right)),
+                    None => Err(format!("unexpected argument `{}' to conditional binary operator", op)),
+                }
+            }
+            Some(operand) if UNARY.contains(&first.as_str()) && !matches!(operand, "&&" | "||" | ")") => {
+                let operand = operand.to_string();
+                self.pos += 1;
+                Ok(Expr::Unary(first, operand))
+            }
+            _ => Ok(Expr::Word(first)),
+        }
+    }
+}
+
+fn expand_err(e: CommandError) -> EvalError {
+    e.to_string()
+}
+
+// Evaluates an expression; `&&` and `||` only expand their right side when needed.
+fn eval(expr: &Expr) -> Result<bool, EvalError> {
+    match expr {
+        Expr::Word(word) => Ok(!expanded(word)?.is_empty()),
+        Expr::Unary(op, operand) => Ok(unary(op, &expanded(operand)?)),
+        Expr::Not(expr) => Ok(!eval(expr)?),
+        Expr::And(left, right) => Ok(eval(left)? && eval(right)?),
+        Expr::Or(left, right) => Ok(eval(left)? || eval(right)?),
+        Expr::Binary(left, op, right) => {
+            let left = expanded(left)?;
+            let ignore_case = shopt::enabled(ShoptOption::Nocasematch);
+            match op.as_str() {
+                "==" | "=" | "!=" => {
+                    let pattern = utils::expand_pattern(right).map_err(expand_err)?;
+                    let extglob = shopt::enabled(ShoptOption::Extglob);
+                    let matched = Pattern::new(&pattern, extglob, ignore_case).matches(&left);
+                    Ok(matched == (op != "!="))
+                }
+                "=~" => {
+                    let regex = utils::expand_regex(right).map_err(expand_err)?;
+                    let groups = regex_match(&regex, &left, ignore_case)?;
+                    let matched = groups.is_some();
+                    let _ = variables::set_array("BASH_REMATCH", variables::indexed(groups.unwrap_or_default()));
+                    Ok(matched)
+                }
+                op => binary(&left, op, &expanded(right)?),
+            }
+        }
+    }
+}
+
+pub(crate) fn parse_conditional(input: &str) -> Result<Command, CommandError> {
+    let input = input.trim();
+    let tokens = parser::tokenize(input).unwrap_or_default();
+    match tokens.as_slice() {
+        [(Token::Word(open), _), inner @ .., (Token::Word(close), _)] if open == "[[" && close == "]]" => {
+            Ok(Command::Conditional { tokens: conditional_tokens(input, inner) })
+        }
+        _ => Err(CommandError::Syntax("newline".to_string())),
+    }
+}
+
+pub(crate) fn conditional_cmd(tokens: &[String]) -> i32 {
+    let mut parser = ConditionalParser { tokens, pos: 0 };
+    let result = parser.or().and_then(|expr| match parser.peek() {
+        None => eval(&expr),
+        Some(token) => Err(format!("syntax error near `{}'", token)),
+    });
+    match result {
+        Ok(value) => i32::from(!value),
+        Err(message) => {
+            eprintln!("{}", message);
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(input: &str) -> i32 {
+        Command::from(input).unwrap().execute()
+    }
+
+    #[test]
+    fn escaped_angle_brackets_compare_strings() {
+        assert_eq!(status(r"[ abc \< abd ]"), 0);
+        assert_eq!(status(r"[ abd \< abc ]"), 1);
+        assert_eq!(status(r"test abd \> abc"), 0);
+        assert_eq!(status(r"test abc '>' abd"), 1);
+        variables::set("test_test_low", "apple").unwrap();
+        variables::set("test_test_high", "banana").unwrap();
+        assert_eq!(status(r#"test "$test_test_low" \< "$test_test_high""#), 0);
+        assert_eq!(status(r#"[ "$test_test_low" \> "$test_test_high" ]"#), 1);
+    }
+
+    #[test]
+    fn evaluation() {
+        let args = |words: &[&str]| words.iter().map(|word| word.to_string()).collect::<Vec<_>>();
+        assert_eq!(evaluate(&args(&[])), Ok(false));
+        assert_eq!(evaluate(&args(&[""])), Ok(false));
+        assert_eq!(evaluate(&args(&["x"])), Ok(true));
+        assert_eq!(evaluate(&args(&["!", "x"])), Ok(false));
+        assert_eq!(evaluate(&args(&["-n", ""])), Ok(false));
+        assert_eq!(evaluate(&args(&["-z", ""])), Ok(true));
+        assert_eq!(evaluate(&args(&["2", "-lt", "10"])), Ok(true));
+        assert_eq!(evaluate(&args(&["b", "<", "a"])), Ok(false));
+        assert_eq!(evaluate(&args(&["a", "=", "a", "-a", "b", "!=", "b"])), Ok(false));
+        assert_eq!(evaluate(&args(&["a", "=", "a", "-o", "b", "!=", "b"])), Ok(true));
+        assert_eq!(evaluate(&args(&["(", "x", ")"])), Ok(true));
+        assert!(evaluate(&args(&["x", "-lt", "1"])).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+// The `trap` builtin. Signal handlers only record that a signal arrived; the
+// trap commands run later, between commands, where it is safe to do anything.
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use super::signals::{self, INTERACTIVE_SIGNALS};
+use super::state::STATE;
+use super::{utils, Command, CommandError, Redirection};
+
+// What a trap is attached to. The order is the one `trap -p` lists them in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Condition {
+    Exit,
+    Signal(libc::c_int),
+    Debug,
+    Err,
+    Return,
+}
+
+impl Condition {
+    fn parse(spec: &str) -> Option<Condition> {
+        match spec.to_ascii_uppercase().as_str() {
+            "0" | "EXIT" | "SIGEXIT" => Some(Condition::Exit),
+            "DEBUG" => Some(Condition::Debug),
+            "ERR" => Some(Condition::Err),
+            "RETURN" => Some(Condition::Return),
+            _ => signals::signal_number(spec).map(Condition::Signal),
+        }
+    }
+
+    fn name(&self) -> String {
+        match *self {
+            Condition::Exit => "EXIT".to_string(),
+            Condition::Signal(signal) => signals::signal_name(signal),
+            Condition::Debug => "DEBUG".to_string(),
+            Condition::Err => "ERR".to_string(),
+            Condition::Return => "RETURN".to_string(),
+        }
+    }
+}
+
+// Signals that arrived and whose trap has not run yet, indexed by number.
+static PENDING: [AtomicBool; 65] = [const { AtomicBool::new(false) }; 65];
+
+// Set while a trap command runs, so that it does not trigger DEBUG or ERR itself.
+static IN_TRAP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn note_signal(signal: libc::c_int) {
+    if let Some(flag) = PENDING.get(signal as usize) {
+        flag.store(true, Ordering::SeqCst);
+    }
+}
+
+fn set_disposition(signal: libc::c_int, handler: libc::sighandler_t) {
+    // SAFETY: `action` is fully initialised and the handler only touches atomics.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(signal, &action, std::ptr::null_mut());
+    }
+}
+
+fn set_trap(condition: Condition, command: Option<&str>) {
+    if let Condition::Signal(signal) = condition {
+        let handler = match command {
+            Some("") => libc::SIG_IGN,
+            Some(_) => note_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            None if STATE.read().unwrap().interactive && INTERACTIVE_SIGNALS.contains(&signal) => libc::SIG_IGN,
+            None => libc::SIG_DFL,
+        };
+        set_disposition(signal, handler);
+    }
+    let mut shell = STATE.write().unwrap();
+    match command {
+        Some(command) => shell.traps.insert(condition, command.to_string()),
+        None => shell.traps.remove(&condition),
+    };
+}
+
+// Whether the user asked for `signal` to be ignored with `trap '' SIG`.
+pub(crate) fn is_ignored(signal: libc::c_int) -> bool {
+    STATE.read().unwrap().traps.get(&Condition::Signal(signal)).is_some_and(String::is_empty)
+}
+
+// Runs a trap command. `$?` is left as it was before the trap.
+fn run_trap(command: &str) {
+    let status = STATE.read().unwrap().last_status;
+    let nested = IN_TRAP.swap(true, Ordering::SeqCst);
+    Command::run(command);
+    IN_TRAP.store(nested, Ordering::SeqCst);
+    STATE.write().unwrap().last_status = status;
+}
+
+fn run_condition(condition: Condition) {
+    if IN_TRAP.load(Ordering::SeqCst) {
+        return;
+    }
+    let command = STATE.read().unwrap().traps.get(&condition).cloned();
+    if let Some(command) = command.filter(|c| !c.is_empty()) {
+        run_trap(&command);
+    }
+}
+
+/// Whether a trap command is running, which then keeps `BASH_COMMAND` as it was.
+pub(crate) fn in_trap() -> bool {
+    IN_TRAP.load(Ordering::SeqCst)
+}
+
+/// Runs the traps of the signals received since the last call.
+pub(crate) fn run_pending() {
+    for (signal, flag) in PENDING.iter().enumerate() {
+        if flag.swap(false, Ordering::SeqCst) {
+            run_condition(Condition::Signal(signal as libc::c_int));
+        }
+    }
+}
+
+/// Runs the DEBUG trap. Called before each simple command.
+pub(crate) fn run_debug_trap() {
+    run_condition(Condition::Debug);
+}
+
+/// Runs the ERR trap. Called when a command fails outside of a `&&`/`||` list.
+pub(crate) fn run_err_trap() {
+    run_condition(Condition::Err);
+}
+
+/// Runs the EXIT trap once, when the shell is about to exit with `status`.
+pub(crate) fn run_exit_trap(status: i32) {
+    let command = STATE.write().unwrap().traps.remove(&Condition::Exit);
+    if let Some(command) = command {
+        STATE.write().unwrap().last_status = status;
+        run_trap(&command);
+    }
+}
+
+// Quotes a trap command so that `trap -p` output can be read back in.
+fn quote(command: &str) -> String {
+    format!("'{}'", command.replace('\'', "'\\''"))
+}
+
+fn print_traps(conditions: Option<&[Condition]>, stdout_writer: &mut dyn Write) -> io::Result<()> {
+    let shell = STATE.read().unwrap();
+    for (condition, command) in &shell.traps {
+        if conditions.map_or(true, |wanted| wanted.contains(condition)) {
+            writeln!(stdout_writer, "trap -- {} {}", quote(command), condition.name())?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn trap_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    trap(args, stdout_writer, stderr_writer).unwrap_or_else(|err| utils::write_error("trap", &err, stderr_writer))
+}
+
+fn trap(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> io::Result<i32> {
+    let mut print = false;
+    let mut operands = args.iter().map(String::as_str).peekable();
+    while let Some(&arg) = operands.peek() {
+        match arg {
+            "--" => {
+                operands.next();
+                break;
+            }
+            "-" => break,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                for flag in arg[1..].chars() {
+                    match flag {
+                        'p' => print = true,
+                        'l' => {
+                            signals::list_signals(stdout_writer)?;
+                            return Ok(0);
+                        }
+                        _ => {
+                            let _ = writeln!(stderr_writer, "trap: -{}: invalid option", flag);
+                            let _ = writeln!(stderr_writer, "trap: usage: trap [-lp] [[arg] signal_spec ...]");
+                            return Ok(2);
+                        }
+                    }
+                }
+            }
+            _ => break,
+        }
+        operands.next();
+    }
+    let operands: Vec<&str> = operands.collect();
+
+    if operands.is_empty() {
+        print_traps(None, stdout_writer)?;
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    let mut conditions = Vec::new();
+    // A lone signal, or `-` as the command, resets the signals to their defaults.
+    let (command, specs) = match operands.as_slice() {
+        _ if print => (None, &operands[..]),
+        [_] => (None, &operands[..]),
+        ["-", specs @ ..] => (None, specs),
+        [command, specs @ ..] => (Some(*command), specs),
+        [] => unreachable!(),
+    };
+    for spec in specs {
+        match Condition::parse(spec) {
+            Some(condition) => conditions.push(condition),
+            None => {
+                let _ = writeln!(stderr_writer, "trap: {}: invalid signal specification", spec);
+                status = 1;
+            }
+        }
+    }
+
+    if print {
+        print_traps(Some(&conditions), stdout_writer)?;
+    } else {
+        for condition in conditions {
+            set_trap(condition, command);
+        }
+    }
+    Ok(status)
+}
+
+pub(crate) fn parse_trap_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Trap { args, redirections })
+}
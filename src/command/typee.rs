@@ -1,35 +1,48 @@
-use super::{Command, CommandError, Redirection};
+use super::{external, utils, Command, CommandError, Redirection, BUILTINS};
 use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
 
-pub(crate) fn type_cmd(cmd: &str, stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) {
-    use Command::*;
-    if cmd.is_empty() {
-        return;
+// Reserved words of the constructs the shell runs. The others, such as `if`,
+// are rejected by the parser and so are looked up like any other name.
+pub(crate) const KEYWORDS: &[&str] = &["case", "esac", "in", "!", "[[", "]]", "coproc"];
+
+// Describes what the name `cmd` runs, looking it up as it is: it has already
+// been expanded and must not be parsed again.
+fn describe(cmd: &str) -> Option<String> {
+    if KEYWORDS.contains(&cmd) {
+        return Some(format!("{} is a shell keyword", cmd));
+    }
+    if BUILTINS.contains(&cmd) {
+        return Some(format!("{} is a shell builtin", cmd));
     }
+    let path = if cmd.contains('/') {
+        let executable = Path::new(cmd).metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+        executable.then(|| cmd.to_string())
+    } else {
+        external::find_in_path(cmd)
+    };
+    path.map(|path| format!("{} is {}", cmd, path))
+}
 
-    // We need to parse the command without executing it, so we create a new `from` without redirections.
-    let cmd_to_check = format!("{} {}", cmd, "");
-    match Command::from(&cmd_to_check) {
-        Ok(External { path, .. }) => {
-            writeln!(stdout_writer, "{} is {}", cmd, path).unwrap();
-        }
-        Ok(Noop) => {
-            // This case can be hit if the input to `type` is just a redirection, which is not a valid command.
-            writeln!(stderr_writer, "{}: not found", cmd).unwrap();
-        }
-        Ok(_) => {
-            writeln!(stdout_writer, "{} is a shell builtin", cmd).unwrap();
+pub(crate) fn type_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    let mut status = 0;
+    for cmd in args {
+        match describe(cmd) {
+            Some(description) => {
+                if let Err(err) = writeln!(stdout_writer, "{}", description) {
+                    return utils::write_error("type", &err, stderr_writer);
+                }
+            }
+            None => {
+                let _ = writeln!(stderr_writer, "{}: not found", cmd);
+                status = 1;
+            }
         }
-        Err(CommandError::NotFound(..)) => {
-            writeln!(stderr_writer, "{}: not found", cmd).unwrap();
-        }
-        Err(e) => {
-            writeln!(stderr_writer, "type: error: {}", e).unwrap();
-        }
-    };
+    }
+    status
 }
 
-pub(crate) fn parse_type_cmd(args: &str, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
-    let cmd = args.trim().to_owned();
-    Ok(Command::Type { cmd, redirections })
-}
\ No newline at end of file
+pub(crate) fn parse_type_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Type { args, redirections })
+}
@@ -0,0 +1,53 @@
+// The `unset` builtin: removes variables, arrays and array elements.
+use std::io::Write;
+use super::{variables, Command, CommandError, Redirection};
+
+pub(crate) fn unset_cmd(args: &[String], stderr_writer: &mut dyn Write) -> i32 {
+    let mut status = 0;
+    let mut variables_only = false;
+    let mut references = false;
+    let mut names = args;
+    while let Some(flags) = names.first().and_then(|arg| arg.strip_prefix('-')) {
+        if flags == "-" {
+            names = &names[1..];
+            break;
+        }
+        // There are no shell functions, so `-f` has nothing to remove.
+        if let Some(flag) = flags.chars().find(|c| !"vfn".contains(*c)) {
+            let _ = writeln!(stderr_writer, "unset: -{}: invalid option", flag);
+            let _ = writeln!(stderr_writer, "unset: usage: unset [-f] [-v] [-n] [name ...]");
+            return 2;
+        }
+        if flags.is_empty() {
+            break;
+        }
+        variables_only |= flags.contains('v');
+        references |= flags.contains('n');
+        names = &names[1..];
+    }
+
+    for name in names {
+        let result = match variables::split_subscript(name) {
+            Some((name, subscript)) => variables::unset_element(name, subscript),
+            // `-n` removes a `declare -n` reference rather than the variable it names.
+            None if variables::valid_name(name) && references => variables::unset_reference(name),
+            None if variables::valid_name(name) => variables::unset(name),
+            // Without `-v` the name may be a function's, which need not be an identifier.
+            None if !variables_only => continue,
+            None => {
+                let _ = writeln!(stderr_writer, "unset: `{}': not a valid identifier", name);
+                status = 1;
+                continue;
+            }
+        };
+        if let Err(err) = result {
+            let _ = writeln!(stderr_writer, "unset: {}", err);
+            status = 1;
+        }
+    }
+    status
+}
+
+pub(crate) fn parse_unset_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Unset { args, redirections })
+}
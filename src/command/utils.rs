@@ -1,102 +1,607 @@
-use std::fs::{File, OpenOptions};
+use std::ffi::{CStr, CString};
+use std::fs::{self, File, OpenOptions};
 use std::io::{self, Write};
-use super::{Redirection, RedirectionMode, RedirectionTarget};
+use std::os::fd::{FromRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::Command as StdCommand;
+use super::set::{self, ShellOption};
+use super::printf::{self, EscapeMode};
+use super::shopt::{self, ShoptOption};
+use super::parser::{self, Token};
+use super::{arithmetic, dirstack, expand, glob, pattern, procsub, variables, CommandError, Redirection, RedirectionMode, RedirectionTarget};
 
 pub fn open_file_for_redirection(redir: &Redirection) -> std::io::Result<File> {
     match redir.mode {
-        RedirectionMode::Overwrite => File::create(&redir.file),
+        // With noclobber, `>` must not truncate an existing regular file.
+        RedirectionMode::Overwrite if set::enabled(ShellOption::Noclobber) => {
+            if fs::metadata(&redir.file).is_ok_and(|m| m.is_file()) {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, "cannot overwrite existing file"));
+            }
+            File::create(&redir.file)
+        }
+        RedirectionMode::Overwrite | RedirectionMode::Clobber => File::create(&redir.file),
         RedirectionMode::Append => OpenOptions::new().create(true).append(true).open(&redir.file),
+        RedirectionMode::Read => File::open(&redir.file),
+        RedirectionMode::Duplicate => {
+            let fd = redir.file.parse::<RawFd>().map_err(|_| io::Error::from_raw_os_error(libc::EBADF))?;
+            // SAFETY: F_DUPFD_CLOEXEC only creates a new descriptor.
+            let copy = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 3) };
+            if copy < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: `copy` was just created and nothing else owns it.
+            Ok(unsafe { File::from_raw_fd(copy) })
+        }
+    }
+}
+
+/// Has `command` inherit `fds`, which the shell keeps close-on-exec so that
+/// no other child gets them.
+pub(crate) fn inherit_fds(command: &mut StdCommand, fds: Vec<RawFd>) {
+    if fds.is_empty() {
+        return;
+    }
+    // SAFETY: the closure only calls fcntl, which is async-signal-safe.
+    unsafe {
+        command.pre_exec(move || {
+            for &fd in &fds {
+                libc::fcntl(fd, libc::F_SETFD, 0);
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Takes the redirections out of the simple command `input`. Only the operators
+/// the lexer reads unquoted redirect, so `echo '>' f` prints `> f`, and a number
+/// right before an operator is the descriptor it redirects. Returns the rest of
+/// the command, for `split_args`, and the last redirection of each stream.
+pub(crate) fn split_redirections(input: &str) -> Result<(String, Vec<Redirection>), CommandError> {
+    let syntax = |token: &str| CommandError::Syntax(token.to_string());
+    let tokens = parser::tokenize(input).map_err(|_| syntax("newline"))?;
+    let mut words = Vec::new();
+    let mut streams: [Option<Redirection>; 3] = [None, None, None];
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some((token, span)) = tokens.next() {
+        let (number, op) = match (token, tokens.peek()) {
+            (Token::Word(word), Some((Token::Operator(op), next)))
+                if next.start == span.end && parser::is_redirection(op) && word.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                let op = *op;
+                tokens.next();
+                (Some(word), op)
+            }
+            (Token::Word(word), _) => {
+                words.push(word);
+                continue;
+            }
+            (Token::Operator(op), _) if parser::is_redirection(op) => (None, op),
+            (Token::Operator(op), _) => return Err(syntax(op)),
+            (Token::Newline, _) => return Err(syntax("newline")),
+        };
+        let file = match tokens.next() {
+            Some((Token::Word(target), _)) => expand_word(&target)?,
+            Some((Token::Operator(op), _)) => return Err(syntax(op)),
+            _ => return Err(syntax("newline")),
+        };
+        let mode = match op {
+            "<" => RedirectionMode::Read,
+            ">" | "&>" => RedirectionMode::Overwrite,
+            ">|" => RedirectionMode::Clobber,
+            ">>" | "&>>" => RedirectionMode::Append,
+            "<&" | ">&" => RedirectionMode::Duplicate,
+            _ => return Err(syntax(op)),
+        };
+        let number = number.unwrap_or_else(|| if op.starts_with('<') { "0" } else { "1" }.to_string());
+        let Some(target) = standard_stream(&number) else {
+            return Err(CommandError::NullParameter(number, "only the standard streams can be redirected here".to_string()));
+        };
+        let digits = !file.is_empty() && file.chars().all(|c| c.is_ascii_digit());
+        // `&>file`, and `>&file` with a file that is not a descriptor, send both stdout and stderr to file.
+        let both = op.starts_with('&') || (op == ">&" && target == RedirectionTarget::Stdout && !digits);
+        if both {
+            let mode = if mode == RedirectionMode::Duplicate { RedirectionMode::Overwrite } else { mode };
+            streams[1] = Some(Redirection { target: RedirectionTarget::Stdout, file, mode });
+            streams[2] = Some(Redirection { target: RedirectionTarget::Stderr, file: "1".to_string(), mode: RedirectionMode::Duplicate });
+            continue;
+        }
+        if mode == RedirectionMode::Duplicate && !digits {
+            return Err(CommandError::NullParameter(file, "ambiguous redirect".to_string()));
+        }
+        streams[target as usize] = Some(Redirection { target, file, mode });
+    }
+    Ok((words.join(" "), streams.into_iter().flatten().collect()))
+}
+
+// The stream a file descriptor number stands for.
+fn standard_stream(fd: &str) -> Option<RedirectionTarget> {
+    match fd {
+        "0" => Some(RedirectionTarget::Stdin),
+        "1" => Some(RedirectionTarget::Stdout),
+        "2" => Some(RedirectionTarget::Stderr),
+        _ => None,
+    }
+}
+
+/// Opens the files of `redirections` in order. A copy of a standard stream
+/// redirected before it, as in `>out 2>&1`, shares that stream's file. On
+/// failure, returns the redirection that could not be opened.
+pub fn open_redirections(redirections: &[Redirection]) -> Result<Vec<(RedirectionTarget, File)>, (&Redirection, io::Error)> {
+    let mut opened: Vec<(RedirectionTarget, File)> = Vec::new();
+    for redir in redirections {
+        let shared = match redir.mode {
+            RedirectionMode::Duplicate => standard_stream(&redir.file)
+                .and_then(|stream| opened.iter().find(|(target, _)| *target == stream))
+                .map(|(_, file)| file.try_clone()),
+            _ => None,
+        };
+        let file = shared.unwrap_or_else(|| open_file_for_redirection(redir)).map_err(|err| (redir, err))?;
+        opened.push((redir.target, file));
+    }
+    Ok(opened)
+}
+
+// Opens the file redirected to stdin, if any.
+pub fn get_input_file(redirections: &[Redirection]) -> Option<io::Result<File>> {
+    redirections.iter().find(|r| r.target == RedirectionTarget::Stdin).map(open_file_for_redirection)
+}
+
+// Opens the stdout and stderr writers of a builtin, both eagerly so that
+// the files are created even if unused. Reports a file that cannot be opened,
+// including a missing input file.
+pub fn get_writers(redirections: &[Redirection]) -> Option<(Box<dyn Write>, Box<dyn Write>)> {
+    let mut opened = match open_redirections(redirections) {
+        Ok(opened) => opened,
+        Err((redir, err)) => {
+            eprintln!("{}: {}", redir.file, describe_io_error(&err));
+            return None;
+        }
+    };
+    let mut writer = |stream, standard: Box<dyn Write>| match opened.iter().position(|(target, _)| *target == stream) {
+        Some(i) => Box::new(opened.remove(i).1) as Box<dyn Write>,
+        None => standard,
+    };
+    let stdout_writer = writer(RedirectionTarget::Stdout, Box::new(io::stdout()));
+    let stderr_writer = writer(RedirectionTarget::Stderr, Box::new(io::stderr()));
+    Some((stdout_writer, stderr_writer))
+}
+
+// The message of an I/O error without the "(os error N)" suffix.
+pub(crate) fn describe_io_error(err: &io::Error) -> String {
+    let message = err.to_string();
+    match message.find(" (os error") {
+        Some(end) => message[..end].to_string(),
+        None => message,
     }
 }
 
-// This function returns a "Writer" that is either a file or stdout/stderr.
-pub fn get_output_writer(
-    redirections: &[Redirection],
-    target: RedirectionTarget,
-) -> Box<dyn Write> {
-    if let Some(redir) = redirections.iter().find(|r| r.target == target) {
-        let file = open_file_for_redirection(redir)
-            .expect("Failed to open redirection file");
-        return Box::new(file);
+// Reports that a builtin could not write its output, as bash does with
+// `echo: write error: Broken pipe`, and returns the builtin's status.
+pub(crate) fn write_error(name: &str, err: &io::Error, stderr_writer: &mut dyn Write) -> i32 {
+    let _ = writeln!(stderr_writer, "{}: write error: {}", name, describe_io_error(err));
+    1
+}
+
+// Home directory of `user` from the password database.
+fn home_of(user: &str) -> Option<String> {
+    let name = CString::new(user).ok()?;
+    // SAFETY: getpwnam returns either null or a pointer to a valid static passwd entry.
+    unsafe {
+        let pw = libc::getpwnam(name.as_ptr());
+        if pw.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr((*pw).pw_dir).to_string_lossy().into_owned())
     }
+}
 
-    // If no redirection is found for the target, return the standard stream.
-    match target {
-        RedirectionTarget::Stdout => Box::new(io::stdout()),
-        RedirectionTarget::Stderr => Box::new(io::stderr()),
+// Expands the `~prefix` at the start of a word: `~`, `~user`, `~+`, `~-`
+// and the directory stack entries `~N`, `~+N`, `~-N`.
+pub(crate) fn expand_tilde(prefix: &str) -> Option<String> {
+    match prefix {
+        "" => variables::get("HOME"),
+        "+" => variables::get("PWD"),
+        "-" => variables::get("OLDPWD"),
+        _ if prefix.trim_start_matches(['+', '-']).parse::<usize>().is_ok() => {
+            dirstack::entry(prefix).map(|dir| dir.to_string_lossy().into_owned())
+        }
+        user => home_of(user),
     }
 }
 
-pub(crate) fn preprocess_args(args: &str) -> String {
-    let tokens = split_args(args);
-    if tokens.is_empty() {
-        String::new()
+// Replaces a leading $HOME with `~`.
+pub(crate) fn abbreviate_home(path: &str) -> String {
+    match variables::get("HOME") {
+        Some(home) if !home.is_empty() && (path == home || path.starts_with(&format!("{}/", home))) => {
+            format!("~{}", &path[home.len()..])
+        }
+        _ => path.to_string(),
+    }
+}
+
+// Expands the `$` or backquote at `chars[i]`, if it starts an expansion.
+// Returns the values, more or fewer than one only for the likes of `$@`,
+// and the index just past the expansion.
+fn expansion_at(chars: &[char], i: usize, quoted: bool) -> Result<Option<(Vec<String>, usize)>, CommandError> {
+    if chars[i] == '`' {
+        return Ok(parser::backquoted(chars, i).map(|(cmd, end)| (vec![expand::command_substitution(&cmd)], end)));
+    }
+    // `$((expr))` is arithmetic, unless the inner group does not end the outer
+    // one, as in `$((cmd1); (cmd2))`, which is a command substitution.
+    if chars.get(i + 1) == Some(&'(') && chars.get(i + 2) == Some(&'(') {
+        if let Some((expr, end)) = parser::group(chars, i + 2).filter(|&(_, end)| chars.get(end) == Some(&')')) {
+            let value = arithmetic::evaluate(&expand_word(&expr)?)?;
+            return Ok(Some((vec![value.to_string()], end + 1)));
+        }
+    }
+    if chars.get(i + 1) == Some(&'(') {
+        return Ok(parser::group(chars, i + 1).map(|(cmd, end)| (vec![expand::command_substitution(&cmd)], end)));
+    }
+    let Some((expr, end)) = expand::parameter_name(chars, i + 1) else {
+        return Ok(None);
+    };
+    match expand::expand_fields(&expr, quoted)? {
+        Some(values) => Ok(Some((values, end))),
+        None => Ok(Some((vec![expand::expand_parameter(&expr)?], end))),
+    }
+}
+
+// Quotes `word` for output that can be read back by the shell, if it needs it.
+pub(crate) fn quote_if_needed(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-./=:,+@%^".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
     } else {
-        tokens.join(" ")
+        format!("'{}'", word.replace('\'', "'\\''"))
     }
 }
 
-pub(crate) fn split_args(raw: &str) -> Vec<String> {
-    use std::iter::Peekable;
-    use std::str::Chars;
+// A word being scanned: its text, and which of its characters were quoted
+// (and so must only match themselves when the word is used as a pattern).
+#[derive(Default)]
+struct Word {
+    text: String,
+    quoted: Vec<bool>,
+    // Which characters came from unquoted expansions, where IFS splits fields.
+    split: Vec<bool>,
+    // Where quotes opened: even an empty `""` makes a field.
+    quotes: Vec<usize>,
+    // Spaces that separate the values of `$@` or `${name[@]}`, which always
+    // become separate fields; whether each was inside double quotes.
+    breaks: Vec<(usize, bool)>,
+}
 
+impl Word {
+    fn push(&mut self, c: char, quoted: bool) {
+        self.text.push(c);
+        self.quoted.push(quoted);
+        self.split.push(false);
+    }
+
+    fn push_str(&mut self, s: &str, quoted: bool) {
+        for c in s.chars() {
+            self.push(c, quoted);
+        }
+    }
+
+    // Adds the values of an expansion; unquoted, they are subject to field splitting.
+    fn push_expansion(&mut self, values: &[String], quoted: bool) {
+        // `"$@"` with no values is no word at all, not an empty one.
+        if values.is_empty() && quoted && self.quotes.last() == Some(&self.quoted.len()) {
+            self.quotes.pop();
+        }
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                self.breaks.push((self.quoted.len(), quoted));
+                self.push(' ', true);
+            }
+            for c in value.chars() {
+                self.text.push(c);
+                self.quoted.push(quoted);
+                self.split.push(!quoted);
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.text.is_empty() && self.quotes.is_empty() && self.breaks.is_empty()
+    }
+
+    // Whether the word is a `name=value` assignment written as such, and not
+    // one produced by an expansion or quoting.
+    fn is_assignment(&self) -> bool {
+        let Some(assignment) = variables::Assignment::parse(&self.text) else {
+            return false;
+        };
+        let literal = assignment.name.chars().count();
+        !self.quoted[..literal].contains(&true) && !self.split[..literal].contains(&true)
+    }
+
+    // The word with its quoted characters escaped by a backslash if they are in `special`.
+    fn escaped(&self, special: &str) -> String {
+        let mut out = String::with_capacity(self.text.len());
+        for (c, &quoted) in self.text.chars().zip(&self.quoted) {
+            if quoted && special.contains(c) {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        out
+    }
+
+    fn pattern(&self) -> String {
+        self.escaped("*?[]\\()|!@+")
+    }
+
+    // Splits the word into fields at the IFS characters that came from
+    // unquoted expansions. Blanks in IFS gather into one separator and are
+    // trimmed at the ends; each other IFS character ends a field, empty or not.
+    fn fields(self, ifs: &str) -> Vec<Word> {
+        let chars: Vec<char> = self.text.chars().collect();
+        let blank = |c: char| matches!(c, ' ' | '\t' | '\n');
+        let separator = |i: usize| self.split[i] && ifs.contains(chars[i]);
+        let mut fields = Vec::new();
+        let mut field = Word::default();
+        // Whether the current field exists, even if it is empty.
+        let mut started = false;
+        let mut i = 0;
+        loop {
+            if self.quotes.contains(&i) {
+                started = true;
+            }
+            if i == chars.len() {
+                break;
+            }
+            if let Some(&(_, quoted)) = self.breaks.iter().find(|(at, _)| *at == i) {
+                if started || quoted {
+                    fields.push(std::mem::take(&mut field));
+                }
+                started = quoted;
+                i += 1;
+                continue;
+            }
+            if !separator(i) {
+                field.push(chars[i], self.quoted[i]);
+                started = true;
+                i += 1;
+                continue;
+            }
+            let skip_blanks = |mut j: usize| {
+                while j < chars.len() && separator(j) && blank(chars[j]) {
+                    j += 1;
+                }
+                j
+            };
+            let mut j = skip_blanks(i);
+            let hard = j < chars.len() && separator(j) && !blank(chars[j]);
+            if hard {
+                j = skip_blanks(j + 1);
+            }
+            if started || hard {
+                fields.push(std::mem::take(&mut field));
+            }
+            started = false;
+            i = j;
+        }
+        if started {
+            fields.push(field);
+        }
+        fields
+    }
+}
+
+// The characters that separate fields: IFS, or blanks when it is unset.
+fn field_separators() -> String {
+    variables::get("IFS").unwrap_or_else(|| " \t\n".to_string())
+}
+
+// Expands a word without splitting it, as in the `word` of `${name:-word}`.
+pub(crate) fn expand_word(raw: &str) -> Result<String, CommandError> {
+    let words = scan_words(raw)?;
+    Ok(words.into_iter().map(|word| word.text).collect::<Vec<_>>().join(" "))
+}
+
+// Expands a word for use as a pattern, as in `case` items: quoted characters lose their meaning.
+pub(crate) fn expand_pattern(raw: &str) -> Result<String, CommandError> {
+    let words = scan_words(raw)?;
+    Ok(words.iter().map(Word::pattern).collect::<Vec<_>>().join(" "))
+}
+
+// Expands a word for use as an extended regular expression, as in `[[ =~ ]]`:
+// quoted characters lose their meaning.
+pub(crate) fn expand_regex(raw: &str) -> Result<String, CommandError> {
+    let words = scan_words(raw)?;
+    Ok(words.iter().map(|word| word.escaped(".[]()*+?{}|^$\\")).collect::<Vec<_>>().join(" "))
+}
+
+pub(crate) fn split_args(raw: &str) -> Result<Vec<String>, CommandError> {
+    let extglob = shopt::enabled(ShoptOption::Extglob);
+    let ifs = field_separators();
     let mut result = Vec::new();
-    let mut current = String::new();
+    // Assignments before the command, and those given to `declare` or `export`, are neither split nor globbed.
+    let mut assignments = true;
+    let mut declaration = false;
+    for word in scan_words(raw)? {
+        if (assignments || declaration) && word.is_assignment() {
+            result.push(word.text);
+            continue;
+        }
+        if assignments {
+            declaration = matches!(word.text.as_str(), "declare" | "typeset" | "export");
+        }
+        assignments = false;
+        for field in word.fields(&ifs) {
+            let pattern = field.pattern();
+            if set::enabled(ShellOption::Noglob) || !pattern::has_magic(&pattern, extglob) {
+                result.push(field.text);
+                continue;
+            }
+            let matches = glob::expand(&pattern);
+            if matches.is_empty() && !shopt::enabled(ShoptOption::Nullglob) {
+                result.push(field.text);
+            }
+            result.extend(matches);
+        }
+    }
+    Ok(result)
+}
+
+// Whether the word scanned so far is `name=` or `name+=`, so that a `(` starts an array.
+fn compound_assignment(word: &Word) -> bool {
+    let Some(target) = word.text.strip_suffix('=') else {
+        return false;
+    };
+    let name = target.strip_suffix('+').unwrap_or(target);
+    !word.quoted.contains(&true) && !word.split.contains(&true) && variables::valid_name(name)
+}
+
+// Decodes the `$'...'` string whose opening quote is at `chars[i]`. Returns its
+// text and the index after the closing quote.
+fn ansi_quoted(chars: &[char], i: usize) -> (String, usize) {
+    let mut out = Vec::new();
+    let mut j = i + 1;
+    while j < chars.len() && chars[j] != '\'' {
+        if chars[j] == '\\' {
+            j = printf::escape_at(chars, j, EscapeMode::Ansi, &mut out).unwrap_or(j + 2);
+        } else {
+            out.extend_from_slice(chars[j].encode_utf8(&mut [0; 4]).as_bytes());
+            j += 1;
+        }
+    }
+    // As in bash, a NUL ends the string.
+    if let Some(nul) = out.iter().position(|&byte| byte == 0) {
+        out.truncate(nul);
+    }
+    (String::from_utf8_lossy(&out).into_owned(), j + 1)
+}
+
+fn scan_words(raw: &str) -> Result<Vec<Word>, CommandError> {
+    let mut result = Vec::new();
+    let mut current = Word::default();
     let mut in_single_quotes = false;
     let mut in_double_quotes = false;
+    let mut at_word_start = true;
 
-    let mut iter: Peekable<Chars<'_>> = raw.chars().peekable();
+    let chars: Vec<char> = raw.chars().collect();
+    let mut i = 0;
 
-    while let Some(ch) = iter.next() {
+    while i < chars.len() {
+        let ch = chars[i];
+        i += 1;
+        let starts_word = std::mem::replace(&mut at_word_start, false);
+        let quoted = in_single_quotes || in_double_quotes;
         match ch {
+            '~' if starts_word && !quoted => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|c| !matches!(c, '/' | ' ' | '\n' | '\'' | '"' | '\\' | '$'))
+                    .count();
+                let prefix: String = chars[i..i + len].iter().collect();
+                i += len;
+                match expand_tilde(&prefix) {
+                    Some(dir) => current.push_str(&dir, true),
+                    None => {
+                        current.push('~', false);
+                        current.push_str(&prefix, false);
+                    }
+                }
+            }
+            // `$'...'` decodes C escapes; `$"..."` is an ordinary double-quoted string.
+            '$' if !quoted && chars.get(i) == Some(&'\'') => {
+                let (text, end) = ansi_quoted(&chars, i);
+                current.quotes.push(current.quoted.len());
+                current.push_str(&text, true);
+                i = end;
+            }
+            '$' if !quoted && chars.get(i) == Some(&'"') => {}
+            '$' | '`' if !in_single_quotes => match expansion_at(&chars, i - 1, quoted)? {
+                Some((values, end)) => {
+                    current.push_expansion(&values, quoted);
+                    i = end;
+                }
+                None => current.push(ch, quoted),
+            },
+            // `<(cmd)` and `>(cmd)` stand for a path connected to the input or output of cmd.
+            '<' | '>' if !quoted && current.is_empty() && chars.get(i) == Some(&'(') => {
+                let Some((cmd, end)) = parser::group(&chars, i) else {
+                    return Err(CommandError::Syntax("newline".to_string()));
+                };
+                if let Some(path) = procsub::start(&cmd, ch == '<') {
+                    current.push_str(&path, true);
+                }
+                i = end;
+            }
+            // `name=(...)` keeps its words unexpanded until the assignment runs.
+            '(' if !quoted && compound_assignment(&current) => {
+                let Some((inner, end)) = parser::group(&chars, i - 1) else {
+                    return Err(CommandError::Syntax("newline".to_string()));
+                };
+                current.push(variables::COMPOUND_MARK, true);
+                current.push_str(&inner, true);
+                i = end;
+            }
             '\'' if !in_double_quotes => {
+                if !in_single_quotes {
+                    current.quotes.push(current.quoted.len());
+                }
                 in_single_quotes = !in_single_quotes;
             }
             '"' if !in_single_quotes => {
+                if !in_double_quotes {
+                    current.quotes.push(current.quoted.len());
+                }
                 in_double_quotes = !in_double_quotes;
             }
             '\\' => {
                 if in_single_quotes {
                     // In single quotes backslash is literal
-                    current.push('\\');
+                    current.push('\\', true);
                 } else if in_double_quotes {
                     // In double quotes, backslash only escapes a few chars: " \\ $ `
-                    match iter.peek() {
+                    match chars.get(i) {
                         Some(&next) => match next {
                             '"' | '\\' | '$' | '`' => {
                                 // consume and push the escaped char
-                                current.push(iter.next().unwrap());
+                                current.push(next, true);
+                                i += 1;
                             }
                             '\n' => {
                                 // backslash-newline: remove both (line continuation)
-                                iter.next();
+                                i += 1;
                             }
                             _ => {
                                 // leave backslash as literal
-                                current.push('\\');
+                                current.push('\\', true);
                             }
                         },
                         None => {
                             // trailing backslash -> literal
-                            current.push('\\');
+                            current.push('\\', true);
                         }
                     }
                 } else {
                     // Outside any quotes: backslash escapes next character (if any)
-                    match iter.next() {
-                        Some(next) => current.push(next),
-                        None => current.push('\\'),
+                    match chars.get(i) {
+                        Some('\n') => {
+                            // backslash-newline: line continuation
+                            i += 1;
+                        }
+                        Some(&next) => {
+                            current.push(next, true);
+                            i += 1;
+                        }
+                        None => current.push('\\', true),
                     }
                 }
             }
-            ' ' if !in_single_quotes && !in_double_quotes => {
+            ' ' | '\t' | '\n' if !quoted => {
                 if !current.is_empty() {
-                    result.push(current.clone());
-                    current.clear();
+                    result.push(std::mem::take(&mut current));
                 }
                 // else skip multiple spaces
+                at_word_start = true;
             }
-            other => current.push(other),
+            other => current.push(other, quoted),
         }
     }
 
@@ -104,5 +609,128 @@ pub(crate) fn split_args(raw: &str) -> Vec<String> {
         result.push(current);
     }
 
-    result
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLANKS: &str = " \t\n";
+
+    // A word made of the unquoted expansion of `value`, after the literal `prefix`.
+    fn expansion(prefix: &str, value: &str) -> Word {
+        let mut word = Word::default();
+        word.push_str(prefix, false);
+        word.push_expansion(&[value.to_string()], false);
+        word
+    }
+
+    fn fields(word: Word, ifs: &str) -> Vec<String> {
+        word.fields(ifs).into_iter().map(|field| field.text).collect()
+    }
+
+    #[test]
+    fn blanks_gather_and_are_trimmed() {
+        assert_eq!(fields(expansion("", "  a \t b\n "), BLANKS), ["a", "b"]);
+        assert_eq!(fields(expansion("x", " a  b "), BLANKS), ["x", "a", "b"]);
+        assert_eq!(fields(expansion("", "   "), BLANKS), Vec::<String>::new());
+    }
+
+    #[test]
+    fn other_separators_each_end_a_field() {
+        assert_eq!(fields(expansion("", "a::b:"), ":"), ["a", "", "b"]);
+        assert_eq!(fields(expansion("", ":a"), ":"), ["", "a"]);
+        assert_eq!(fields(expansion("", " a : b "), " :"), ["a", "b"]);
+        assert_eq!(fields(expansion("", "a : : b"), " :"), ["a", "", "b"]);
+    }
+
+    #[test]
+    fn only_unquoted_expansions_split() {
+        let mut word = Word::default();
+        word.push_str("a b", false);
+        assert_eq!(fields(word, BLANKS), ["a b"]);
+        let mut word = Word::default();
+        word.push_expansion(&["a b".to_string()], true);
+        assert_eq!(fields(word, BLANKS), ["a b"]);
+        assert_eq!(fields(expansion("", "a b"), ""), ["a b"]);
+    }
+
+    #[test]
+    fn quotes_make_a_field_even_when_empty() {
+        assert_eq!(fields(expansion("", ""), BLANKS), Vec::<String>::new());
+        let mut word = expansion("", "");
+        word.quotes.push(0);
+        assert_eq!(fields(word, BLANKS), [""]);
+    }
+
+    #[test]
+    fn quoted_lists_of_values_become_separate_fields() {
+        let mut word = Word::default();
+        word.push_str("x", false);
+        word.push_expansion(&["a b".to_string(), "c".to_string()], true);
+        word.push_str("y", false);
+        assert_eq!(fields(word, BLANKS), ["xa b", "cy"]);
+        // `"$@"` with no values is no field at all.
+        let mut word = Word::default();
+        word.quotes.push(0);
+        word.push_expansion(&[], true);
+        assert_eq!(fields(word, BLANKS), Vec::<String>::new());
+    }
+
+    #[test]
+    fn arguments_split_but_assignments_do_not() {
+        variables::set("ifs_test_value", " a  b ").unwrap();
+        assert_eq!(
+            split_args("ifs_test_x=$ifs_test_value echo $ifs_test_value \"$ifs_test_value\" x=$ifs_test_value").unwrap(),
+            ["ifs_test_x= a  b ", "echo", "a", "b", " a  b ", "x=", "a", "b"]
+        );
+        assert_eq!(split_args("declare ifs_test_x=$ifs_test_value").unwrap(), ["declare", "ifs_test_x= a  b "]);
+    }
+
+    #[test]
+    fn redirections_come_from_unquoted_operators() {
+        let (text, redirections) = split_redirections("echo a>out 2>>log <in").unwrap();
+        assert_eq!(text, "echo a");
+        let found: Vec<_> = redirections.iter().map(|r| (r.target, r.file.as_str(), r.mode)).collect();
+        assert_eq!(
+            found,
+            [
+                (RedirectionTarget::Stdin, "in", RedirectionMode::Read),
+                (RedirectionTarget::Stdout, "out", RedirectionMode::Overwrite),
+                (RedirectionTarget::Stderr, "log", RedirectionMode::Append),
+            ]
+        );
+        // Quoted or escaped operators, and operators that come from an expansion, are words.
+        variables::set("redir_test_op", ">").unwrap();
+        for input in ["echo '>' f", "echo \\> f", "echo $redir_test_op f"] {
+            let (text, redirections) = split_redirections(input).unwrap();
+            assert_eq!(text, input);
+            assert!(redirections.is_empty(), "{}", input);
+        }
+        // Only an unquoted number names the descriptor.
+        let (text, redirections) = split_redirections("echo \"2\">f").unwrap();
+        assert_eq!(text, "echo \"2\"");
+        assert_eq!(redirections[0].target, RedirectionTarget::Stdout);
+        // The last redirection of a stream wins; `&>` sends stderr where stdout goes.
+        let (_, redirections) = split_redirections("cmd >a &>b").unwrap();
+        let found: Vec<_> = redirections.iter().map(|r| (r.target, r.file.as_str(), r.mode)).collect();
+        assert_eq!(
+            found,
+            [
+                (RedirectionTarget::Stdout, "b", RedirectionMode::Overwrite),
+                (RedirectionTarget::Stderr, "1", RedirectionMode::Duplicate),
+            ]
+        );
+        assert_eq!(split_redirections("echo >").unwrap_err().to_string(), "syntax error near unexpected token `newline'");
+        assert_eq!(split_redirections("echo > >").unwrap_err().to_string(), "syntax error near unexpected token `>'");
+    }
+
+    #[test]
+    fn arithmetic_expansion() {
+        variables::set("arith_test_x", "4").unwrap();
+        assert_eq!(split_args("echo $((1+2)) \"$(( arith_test_x * 2 ))\" $(( ($arith_test_x + 1) * 3 ))").unwrap(), ["echo", "3", "8", "15"]);
+        assert_eq!(split_args("echo x$((arith_test_x-5))y").unwrap(), ["echo", "x-1y"]);
+        assert_eq!(split_args("echo $((1/0))").unwrap_err().to_string(), "1/0: division by 0 (error token is \"0\")");
+    }
 }
@@ -0,0 +1,593 @@
+// Shell variables, kept in the shell state. Child processes get only the
+// scalars marked for export as their environment; arrays are never exported.
+use std::collections::{BTreeMap, BTreeSet};
+use std::process::Command as StdCommand;
+use super::state::STATE;
+use super::parser::{self, Token};
+use super::{arithmetic, special, utils, CommandError};
+
+/// Marks the value of a `name=(...)` word after scanning: the raw text between
+/// the parentheses follows it. A NUL cannot otherwise appear in a shell word, so
+/// this tells a compound assignment from a quoted value such as `x="(a)"`.
+pub(crate) const COMPOUND_MARK: char = '\0';
+
+#[derive(Debug, Clone)]
+pub(crate) enum Array {
+    // Indexed arrays may be sparse: `a[10]=x` does not create `a[1]` to `a[9]`.
+    Indexed(BTreeMap<usize, String>),
+    Associative(BTreeMap<String, String>),
+}
+
+impl Array {
+    fn values(&self) -> Vec<String> {
+        match self {
+            Array::Indexed(map) => map.values().cloned().collect(),
+            Array::Associative(map) => map.values().cloned().collect(),
+        }
+    }
+
+    fn keys(&self) -> Vec<String> {
+        match self {
+            Array::Indexed(map) => map.keys().map(usize::to_string).collect(),
+            Array::Associative(map) => map.keys().cloned().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Attribute {
+    Integer,
+    Nameref,
+    Readonly,
+    Trace,
+    Export,
+    Lowercase,
+    Uppercase,
+}
+
+/// The `declare` option letter of each attribute, in the order `declare -p` prints them.
+pub(crate) const ATTRIBUTES: &[(Attribute, char)] = &[
+    (Attribute::Integer, 'i'),
+    (Attribute::Nameref, 'n'),
+    (Attribute::Readonly, 'r'),
+    (Attribute::Trace, 't'),
+    (Attribute::Export, 'x'),
+    (Attribute::Lowercase, 'l'),
+    (Attribute::Uppercase, 'u'),
+];
+
+pub(crate) fn has_attribute(name: &str, attribute: Attribute) -> bool {
+    STATE.read().unwrap().attributes.get(name).is_some_and(|set| set.contains(&attribute))
+}
+
+pub(crate) fn set_attribute(name: &str, attribute: Attribute, on: bool) {
+    let mut shell = STATE.write().unwrap();
+    if on {
+        shell.attributes.entry(name.to_string()).or_default().insert(attribute);
+    } else if let Some(set) = shell.attributes.get_mut(name) {
+        set.remove(&attribute);
+        if set.is_empty() {
+            shell.attributes.remove(name);
+        }
+    }
+}
+
+/// Turns the environment the shell started with into exported variables.
+pub(crate) fn import_environment() {
+    // What a shell hands its subshells in `__SHELL_*` entries is not a variable.
+    for (name, value) in std::env::vars().filter(|(name, _)| !name.starts_with("__SHELL_")) {
+        STATE.write().unwrap().scalars.insert(name.clone(), value);
+        set_attribute(&name, Attribute::Export, true);
+    }
+}
+
+/// The exported variables, as the environment of a child process.
+pub(crate) fn exported() -> Vec<(String, String)> {
+    let shell = STATE.read().unwrap();
+    shell
+        .scalars
+        .iter()
+        .filter(|(name, _)| shell.attributes.get(*name).is_some_and(|set| set.contains(&Attribute::Export)))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// Gives `command` the exported variables, and nothing else, as its environment.
+pub(crate) fn export_to(command: &mut StdCommand) {
+    command.env_clear().envs(exported());
+}
+
+// The value of a plain variable, without looking at arrays or references.
+fn scalar(name: &str) -> Option<String> {
+    STATE.read().unwrap().scalars.get(name).cloned()
+}
+
+/// The variable `name` stands for, following `declare -n` references.
+pub(crate) fn resolve(name: &str) -> String {
+    let mut name = name.to_string();
+    // Bounded, as references may form a loop.
+    for _ in 0..8 {
+        if !has_attribute(&name, Attribute::Nameref) {
+            break;
+        }
+        match scalar(&name) {
+            Some(target) if !target.is_empty() && target != name => name = target,
+            _ => break,
+        }
+    }
+    name
+}
+
+/// Makes `name` a reference to the variable `target`.
+pub(crate) fn set_reference(name: &str, target: &str) -> Result<(), CommandError> {
+    writable(name)?;
+    let mut shell = STATE.write().unwrap();
+    shell.arrays.remove(name);
+    shell.scalars.insert(name.to_string(), target.to_string());
+    drop(shell);
+    set_attribute(name, Attribute::Nameref, true);
+    Ok(())
+}
+
+fn writable(name: &str) -> Result<(), CommandError> {
+    if has_attribute(name, Attribute::Readonly) {
+        return Err(CommandError::NullParameter(name.to_string(), "readonly variable".to_string()));
+    }
+    Ok(())
+}
+
+// The value stored when assigning `value` to `name`, given its attributes:
+// integers are evaluated, and case is converted. `old` is the value `+=` appends to.
+fn convert(name: &str, value: &str, old: Option<&str>) -> Result<String, CommandError> {
+    let value = if has_attribute(name, Attribute::Integer) {
+        let old: i64 = old.map_or(Ok(0), arithmetic::evaluate)?;
+        old.wrapping_add(arithmetic::evaluate(value)?).to_string()
+    } else {
+        format!("{}{}", old.unwrap_or_default(), value)
+    };
+    Ok(if has_attribute(name, Attribute::Lowercase) {
+        value.to_lowercase()
+    } else if has_attribute(name, Attribute::Uppercase) {
+        value.to_uppercase()
+    } else {
+        value
+    })
+}
+
+/// Builds an indexed array from a list of values, as for `BASH_REMATCH` or `read -a`.
+pub(crate) fn indexed(values: Vec<String>) -> Array {
+    Array::Indexed(values.into_iter().enumerate().collect())
+}
+
+pub(crate) fn valid_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// `label` is what bash names in the message: the whole `name[subscript]` when
+// assigning, only the name when expanding.
+fn bad_subscript(label: String) -> CommandError {
+    CommandError::NullParameter(label, "bad array subscript".to_string())
+}
+
+// Evaluates the subscript of an indexed array, an arithmetic expression.
+// This must not run with STATE locked, as the expression may name variables.
+fn subscript_number(subscript: &str, label: impl FnOnce() -> String) -> Result<i64, CommandError> {
+    if subscript.trim().is_empty() {
+        return Err(bad_subscript(label()));
+    }
+    arithmetic::evaluate(subscript)
+}
+
+// The index a subscript number stands for: negative numbers count back from the end.
+fn position(number: i64, map: Option<&BTreeMap<usize, String>>) -> Option<usize> {
+    if number >= 0 {
+        return Some(number as usize);
+    }
+    let len = map.and_then(|map| map.keys().next_back()).map_or(0, |last| last + 1);
+    usize::try_from(len as i64 + number).ok()
+}
+
+fn is_associative(name: &str) -> bool {
+    matches!(STATE.read().unwrap().arrays.get(name), Some(Array::Associative(_)))
+}
+
+/// Splits `name[subscript]` into its parts.
+pub(crate) fn split_subscript(text: &str) -> Option<(&str, &str)> {
+    let (name, rest) = text.split_once('[')?;
+    let subscript = rest.strip_suffix(']')?;
+    valid_name(name).then_some((name, subscript))
+}
+
+/// Every variable the shell knows of, by name.
+pub(crate) fn names() -> BTreeSet<String> {
+    let shell = STATE.read().unwrap();
+    shell
+        .scalars
+        .keys()
+        .cloned()
+        .chain(shell.arrays.keys().cloned())
+        .chain(shell.attributes.keys().cloned())
+        .collect()
+}
+
+pub(crate) fn is_array(name: &str) -> bool {
+    STATE.read().unwrap().arrays.contains_key(&resolve(name))
+}
+
+/// The value of a variable. An array used as a plain variable means its element 0.
+pub(crate) fn get(name: &str) -> Option<String> {
+    let name = &resolve(name);
+    if let Some(value) = special::dynamic_value(name) {
+        return Some(value);
+    }
+    match STATE.read().unwrap().arrays.get(name) {
+        Some(Array::Indexed(map)) => map.get(&0).cloned(),
+        Some(Array::Associative(map)) => map.get("0").cloned(),
+        None => scalar(name),
+    }
+}
+
+/// Sets a variable, applying its attributes. For an array this sets its element 0.
+pub(crate) fn set(name: &str, value: &str) -> Result<(), CommandError> {
+    assign_scalar(name, value, false)
+}
+
+fn assign_scalar(name: &str, value: &str, append: bool) -> Result<(), CommandError> {
+    let name = &resolve(name);
+    writable(name)?;
+    let old = if append { get(name) } else { None };
+    let value = convert(name, value, old.as_deref())?;
+    if special::assign(name, &value) {
+        return Ok(());
+    }
+    let mut shell = STATE.write().unwrap();
+    match shell.arrays.get_mut(name) {
+        Some(Array::Indexed(map)) => {
+            map.insert(0, value);
+        }
+        Some(Array::Associative(map)) => {
+            map.insert("0".to_string(), value);
+        }
+        None => {
+            shell.scalars.insert(name.to_string(), value);
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn set_array(name: &str, array: Array) -> Result<(), CommandError> {
+    let name = &resolve(name);
+    writable(name)?;
+    let mut shell = STATE.write().unwrap();
+    shell.scalars.remove(name);
+    shell.arrays.insert(name.to_string(), array);
+    Ok(())
+}
+
+/// An element of an array; `subscript` is already expanded. A scalar is an
+/// array of one element.
+pub(crate) fn element(name: &str, subscript: &str) -> Result<Option<String>, CommandError> {
+    let name = &resolve(name);
+    if is_associative(name) {
+        return Ok(get_element(name, |array| match array {
+            Array::Associative(map) => map.get(subscript).cloned(),
+            Array::Indexed(_) => None,
+        }));
+    }
+    let number = subscript_number(subscript, || name.to_string())?;
+    let shell = STATE.read().unwrap();
+    match shell.arrays.get(name) {
+        Some(Array::Indexed(map)) => {
+            let index = position(number, Some(map)).ok_or_else(|| bad_subscript(name.to_string()))?;
+            Ok(map.get(&index).cloned())
+        }
+        _ => {
+            let index = position(number, None).ok_or_else(|| bad_subscript(name.to_string()))?;
+            Ok(if index == 0 { shell.scalars.get(name).cloned() } else { None })
+        }
+    }
+}
+
+fn get_element(name: &str, find: impl FnOnce(&Array) -> Option<String>) -> Option<String> {
+    STATE.read().unwrap().arrays.get(name).and_then(find)
+}
+
+/// All the values of an array, in index order.
+pub(crate) fn values(name: &str) -> Vec<String> {
+    let name = &resolve(name);
+    match STATE.read().unwrap().arrays.get(name) {
+        Some(array) => array.values(),
+        None => scalar(name).into_iter().collect(),
+    }
+}
+
+/// The indices or keys of an array.
+pub(crate) fn keys(name: &str) -> Vec<String> {
+    let name = &resolve(name);
+    match STATE.read().unwrap().arrays.get(name) {
+        Some(array) => array.keys(),
+        None => scalar(name).map(|_| vec!["0".to_string()]).unwrap_or_default(),
+    }
+}
+
+/// The values of an indexed array from index `offset` on, at most `length` of
+/// them; a negative offset counts back from the end.
+pub(crate) fn slice(name: &str, offset: i64, length: Option<i64>) -> Result<Vec<String>, CommandError> {
+    let name = &resolve(name);
+    let shell = STATE.read().unwrap();
+    let entries: Vec<(usize, String)> = match shell.arrays.get(name) {
+        Some(Array::Indexed(map)) => map.iter().map(|(k, v)| (*k, v.clone())).collect(),
+        Some(Array::Associative(map)) => map.values().cloned().enumerate().collect(),
+        None => shell.scalars.get(name).cloned().into_iter().enumerate().collect(),
+    };
+    let end = entries.last().map_or(0, |(index, _)| *index as i64 + 1);
+    let start = if offset < 0 { end + offset } else { offset };
+    if start < 0 {
+        return Ok(Vec::new());
+    }
+    let selected = entries.into_iter().filter(|(index, _)| *index as i64 >= start).map(|(_, value)| value);
+    match length {
+        Some(length) if length < 0 => Err(CommandError::NullParameter(length.to_string(), "substring expression < 0".to_string())),
+        Some(length) => Ok(selected.take(length as usize).collect()),
+        None => Ok(selected.collect()),
+    }
+}
+
+/// Sets one element, turning a scalar into an indexed array if needed.
+pub(crate) fn set_element(name: &str, subscript: &str, value: &str, append: bool) -> Result<(), CommandError> {
+    let name = &resolve(name);
+    writable(name)?;
+    let number = match is_associative(name) {
+        true => None,
+        false => Some(subscript_number(subscript, || format!("{}[{}]", name, subscript))?),
+    };
+    let mut shell = STATE.write().unwrap();
+    if !shell.arrays.contains_key(name) {
+        let existing = shell.scalars.remove(name);
+        shell.arrays.insert(name.to_string(), indexed(existing.into_iter().collect()));
+    }
+    let index = match (shell.arrays.get(name), number) {
+        (Some(Array::Indexed(map)), Some(number)) => {
+            Some(position(number, Some(map)).ok_or_else(|| bad_subscript(format!("{}[{}]", name, subscript)))?)
+        }
+        _ => None,
+    };
+    let old = match (shell.arrays.get(name), index) {
+        (Some(Array::Indexed(map)), Some(index)) => map.get(&index).cloned(),
+        (Some(Array::Associative(map)), _) => map.get(subscript).cloned(),
+        _ => None,
+    };
+    drop(shell);
+
+    let value = convert(name, value, if append { old.as_deref().or(Some("")) } else { None })?;
+    match STATE.write().unwrap().arrays.get_mut(name) {
+        Some(Array::Indexed(map)) => {
+            map.insert(index.unwrap_or_default(), value);
+        }
+        Some(Array::Associative(map)) => {
+            map.insert(subscript.to_string(), value);
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+/// Makes `name` an array of the given kind, keeping a scalar value as element 0.
+pub(crate) fn declare_array(name: &str, associative: bool) -> Result<(), CommandError> {
+    let name = &resolve(name);
+    let mut shell = STATE.write().unwrap();
+    match shell.arrays.get(name) {
+        Some(Array::Indexed(_)) if associative => {
+            Err(CommandError::NullParameter(name.to_string(), "cannot convert indexed to associative array".to_string()))
+        }
+        Some(Array::Associative(_)) if !associative => {
+            Err(CommandError::NullParameter(name.to_string(), "cannot convert associative to indexed array".to_string()))
+        }
+        Some(_) => Ok(()),
+        None => {
+            let existing = shell.scalars.remove(name);
+            let array = if associative {
+                Array::Associative(existing.map(|value| ("0".to_string(), value)).into_iter().collect())
+            } else {
+                indexed(existing.into_iter().collect())
+            };
+            shell.arrays.insert(name.to_string(), array);
+            Ok(())
+        }
+    }
+}
+
+/// Unsets a variable, or what it refers to, along with its attributes.
+pub(crate) fn unset(name: &str) -> Result<(), CommandError> {
+    remove(&resolve(name))
+}
+
+/// Unsets a `declare -n` reference itself rather than what it refers to.
+pub(crate) fn unset_reference(name: &str) -> Result<(), CommandError> {
+    remove(name)
+}
+
+fn remove(name: &str) -> Result<(), CommandError> {
+    if has_attribute(name, Attribute::Readonly) {
+        return Err(CommandError::NullParameter(name.to_string(), "cannot unset: readonly variable".to_string()));
+    }
+    let mut shell = STATE.write().unwrap();
+    shell.scalars.remove(name);
+    shell.arrays.remove(name);
+    shell.attributes.remove(name);
+    Ok(())
+}
+
+// A variable as it was before `name=value command` changed it for one command.
+pub(crate) struct Saved {
+    name: String,
+    value: Option<String>,
+    exported: bool,
+}
+
+/// Records `name` so that `restore` can put it back after a prefixed command.
+pub(crate) fn save(name: &str) -> Saved {
+    let name = resolve(name);
+    let value = scalar(&name);
+    let exported = has_attribute(&name, Attribute::Export);
+    Saved { name, value, exported }
+}
+
+/// Puts back a variable recorded with `save`.
+pub(crate) fn restore(saved: Saved) {
+    let mut shell = STATE.write().unwrap();
+    match saved.value {
+        Some(value) => shell.scalars.insert(saved.name.clone(), value),
+        None => shell.scalars.remove(&saved.name),
+    };
+    drop(shell);
+    set_attribute(&saved.name, Attribute::Export, saved.exported);
+}
+
+pub(crate) fn unset_element(name: &str, subscript: &str) -> Result<(), CommandError> {
+    let name = &resolve(name);
+    if has_attribute(name, Attribute::Readonly) {
+        return Err(CommandError::NullParameter(name.to_string(), "cannot unset: readonly variable".to_string()));
+    }
+    if is_associative(name) {
+        if let Some(Array::Associative(map)) = STATE.write().unwrap().arrays.get_mut(name) {
+            map.remove(subscript);
+        }
+        return Ok(());
+    }
+    let number = subscript_number(subscript, || format!("[{}]", subscript))?;
+    let mut shell = STATE.write().unwrap();
+    match shell.arrays.get_mut(name) {
+        Some(Array::Indexed(map)) => {
+            let index = position(number, Some(map)).ok_or_else(|| bad_subscript(format!("[{}]", subscript)))?;
+            map.remove(&index);
+        }
+        _ => {
+            if position(number, None).ok_or_else(|| bad_subscript(format!("[{}]", subscript)))? == 0 {
+                shell.scalars.remove(name);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Splits a raw `[subscript]=value` word of a compound assignment.
+fn explicit_subscript(word: &str) -> Option<(&str, &str)> {
+    let rest = word.strip_prefix('[')?;
+    let close = rest.find("]=")?;
+    Some((&rest[..close], &rest[close + 2..]))
+}
+
+/// Assigns `name=(words)`, or appends with `name+=(words)`. `inner` is the raw
+/// text between the parentheses; its words are expanded here.
+pub(crate) fn assign_compound(name: &str, inner: &str, append: bool) -> Result<(), CommandError> {
+    let tokens = parser::tokenize(inner).map_err(|_| CommandError::Syntax("newline".to_string()))?;
+    let mut words = Vec::new();
+    for (token, _) in tokens {
+        match token {
+            Token::Word(word) => words.push(word),
+            Token::Newline => {}
+            Token::Operator(op) => return Err(CommandError::Syntax(op.to_string())),
+        }
+    }
+
+    let name = &resolve(name);
+    writable(name)?;
+    let associative = is_associative(name);
+    let mut array = match STATE.read().unwrap().arrays.get(name) {
+        Some(array) if append => array.clone(),
+        _ if associative => Array::Associative(BTreeMap::new()),
+        _ => Array::Indexed(BTreeMap::new()),
+    };
+    match &mut array {
+        Array::Indexed(map) => {
+            // Appending to a scalar keeps its value as element 0.
+            if append && map.is_empty() {
+                if let Some(value) = scalar(name) {
+                    map.insert(0, value);
+                }
+            }
+            let mut next = map.keys().next_back().map_or(0, |last| last + 1);
+            for word in &words {
+                if let Some((subscript, value)) = explicit_subscript(word) {
+                    let subscript = utils::expand_word(subscript)?;
+                    let number = subscript_number(&subscript, || format!("{}[{}]", name, subscript))?;
+                    let index = position(number, Some(map)).ok_or_else(|| bad_subscript(format!("{}[{}]", name, subscript)))?;
+                    map.insert(index, convert(name, &utils::expand_word(value)?, None)?);
+                    next = index + 1;
+                    continue;
+                }
+                for value in utils::split_args(word)? {
+                    map.insert(next, convert(name, &value, None)?);
+                    next += 1;
+                }
+            }
+        }
+        Array::Associative(map) => {
+            // Without subscripts the words are key-value pairs.
+            let mut pending_key: Option<String> = None;
+            for word in &words {
+                if let Some((subscript, value)) = explicit_subscript(word) {
+                    map.insert(utils::expand_word(subscript)?, convert(name, &utils::expand_word(value)?, None)?);
+                    continue;
+                }
+                let word = utils::expand_word(word)?;
+                match pending_key.take() {
+                    Some(key) => {
+                        map.insert(key, convert(name, &word, None)?);
+                    }
+                    None => pending_key = Some(word),
+                }
+            }
+            if let Some(key) = pending_key {
+                map.insert(key, String::new());
+            }
+        }
+    }
+    set_array(name, array)
+}
+
+// A `name=value` word, possibly `name[subscript]=value`, `name+=value` or `name=(...)`.
+#[derive(Debug)]
+pub(crate) struct Assignment {
+    pub name: String,
+    pub subscript: Option<String>,
+    pub append: bool,
+    pub value: String,
+}
+
+impl Assignment {
+    pub(crate) fn parse(word: &str) -> Option<Assignment> {
+        let (target, value) = word.split_once('=')?;
+        let (target, append) = match target.strip_suffix('+') {
+            Some(target) => (target, true),
+            None => (target, false),
+        };
+        let (name, subscript) = match split_subscript(target) {
+            Some((name, subscript)) => (name, Some(subscript.to_string())),
+            None if valid_name(target) => (target, None),
+            None => return None,
+        };
+        Some(Assignment { name: name.to_string(), subscript, append, value: value.to_string() })
+    }
+
+    // The raw words of a `name=(...)` assignment.
+    fn compound(&self) -> Option<&str> {
+        self.value.strip_prefix(COMPOUND_MARK)
+    }
+
+    /// Whether this sets a plain variable, which can go in a command's environment.
+    pub(crate) fn is_scalar(&self) -> bool {
+        self.subscript.is_none() && self.compound().is_none()
+    }
+
+    pub(crate) fn apply(&self) -> Result<(), CommandError> {
+        if let Some(subscript) = &self.subscript {
+            return set_element(&self.name, subscript, &self.value, self.append);
+        }
+        if let Some(inner) = self.compound() {
+            return assign_compound(&self.name, inner, self.append);
+        }
+        assign_scalar(&self.name, &self.value, self.append)
+    }
+}
@@ -0,0 +1,258 @@
+// Frecency-based directory jumping, compatible with the data file of rupa/z.
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use super::state::STATE;
+use super::{cd, utils, variables, Command, CommandError, Redirection};
+
+// Once the ranks add up to more than this, all of them decay.
+const MAX_TOTAL_RANK: f64 = 9000.0;
+
+struct Entry {
+    path: String,
+    rank: f64,
+    time: u64,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Ordering {
+    Frecency,
+    Rank,
+    Recent,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn data_file() -> Option<PathBuf> {
+    variables::get("_Z_DATA")
+        .map(PathBuf::from)
+        .or_else(|| variables::get("HOME").map(|home| PathBuf::from(home).join(".z")))
+}
+
+fn load() -> Vec<Entry> {
+    let Some(contents) = data_file().and_then(|file| fs::read_to_string(file).ok()) else {
+        return Vec::new();
+    };
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, '|');
+            let time = fields.next()?.parse().ok()?;
+            let rank = fields.next()?.parse().ok()?;
+            let path = fields.next()?.to_string();
+            Some(Entry { path, rank, time })
+        })
+        .collect()
+}
+
+fn save(entries: &[Entry]) {
+    let Some(file) = data_file() else {
+        return;
+    };
+    let contents: String = entries
+        .iter()
+        .map(|e| format!("{}|{}|{}\n", e.path, e.rank, e.time))
+        .collect();
+    // Write a temporary file first so that a concurrent shell never sees a partial file.
+    let tmp = file.with_extension(format!("tmp.{}", std::process::id()));
+    if fs::write(&tmp, contents).is_ok() && fs::rename(&tmp, &file).is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+}
+
+fn score(entry: &Entry, ordering: Ordering, now: u64) -> f64 {
+    match ordering {
+        Ordering::Rank => entry.rank,
+        Ordering::Recent => -(now.saturating_sub(entry.time) as f64),
+        Ordering::Frecency => {
+            let age = now.saturating_sub(entry.time);
+            let weight = match age {
+                0..=3599 => 4.0,
+                3600..=86_399 => 2.0,
+                86_400..=604_799 => 0.5,
+                _ => 0.25,
+            };
+            entry.rank * weight
+        }
+    }
+}
+
+/// Records a visit to `dir`. Called on every directory change, but only those
+/// made at the prompt count: not those of scripts, `-c` or substitutions.
+pub(crate) fn record(dir: &Path) {
+    if !STATE.read().unwrap().interactive || !io::stdin().is_terminal() {
+        return;
+    }
+    let Some(path) = dir.to_str() else {
+        return;
+    };
+    if variables::get("HOME").is_some_and(|home| Path::new(&home) == dir) {
+        return;
+    }
+    let mut entries = load();
+    let time = now();
+    match entries.iter_mut().find(|e| e.path == path) {
+        Some(entry) => {
+            entry.rank += 1.0;
+            entry.time = time;
+        }
+        None => entries.push(Entry { path: path.to_string(), rank: 1.0, time }),
+    }
+    if entries.iter().map(|e| e.rank).sum::<f64>() > MAX_TOTAL_RANK {
+        for entry in &mut entries {
+            entry.rank *= 0.99;
+        }
+        entries.retain(|e| e.rank >= 1.0);
+    }
+    save(&entries);
+}
+
+// Whether `path` contains every pattern, in order.
+fn matches_all(path: &str, patterns: &[String], ignore_case: bool) -> bool {
+    let (path, patterns): (String, Vec<String>) = if ignore_case {
+        (path.to_lowercase(), patterns.iter().map(|p| p.to_lowercase()).collect())
+    } else {
+        (path.to_string(), patterns.to_vec())
+    };
+    let mut rest = path.as_str();
+    for pattern in &patterns {
+        match rest.find(pattern.as_str()) {
+            Some(idx) => rest = &rest[idx + pattern.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+// Existing directories matching the patterns, best first, with their scores.
+// Case-sensitive matches win; case-insensitive ones are only used when there are none.
+fn ranked(patterns: &[String], ordering: Ordering) -> Vec<(String, f64)> {
+    let entries = load();
+    let now = now();
+    for ignore_case in [false, true] {
+        let mut found: Vec<(String, f64)> = entries
+            .iter()
+            .filter(|e| matches_all(&e.path, patterns, ignore_case) && Path::new(&e.path).is_dir())
+            .map(|e| (e.path.clone(), score(e, ordering, now)))
+            .collect();
+        if !found.is_empty() {
+            found.sort_by(|a, b| b.1.total_cmp(&a.1));
+            return found;
+        }
+    }
+    Vec::new()
+}
+
+/// Directories matching `word`, best first. Used for tab completion of `z` and `zi`.
+pub(crate) fn candidates(word: &str) -> Vec<String> {
+    let patterns: Vec<String> = if word.is_empty() { Vec::new() } else { vec![word.to_string()] };
+    ranked(&patterns, Ordering::Frecency).into_iter().map(|(path, _)| path).collect()
+}
+
+pub(crate) fn z_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    jump(args, stdout_writer, stderr_writer).unwrap_or_else(|err| utils::write_error("z", &err, stderr_writer))
+}
+
+fn jump(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> io::Result<i32> {
+    let mut ordering = Ordering::Frecency;
+    let mut list = false;
+    let mut echo = false;
+    let mut patterns = Vec::new();
+    for arg in args {
+        match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() && patterns.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        'l' => list = true,
+                        'e' => echo = true,
+                        'r' => ordering = Ordering::Rank,
+                        't' => ordering = Ordering::Recent,
+                        'x' => {
+                            let mut entries = load();
+                            let cwd = variables::get("PWD").unwrap_or_default();
+                            entries.retain(|e| e.path != cwd);
+                            save(&entries);
+                            return Ok(0);
+                        }
+                        _ => {
+                            let _ = writeln!(stderr_writer, "z: -{}: invalid option", flag);
+                            let _ = writeln!(stderr_writer, "z: usage: z [-elrtx] [pattern ...]");
+                            return Ok(2);
+                        }
+                    }
+                }
+            }
+            _ => patterns.push(arg.clone()),
+        }
+    }
+
+    let found = ranked(&patterns, ordering);
+    if list || patterns.is_empty() {
+        for (path, score) in found.iter().rev() {
+            writeln!(stdout_writer, "{:<10} {}", format!("{:.0}", score), path)?;
+        }
+        return Ok(0);
+    }
+    let Some((best, _)) = found.first() else {
+        return Ok(1);
+    };
+    if echo {
+        writeln!(stdout_writer, "{}", best)?;
+        return Ok(0);
+    }
+    match cd::change_directory(Path::new(best), false) {
+        Ok(_) => Ok(0),
+        Err(_) => {
+            let _ = writeln!(stderr_writer, "z: {}: No such file or directory", best);
+            Ok(1)
+        }
+    }
+}
+
+// Lists the matches and lets the user pick one by number.
+pub(crate) fn zi_cmd(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> i32 {
+    pick(args, stdout_writer, stderr_writer).unwrap_or_else(|err| utils::write_error("zi", &err, stderr_writer))
+}
+
+fn pick(args: &[String], stdout_writer: &mut dyn Write, stderr_writer: &mut dyn Write) -> io::Result<i32> {
+    let found = ranked(args, Ordering::Frecency);
+    if found.is_empty() {
+        let _ = writeln!(stderr_writer, "zi: no match found");
+        return Ok(1);
+    }
+    for (i, (path, _)) in found.iter().enumerate() {
+        writeln!(stdout_writer, "{:3}) {}", i + 1, path)?;
+    }
+    write!(stdout_writer, "zi> ")?;
+    stdout_writer.flush()?;
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).unwrap_or(0) == 0 {
+        return Ok(1);
+    }
+    let choice = match answer.trim().parse::<usize>() {
+        Ok(n) if (1..=found.len()).contains(&n) => &found[n - 1].0,
+        _ => {
+            let _ = writeln!(stderr_writer, "zi: {}: invalid selection", answer.trim());
+            return Ok(1);
+        }
+    };
+    match cd::change_directory(Path::new(choice), false) {
+        Ok(_) => Ok(0),
+        Err(_) => {
+            let _ = writeln!(stderr_writer, "zi: {}: No such file or directory", choice);
+            Ok(1)
+        }
+    }
+}
+
+pub(crate) fn parse_z_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Z { args, redirections })
+}
+
+pub(crate) fn parse_zi_cmd(args: Vec<String>, redirections: Vec<Redirection>) -> Result<Command, CommandError> {
+    Ok(Command::Zi { args, redirections })
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command as StdCommand, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::command::{exec, variables};
+
+// How long the prompt waits for a fresh status before falling back to the cached one.
+const STATUS_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GitStatus {
+    pub branch: String,
+    pub ahead: usize,
+    pub behind: usize,
+    pub staged: bool,
+    pub dirty: bool,
+    pub untracked: bool,
+}
+
+impl GitStatus {
+    // Formats the status like git-prompt.sh: `main ↑1↓2 *+%`.
+    pub fn segment(&self) -> String {
+        let mut segment = self.branch.clone();
+        if self.ahead > 0 || self.behind > 0 {
+            segment.push(' ');
+            if self.ahead > 0 {
+                segment.push_str(&format!("↑{}", self.ahead));
+            }
+            if self.behind > 0 {
+                segment.push_str(&format!("↓{}", self.behind));
+            }
+        }
+        let markers: String = [(self.dirty, '*'), (self.staged, '+'), (self.untracked, '%')]
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, marker)| *marker)
+            .collect();
+        if !markers.is_empty() {
+            segment.push(' ');
+            segment.push_str(&markers);
+        }
+        segment
+    }
+}
+
+// Walks up from `dir` to the enclosing work tree. Returns the work tree root and its git directory.
+fn find_repository(dir: &Path) -> Option<(PathBuf, PathBuf)> {
+    for root in dir.ancestors() {
+        let dot_git = root.join(".git");
+        if dot_git.is_dir() {
+            return Some((root.to_path_buf(), dot_git));
+        }
+        // Linked work trees and submodules use a `gitdir: <path>` file.
+        if let Ok(contents) = fs::read_to_string(&dot_git) {
+            let git_dir = contents.strip_prefix("gitdir:")?.trim();
+            return Some((root.to_path_buf(), root.join(git_dir)));
+        }
+    }
+    None
+}
+
+// Branch name from HEAD, or the abbreviated commit when detached.
+fn read_head(git_dir: &Path) -> Option<String> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(reference.strip_prefix("refs/heads/").unwrap_or(reference).to_string()),
+        None => Some(head.chars().take(7).collect()),
+    }
+}
+
+// Runs `git status` for the counts and markers that cannot be read cheaply from `.git`.
+fn query_status(root: &Path) -> GitStatus {
+    let mut status = GitStatus::default();
+    let mut command = StdCommand::new("git");
+    // The shell's exported variables, such as GIT_DIR, apply to git as to any command.
+    variables::export_to(&mut command);
+    let output = command
+        .arg("-C")
+        .arg(root)
+        .args(["status", "--porcelain=v2", "--branch"])
+        .env("GIT_OPTIONAL_LOCKS", "0")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    let Ok(output) = output else {
+        return status;
+    };
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some(ab) = line.strip_prefix("# branch.ab ") {
+            for count in ab.split_whitespace() {
+                match count.split_at(1) {
+                    ("+", n) => status.ahead = n.parse().unwrap_or(0),
+                    ("-", n) => status.behind = n.parse().unwrap_or(0),
+                    _ => {}
+                }
+            }
+        } else if line.starts_with("? ") {
+            status.untracked = true;
+        } else if line.starts_with("u ") {
+            status.dirty = true;
+        } else if line.starts_with("1 ") || line.starts_with("2 ") {
+            let xy = line.as_bytes().get(2..4).unwrap_or(b"..");
+            status.staged |= xy[0] != b'.';
+            status.dirty |= xy[1] != b'.';
+        }
+    }
+    status
+}
+
+// Computes git statuses on a background thread so that a slow `git status`
+// in a large repository never delays the prompt.
+pub(crate) struct GitPrompt {
+    requests: Sender<PathBuf>,
+    finished: Receiver<PathBuf>,
+    cache: Arc<RwLock<HashMap<PathBuf, GitStatus>>>,
+}
+
+impl GitPrompt {
+    pub fn spawn() -> Self {
+        let (requests, pending) = mpsc::channel::<PathBuf>();
+        let (done, finished) = mpsc::channel();
+        let cache = Arc::new(RwLock::new(HashMap::new()));
+        let cache_clone = Arc::clone(&cache);
+        thread::spawn(move || {
+            while let Ok(mut root) = pending.recv() {
+                // Only the most recent request matters.
+                while let Ok(newer) = pending.try_recv() {
+                    root = newer;
+                }
+                let status = {
+                    let _descriptors = exec::hold_descriptors();
+                    query_status(&root)
+                };
+                cache_clone.write().unwrap().insert(root.clone(), status);
+                if done.send(root).is_err() {
+                    break;
+                }
+            }
+        });
+        GitPrompt { requests, finished, cache }
+    }
+
+    /// Status of the repository containing the working directory, if any.
+    /// Waits briefly for a fresh result, otherwise uses the last known one.
+    pub fn status(&self) -> Option<GitStatus> {
+        let cwd = std::env::current_dir().ok()?;
+        let (root, git_dir) = find_repository(&cwd)?;
+        let branch = read_head(&git_dir)?;
+
+        // Forget notifications for requests that timed out earlier.
+        while self.finished.try_recv().is_ok() {}
+        let _ = self.requests.send(root.clone());
+        let deadline = Instant::now() + STATUS_TIMEOUT;
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match self.finished.recv_timeout(remaining) {
+                Ok(done) if done == root => break,
+                Ok(_) => continue,
+                Err(_) => break,
+            }
+        }
+
+        let mut status = self.cache.read().unwrap().get(&root).cloned().unwrap_or_default();
+        // HEAD is cheap to read, so the branch is always current.
+        status.branch = branch;
+        Some(status)
+    }
+}
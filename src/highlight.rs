@@ -0,0 +1,428 @@
+use crate::command::typee::KEYWORDS;
+use crate::command::{variables, BUILTINS};
+use std::collections::HashSet;
+use std::path::Path;
+
+// What a byte of the input line is rendered as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Style {
+    Plain,
+    Command,
+    Unknown,
+    String,
+    Variable,
+    Operator,
+    Comment,
+    Path,
+    MissingPath,
+    Bracket,
+}
+
+// ANSI SGR parameters for every style, e.g. "1;32".
+// Overridable through the HIGHLIGHT_COLORS variable, using the LS_COLORS-like
+// syntax `command=1;32:error=1;31:string=33`.
+struct Colors {
+    command: String,
+    unknown: String,
+    string: String,
+    variable: String,
+    operator: String,
+    comment: String,
+    path: String,
+    missing_path: String,
+    bracket: String,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            command: "1;32".to_string(),
+            unknown: "1;31".to_string(),
+            string: "33".to_string(),
+            variable: "36".to_string(),
+            operator: "35".to_string(),
+            comment: "2".to_string(),
+            path: "4".to_string(),
+            missing_path: "4;31".to_string(),
+            bracket: "1;34".to_string(),
+        }
+    }
+}
+
+impl Colors {
+    fn from_variables() -> Self {
+        let mut colors = Colors::default();
+        let Some(spec) = variables::get("HIGHLIGHT_COLORS") else {
+            return colors;
+        };
+        for entry in spec.split(':') {
+            let Some((key, value)) = entry.split_once('=') else {
+                continue;
+            };
+            let value = value.to_string();
+            match key {
+                "command" => colors.command = value,
+                "error" | "unknown" => colors.unknown = value,
+                "string" => colors.string = value,
+                "variable" => colors.variable = value,
+                "operator" | "redirect" => colors.operator = value,
+                "comment" => colors.comment = value,
+                "path" => colors.path = value,
+                "missing-path" => colors.missing_path = value,
+                "bracket" => colors.bracket = value,
+                _ => {}
+            }
+        }
+        colors
+    }
+
+    fn sgr(&self, style: Style) -> Option<&str> {
+        match style {
+            Style::Plain => None,
+            Style::Command => Some(&self.command),
+            Style::Unknown => Some(&self.unknown),
+            Style::String => Some(&self.string),
+            Style::Variable => Some(&self.variable),
+            Style::Operator => Some(&self.operator),
+            Style::Comment => Some(&self.comment),
+            Style::Path => Some(&self.path),
+            Style::MissingPath => Some(&self.missing_path),
+            Style::Bracket => Some(&self.bracket),
+        }
+    }
+}
+
+// Where the next word sits in the command line.
+#[derive(Clone, Copy, PartialEq)]
+enum WordRole {
+    Command,
+    Argument,
+    RedirectTarget,
+}
+
+struct Scan {
+    styles: Vec<Style>,
+    // Byte offsets of matching opening/closing quotes and brackets.
+    pairs: Vec<(usize, usize)>,
+}
+
+fn is_operator_char(ch: char) -> bool {
+    matches!(ch, '|' | '&' | ';' | '<' | '>' | '(' | ')')
+}
+
+fn is_known_command(word: &str, executables: &HashSet<String>) -> bool {
+    if BUILTINS.contains(&word) || executables.contains(word) {
+        return true;
+    }
+    if word.contains('/') {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            return Path::new(word)
+                .metadata()
+                .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false);
+        }
+        #[cfg(not(unix))]
+        return Path::new(word).is_file();
+    }
+    false
+}
+
+fn path_style(word: &str) -> Style {
+    let expanded = match word.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => match variables::get("HOME") {
+            Some(home) => format!("{}{}", home, rest),
+            None => word.to_string(),
+        },
+        _ => word.to_string(),
+    };
+    if Path::new(&expanded).exists() {
+        Style::Path
+    } else if word.contains('/') {
+        Style::MissingPath
+    } else {
+        Style::Plain
+    }
+}
+
+// How an operator affects the role of the following word.
+#[derive(Clone, Copy, PartialEq)]
+enum OperatorKind {
+    // `>`, `2>>`, `<` ...: the next word is a file.
+    Redirection,
+    // `2>&1`, `>&2`: complete on its own.
+    Duplication,
+    // `|`, `&&`, `;` ...: the next word is a command.
+    Control,
+}
+
+// Length in bytes and kind of the operator starting at `rest`, if any.
+fn operator_at(rest: &str) -> Option<(usize, OperatorKind)> {
+    const DUPLICATIONS: &[&str] = &["2>&1", "1>&2", ">&2", ">&1"];
+    const REDIRECTIONS: &[&str] = &["&>>", "1>>", "2>>", "&>", ">>", "1>", "2>", ">|", "<<<", "<<", "<", ">"];
+    const CONTROLS: &[&str] = &["&&", "||", ";;", "|", "&", ";", "(", ")"];
+    [
+        (DUPLICATIONS, OperatorKind::Duplication),
+        (REDIRECTIONS, OperatorKind::Redirection),
+        (CONTROLS, OperatorKind::Control),
+    ]
+    .iter()
+    .find_map(|(ops, kind)| {
+        ops.iter()
+            .find(|op| rest.starts_with(**op))
+            .map(|op| (op.len(), *kind))
+    })
+}
+
+fn scan(line: &str, executables: &HashSet<String>) -> Scan {
+    let mut styles = vec![Style::Plain; line.len()];
+    let mut pairs = Vec::new();
+    let mut brackets: Vec<usize> = Vec::new();
+    let mut role = WordRole::Command;
+    let bytes = line.as_bytes();
+    let mut i = 0;
+
+    while i < line.len() {
+        let ch = line[i..].chars().next().unwrap();
+
+        if ch.is_whitespace() {
+            i += ch.len_utf8();
+            continue;
+        }
+
+        if ch == '#' {
+            styles[i..].fill(Style::Comment);
+            break;
+        }
+
+        // A digit is only a file descriptor when directly followed by `>`.
+        let starts_operator = is_operator_char(ch)
+            || (matches!(ch, '1' | '2') && bytes.get(i + 1) == Some(&b'>'));
+        if starts_operator {
+            if let Some((len, kind)) = operator_at(&line[i..]) {
+                styles[i..i + len].fill(Style::Operator);
+                match &line[i..i + len] {
+                    "(" => brackets.push(i),
+                    ")" => {
+                        if let Some(open) = brackets.pop() {
+                            pairs.push((open, i));
+                        }
+                    }
+                    _ => {}
+                }
+                role = match kind {
+                    OperatorKind::Redirection => WordRole::RedirectTarget,
+                    OperatorKind::Duplication => role,
+                    OperatorKind::Control if &line[i..i + len] == ")" => WordRole::Argument,
+                    OperatorKind::Control => WordRole::Command,
+                };
+                i += len;
+                continue;
+            }
+        }
+
+        // --- Scan a single word ---
+        let start = i;
+        let mut text = String::new();
+        let mut overlays: Vec<(usize, usize, Style)> = Vec::new();
+        let mut quote: Option<(char, usize)> = None;
+        while i < line.len() {
+            let ch = line[i..].chars().next().unwrap();
+            let len = ch.len_utf8();
+            match quote {
+                Some(('\'', open)) => {
+                    if ch == '\'' {
+                        overlays.push((open, i + 1, Style::String));
+                        pairs.push((open, i));
+                        quote = None;
+                    } else {
+                        text.push(ch);
+                    }
+                }
+                Some((_, open)) => match ch {
+                    '"' => {
+                        overlays.push((open, i + 1, Style::String));
+                        pairs.push((open, i));
+                        quote = None;
+                    }
+                    '\\' if i + 1 < line.len() => {
+                        let next = line[i + 1..].chars().next().unwrap();
+                        text.push(next);
+                        i += 1 + next.len_utf8();
+                        continue;
+                    }
+                    '$' => {
+                        let end = variable_end(line, i);
+                        overlays.push((i, end, Style::Variable));
+                        text.push_str(&line[i..end]);
+                        i = end;
+                        continue;
+                    }
+                    _ => text.push(ch),
+                },
+                None => match ch {
+                    c if c.is_whitespace() || is_operator_char(c) => break,
+                    '\'' | '"' => quote = Some((ch, i)),
+                    '\\' if i + 1 < line.len() => {
+                        let next = line[i + 1..].chars().next().unwrap();
+                        text.push(next);
+                        i += 1 + next.len_utf8();
+                        continue;
+                    }
+                    '$' => {
+                        let end = variable_end(line, i);
+                        overlays.push((i, end, Style::Variable));
+                        text.push_str(&line[i..end]);
+                        i = end;
+                        continue;
+                    }
+                    '{' | '[' => {
+                        brackets.push(i);
+                        text.push(ch);
+                    }
+                    '}' | ']' => {
+                        if let Some(open) = brackets.pop() {
+                            pairs.push((open, i));
+                        }
+                        text.push(ch);
+                    }
+                    _ => text.push(ch),
+                },
+            }
+            i += len;
+        }
+        if let Some((_, open)) = quote {
+            // Unterminated quote: colour up to the end of the line.
+            overlays.push((open, line.len(), Style::String));
+        }
+
+        let keyword = role == WordRole::Command && KEYWORDS.contains(&text.as_str());
+        let assignment = role == WordRole::Command && variables::Assignment::parse(&text).is_some();
+        let base = match role {
+            WordRole::Command if keyword || is_known_command(&text, executables) => Style::Command,
+            WordRole::Command if assignment => Style::Plain,
+            WordRole::Command => Style::Unknown,
+            WordRole::Argument | WordRole::RedirectTarget => path_style(&text),
+        };
+        styles[start..i].fill(base);
+        // Quoted spans first so that variables inside double quotes stay visible.
+        overlays.sort_by_key(|(_, _, style)| *style != Style::String);
+        for (from, to, style) in overlays {
+            styles[from..to.min(line.len())].fill(style);
+        }
+        // Assignments, `!` and `coproc` are followed by a command.
+        role = if assignment || (keyword && matches!(text.as_str(), "!" | "coproc")) {
+            WordRole::Command
+        } else {
+            WordRole::Argument
+        };
+    }
+
+    Scan { styles, pairs }
+}
+
+// End offset of the `$NAME`, `${...}`, `$(...)` or special parameter starting at `start`.
+fn variable_end(line: &str, start: usize) -> usize {
+    let bytes = line.as_bytes();
+    let mut i = start + 1;
+    match bytes.get(i) {
+        Some(b'{') | Some(b'(') => {
+            let (open, close) = if bytes[i] == b'{' { (b'{', b'}') } else { (b'(', b')') };
+            let mut depth = 0;
+            while i < bytes.len() {
+                if bytes[i] == open {
+                    depth += 1;
+                } else if bytes[i] == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i + 1;
+                    }
+                }
+                i += 1;
+            }
+            bytes.len()
+        }
+        Some(b) if b.is_ascii_alphabetic() || *b == b'_' => {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            i
+        }
+        Some(b) if b.is_ascii_digit() || b"?$!#@*-".contains(b) => i + 1,
+        _ => i,
+    }
+}
+
+/// Returns `line` decorated with ANSI colours. The display width is unchanged.
+pub(crate) fn highlight(line: &str, pos: usize, executables: &HashSet<String>) -> String {
+    let colors = Colors::from_variables();
+    let Scan { mut styles, pairs } = scan(line, executables);
+
+    // Highlight the bracket or quote matching the one under (or just before) the cursor.
+    let cursor_candidates = [Some(pos), pos.checked_sub(1)];
+    if let Some((open, close)) = cursor_candidates.iter().flatten().find_map(|&at| {
+        pairs
+            .iter()
+            .find(|(open, close)| *open == at || *close == at)
+            .copied()
+    }) {
+        styles[open] = Style::Bracket;
+        styles[close] = Style::Bracket;
+    }
+
+    let mut out = String::with_capacity(line.len() * 2);
+    let mut current = Style::Plain;
+    for (idx, ch) in line.char_indices() {
+        let style = styles[idx];
+        if style != current {
+            if current != Style::Plain {
+                out.push_str("\x1b[0m");
+            }
+            if let Some(sgr) = colors.sgr(style) {
+                out.push_str("\x1b[");
+                out.push_str(sgr);
+                out.push('m');
+            }
+            current = style;
+        }
+        out.push(ch);
+    }
+    if current != Style::Plain {
+        out.push_str("\x1b[0m");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The style of the first character of each word of `line`.
+    fn word_styles(line: &str) -> Vec<Style> {
+        let executables = HashSet::from(["ls".to_string()]);
+        let styles = scan(line, &executables).styles;
+        line.match_indices(|c: char| !c.is_whitespace())
+            .filter(|&(at, _)| at == 0 || line[..at].ends_with(' '))
+            .map(|(at, _)| styles[at])
+            .collect()
+    }
+
+    #[test]
+    fn commands_are_checked() {
+        assert_eq!(word_styles("ls hl_test_word"), [Style::Command, Style::Plain]);
+        assert_eq!(word_styles("echo hl_test_word"), [Style::Command, Style::Plain]);
+        assert_eq!(word_styles("nosuchcommand hl_test_word"), [Style::Unknown, Style::Plain]);
+    }
+
+    #[test]
+    fn keywords_and_assignments_are_valid_in_command_position() {
+        assert_eq!(word_styles("! ls"), [Style::Command, Style::Command]);
+        assert_eq!(word_styles("coproc ls"), [Style::Command, Style::Command]);
+        assert_eq!(word_styles("case hl_test_word in")[0], Style::Command);
+        assert_eq!(word_styles("[[ -n hl_test_word ]]")[0], Style::Command);
+        assert_eq!(word_styles("a=1 b=2 ls"), [Style::Plain, Style::Plain, Style::Command]);
+        assert_eq!(word_styles("a=1"), [Style::Plain]);
+        assert_eq!(word_styles("a=1 nosuchcommand"), [Style::Plain, Style::Unknown]);
+    }
+}
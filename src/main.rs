@@ -1,25 +1,54 @@
 mod command;
+mod git;
+mod highlight;
+mod prompt;
 
-use crate::command::Command;
+use crate::command::shopt::{self, ShoptOption};
+use crate::command::state::STATE;
+use crate::command::{parser, variables, Command, BUILTINS};
+use crate::prompt::{Prompt, PromptContext};
 use rustyline::config::Configurer;
 use rustyline::completion::Completer;
 use rustyline::error::ReadlineError;
-use rustyline::highlight::Highlighter;
+use rustyline::highlight::{CmdKind, Highlighter};
 use rustyline::hint::Hinter;
-use rustyline::validate::Validator;
+use rustyline::history::{DefaultHistory, History};
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
 use rustyline::{CompletionType, Context, Editor, Helper};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::collections::HashSet;
-use std::env::split_paths;
+use std::env::{self, split_paths};
+use std::io::IsTerminal;
 use std::sync::{Arc, RwLock};
 use std::thread;
+use std::time::Instant;
 
 struct ShellHelper {
     executables_cache: Arc<RwLock<HashSet<String>>>,
+    // The prompt handed to readline, and the optional right-hand prompt (RPS1) drawn next to it.
+    prompt: RefCell<Prompt>,
+    right_prompt: RefCell<Option<Prompt>>,
+    columns: Cell<usize>,
+    // The lines already read when reading a continuation line, so that it is validated in context.
+    continued: RefCell<String>,
 }
 
 impl ShellHelper {
     fn new(executables_cache: Arc<RwLock<HashSet<String>>>) -> Self {
-        ShellHelper { executables_cache }
+        ShellHelper {
+            executables_cache,
+            prompt: RefCell::new(Prompt::default()),
+            right_prompt: RefCell::new(None),
+            columns: Cell::new(80),
+            continued: RefCell::new(String::new()),
+        }
+    }
+
+    fn set_prompt(&self, prompt: Prompt, right_prompt: Option<Prompt>, columns: usize) {
+        *self.prompt.borrow_mut() = prompt;
+        *self.right_prompt.borrow_mut() = right_prompt;
+        self.columns.set(columns);
     }
 }
 
@@ -30,8 +59,54 @@ impl Hinter for ShellHelper {
         None
     }
 }
-impl Validator for ShellHelper {}
-impl Highlighter for ShellHelper {}
+impl Validator for ShellHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        // Incomplete input is accepted here and continued with PS2 in the main loop,
+        // since rustyline cannot display a prompt in front of continuation lines.
+        let continued = self.continued.borrow();
+        let input = if continued.is_empty() {
+            ctx.input().to_string()
+        } else {
+            format!("{}\n{}", continued, ctx.input())
+        };
+        // Invalid input is only kept for editing on a terminal; otherwise rustyline
+        // would join the lines after it, and read_continuation reports it instead.
+        match parser::pending(&input) {
+            Err(message) if std::io::stdin().is_terminal() => Ok(ValidationResult::Invalid(Some(format!("  ({})", message)))),
+            _ => Ok(ValidationResult::Valid(None)),
+        }
+    }
+}
+impl Highlighter for ShellHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        let executables = self.executables_cache.read().unwrap();
+        Cow::Owned(highlight::highlight(line, pos, &executables))
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(&'s self, prompt: &'p str, _default: bool) -> Cow<'b, str> {
+        // Readline only knows the printable part of the prompt; swap in the full version.
+        let current = self.prompt.borrow();
+        if prompt != current.plain {
+            return Cow::Borrowed(prompt);
+        }
+        let mut display = current.display.clone();
+        if let Some(right) = self.right_prompt.borrow().as_ref() {
+            let width = right.plain.chars().count();
+            let used = current.plain.rsplit('\n').next().unwrap_or_default().chars().count();
+            let columns = self.columns.get();
+            if used + width < columns {
+                // Save the cursor, draw at the right edge, then restore it.
+                display.push_str(&format!("\x1b7\x1b[{}G{}\x1b8", columns - width + 1, right.display));
+            }
+        }
+        Cow::Owned(display)
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        // Command validity and bracket matching can change with every key press.
+        true
+    }
+}
 
 fn longest_common_prefix(strings: &[String]) -> String {
     if strings.is_empty() {
@@ -62,12 +137,16 @@ impl Completer for ShellHelper {
         let start = line.rfind(' ').map_or(0, |idx| idx + 1);
         let current_word = &line[start..pos];
 
+        // --- Directory candidates for `z`/`zi`, best match first ---
+        if let Some(("z" | "zi", _)) = line[..start].split_once(' ') {
+            return Ok((start, command::z::candidates(current_word)));
+        }
+
         // --- Gather Candidates from Cache ---
         let executables = self.executables_cache.read().unwrap();
         let mut candidates_set = executables.clone();
         
-        let builtins = &["echo", "exit", "type", "pwd", "cd"];
-        for builtin in builtins {
+        for builtin in BUILTINS {
             candidates_set.insert(builtin.to_string());
         }
 
@@ -95,13 +174,149 @@ impl Completer for ShellHelper {
     }
 }
 
+// Keeps reading PS2 lines while the parser reports unfinished input,
+// so that the whole command ends up as a single history entry.
+fn read_continuation(
+    rl: &mut Editor<ShellHelper, DefaultHistory>,
+    mut line: String,
+    ctx: &PromptContext,
+) -> rustyline::Result<Option<String>> {
+    loop {
+        match parser::pending(&line) {
+            Ok(None) => return Ok(Some(line)),
+            // Input that cannot be run is dropped whole rather than run line by line.
+            // As in other shells, a script (input not from a terminal) stops there.
+            Err(message) => {
+                eprintln!("{}", message);
+                if !std::io::stdin().is_terminal() {
+                    command::exit::exit_cmd(2);
+                }
+                STATE.write().unwrap().last_status = 2;
+                return Ok(None);
+            }
+            Ok(Some(_)) => {
+                let ps2 = variables::get("PS2").unwrap_or_else(|| "> ".to_string());
+                let ps2 = read_prompt(rl, &ps2, None, ctx);
+                if let Some(helper) = rl.helper() {
+                    *helper.continued.borrow_mut() = line.clone();
+                }
+                let next = rl.readline(&ps2);
+                if let Some(helper) = rl.helper() {
+                    helper.continued.borrow_mut().clear();
+                }
+                match next {
+                    Ok(next) => {
+                        line.push('\n');
+                        line.push_str(&next);
+                    }
+                    Err(ReadlineError::Interrupted) => return Ok(None),
+                    Err(ReadlineError::Eof) => {
+                        eprintln!("syntax error: unexpected end of file");
+                        return Ok(None);
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+    }
+}
+
+// Expands `template` (and the right prompt, if any) and registers it with the helper.
+// Returns the printable prompt to pass to readline.
+fn read_prompt(
+    rl: &mut Editor<ShellHelper, DefaultHistory>,
+    template: &str,
+    right_template: Option<&str>,
+    ctx: &PromptContext,
+) -> String {
+    let prompt = prompt::expand(template, ctx);
+    let right_prompt = right_template.map(|template| prompt::expand(template, ctx));
+    let columns = rl.dimensions().map_or(80, |(columns, _)| columns as usize);
+    let plain = prompt.plain.clone();
+    if let Some(helper) = rl.helper() {
+        helper.set_prompt(prompt, right_prompt, columns);
+    }
+    plain
+}
+
+// Writes the history to HISTFILE after each command: with histappend only the
+// new entries are appended, otherwise the file is rewritten with the whole history.
+fn save_history(rl: &mut Editor<ShellHelper, DefaultHistory>) {
+    let Some(file) = variables::get("HISTFILE").filter(|file| !file.is_empty()) else {
+        return;
+    };
+    let result = if shopt::enabled(ShoptOption::Histappend) {
+        rl.append_history(&file)
+    } else {
+        rl.save_history(&file)
+    };
+    if let Err(err) = result {
+        eprintln!("history: {}: {}", file, err);
+    }
+}
+
+// With checkwinsize, keeps LINES and COLUMNS in step with the terminal.
+fn check_window_size() {
+    if !shopt::enabled(ShoptOption::Checkwinsize) {
+        return;
+    }
+    // SAFETY: `size` is a valid winsize for TIOCGWINSZ to fill in.
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+        let _ = variables::set("LINES", &size.ws_row.to_string());
+        let _ = variables::set("COLUMNS", &size.ws_col.to_string());
+    }
+}
+
 fn main() {
+    variables::import_environment();
+    command::pwd::init_pwd();
+    command::special::init();
+    command::procsub::init();
+
+    // --- Non-interactive `[options] -c command` (used for command substitution) ---
+    let args: Vec<String> = env::args().collect();
+    if let Some(c) = args.iter().position(|arg| arg == "-c").filter(|&c| c + 1 < args.len()) {
+        STATE.write().unwrap().command_string = true;
+        // Arguments after the command are `$0` and the positional parameters.
+        if let Some((name, positional)) = args[c + 2..].split_first() {
+            let mut shell = command::state::STATE.write().unwrap();
+            shell.shell_name = name.clone();
+            shell.positional = positional.to_vec();
+        }
+        command::declare::inherit();
+        command::dirstack::inherit();
+        // `-O name` and `+O name` are `shopt` options, the rest those of `set`.
+        let mut options = Vec::new();
+        let mut rest = args[1..c].iter();
+        while let Some(option) = rest.next() {
+            let flag = match option.as_str() {
+                "-O" => "-s",
+                "+O" => "-u",
+                _ => {
+                    options.push(option.clone());
+                    continue;
+                }
+            };
+            let shopt_args = [flag.to_string(), rest.next().cloned().unwrap_or_default()];
+            if command::shopt::shopt_cmd(&shopt_args, &mut std::io::stdout(), &mut std::io::stderr()) != 0 {
+                std::process::exit(2);
+            }
+        }
+        if !options.is_empty() && command::set::set_cmd(&options, &mut std::io::stdout(), &mut std::io::stderr()) != 0 {
+            std::process::exit(2);
+        }
+        command::special::set_line_number(1);
+        command::exit::exit_cmd(Command::run(&args[c + 1]));
+    }
+
     // --- Setup Cache in Background ---
     let executables_cache = Arc::new(RwLock::new(HashSet::new()));
     let cache_clone = Arc::clone(&executables_cache);
     thread::spawn(move || {
+        let _descriptors = command::exec::hold_descriptors();
         let mut new_cache = HashSet::new();
-        if let Ok(path_var) = std::env::var("PATH") {
+        if let Some(path_var) = variables::get("PATH") {
             for path in split_paths(&path_var) {
                 if let Ok(entries) = std::fs::read_dir(path) {
                     for entry in entries.flatten() {
@@ -134,29 +349,71 @@ fn main() {
         *cache = new_cache;
     });
 
+    // --- Interactive shells survive ^C/^Z; foreground children get them ---
+    command::signals::ignore_interactive_signals();
+
     // --- Setup Rustyline ---
     let mut rl = Editor::new().unwrap();
     let helper = ShellHelper::new(executables_cache);
     rl.set_helper(Some(helper));
     rl.set_completion_type(CompletionType::List);
+    if let Some(file) = variables::get("HISTFILE") {
+        let _ = rl.load_history(&file);
+    }
 
     // --- Main Loop (starts immediately) ---
+    let mut last_status = 0;
+    let mut last_duration = None;
+    let mut command_number = 1;
+    // Input line the next command starts on, for `$LINENO`.
+    let mut line_number = 1;
+    let git_prompt = git::GitPrompt::spawn();
     loop {
-        let readline = rl.readline("$ ");
+        command::jobs::reap();
+        command::trap::run_pending();
+        if let Some(hook) = variables::get("PROMPT_COMMAND") {
+            Command::run(&hook);
+            // The hook must not change what `$?` reports.
+            STATE.write().unwrap().last_status = last_status;
+        }
+        let ps1 = variables::get("PS1").unwrap_or_else(|| "$ ".to_string());
+        let rps1 = variables::get("RPS1");
+        let needs_git = prompt::uses_git(&ps1) || rps1.as_deref().is_some_and(prompt::uses_git);
+        let ctx = PromptContext {
+            status: last_status,
+            duration: last_duration,
+            history_number: rl.history().len() + 1,
+            command_number,
+            jobs: command::jobs::count(),
+            git: if needs_git { git_prompt.status() } else { None },
+        };
+        let ps1 = read_prompt(&mut rl, &ps1, rps1.as_deref(), &ctx);
+
+        let readline = rl.readline(&ps1).and_then(|line| read_continuation(&mut rl, line, &ctx));
         match readline {
-            Ok(line) => {
+            Ok(None) => {
+                // Continuation was interrupted, hit end of file or found a syntax error: drop the command.
+                last_status = STATE.read().unwrap().last_status;
+            }
+            Ok(Some(line)) => {
                 rl.add_history_entry(line.as_str()).unwrap();
-                match Command::from(line.trim()) {
-                    Ok(cmd) => cmd.execute(),
-                    Err(e) => eprintln!("{}", e),
-                }
+                save_history(&mut rl);
+                let started = Instant::now();
+                command::special::set_line_number(line_number);
+                last_status = Command::run(&line);
+                line_number += line.matches('\n').count() + 1;
+                last_duration = Some(started.elapsed());
+                check_window_size();
+                command_number += 1;
             }
             Err(ReadlineError::Interrupted) => {
                 // Ctrl-C: new line, do nothing
+                last_status = 130;
+                STATE.write().unwrap().last_status = last_status;
             }
             Err(ReadlineError::Eof) => {
                 // Ctrl-D: exit gracefully
-                break;
+                command::exit::exit_cmd(last_status);
             }
             Err(err) => {
                 eprintln!("Error: {:?}", err);
@@ -0,0 +1,283 @@
+use crate::command::{expand, parser, utils, variables};
+use crate::git::GitStatus;
+use std::env;
+use std::ffi::{CStr, CString};
+use std::time::Duration;
+
+// Shell state the prompt escapes can refer to.
+pub(crate) struct PromptContext {
+    pub status: i32,
+    pub duration: Option<Duration>,
+    pub history_number: usize,
+    pub command_number: usize,
+    pub jobs: usize,
+    // Only computed when the prompt uses `\g`.
+    pub git: Option<GitStatus>,
+}
+
+// An expanded prompt. `display` is what gets printed; `plain` leaves out the
+// non-printing `\[...\]` parts and escape sequences, so that rustyline can
+// compute the cursor position from it.
+#[derive(Default)]
+pub(crate) struct Prompt {
+    pub display: String,
+    pub plain: String,
+}
+
+impl Prompt {
+    fn push(&mut self, text: &str, printing: bool) {
+        self.display.push_str(text);
+        if printing {
+            self.plain.push_str(text);
+        }
+    }
+}
+
+// Formats the current local time with strftime(3).
+fn strftime(format: &str) -> String {
+    let Ok(format) = CString::new(format) else {
+        return String::new();
+    };
+    let mut buf = [0u8; 256];
+    // SAFETY: `tm` is filled by localtime_r before use and `buf` outlives the call.
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return String::new();
+        }
+        let len = libc::strftime(buf.as_mut_ptr().cast(), buf.len(), format.as_ptr(), &tm);
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    }
+}
+
+fn user_name() -> String {
+    if let Some(user) = variables::get("USER") {
+        return user;
+    }
+    // SAFETY: getpwuid returns either null or a pointer to a valid static passwd entry.
+    unsafe {
+        let pw = libc::getpwuid(libc::geteuid());
+        if pw.is_null() {
+            return String::new();
+        }
+        CStr::from_ptr((*pw).pw_name).to_string_lossy().into_owned()
+    }
+}
+
+fn host_name() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer length is passed along, gethostname never writes past it.
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+    if ret != 0 {
+        return String::new();
+    }
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).into_owned()
+}
+
+// The working directory with $HOME abbreviated to `~`.
+fn working_directory() -> String {
+    let cwd = variables::get("PWD")
+        .or_else(|| env::current_dir().ok().map(|p| p.to_string_lossy().into_owned()))
+        .unwrap_or_default();
+    utils::abbreviate_home(&cwd)
+}
+
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    if millis < 1000 {
+        format!("{}ms", millis)
+    } else if millis < 60_000 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        let secs = duration.as_secs();
+        format!("{}m{}s", secs / 60, secs % 60)
+    }
+}
+
+// Value of a parameter referenced from a prompt.
+fn parameter(name: &str, ctx: &PromptContext) -> String {
+    match name {
+        "?" => ctx.status.to_string(),
+        "$" => std::process::id().to_string(),
+        "CMD_DURATION" => ctx.duration.map(format_duration).unwrap_or_default(),
+        _ => expand::parameter_value(name).unwrap_or_default(),
+    }
+}
+
+// Removes ANSI CSI sequences, which take no room on screen.
+fn strip_escapes(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            if chars.peek() == Some(&'[') {
+                chars.next();
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            } else {
+                chars.next();
+            }
+            continue;
+        }
+        out.push(ch);
+    }
+    out
+}
+
+// The byte of an octal escape whose digits start `digits`, and how many digits
+// it takes: up to three, stopping before one that would take it past 0o377.
+fn octal_byte(digits: &[char]) -> (u8, usize) {
+    let mut code: u32 = 0;
+    let mut len = 0;
+    for digit in digits.iter().take(3).map_while(|c| c.to_digit(8)) {
+        if code * 8 + digit > 0o377 {
+            break;
+        }
+        code = code * 8 + digit;
+        len += 1;
+    }
+    (code as u8, len)
+}
+
+// Whether the prompt needs the git status (`\g`), which costs a repository lookup.
+pub(crate) fn uses_git(template: &str) -> bool {
+    let mut chars = template.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' && chars.next() == Some('g') {
+            return true;
+        }
+    }
+    false
+}
+
+/// Expands the bash prompt escapes (`\u`, `\w`, `\$`, `\[...\]`...), parameters and
+/// command substitutions in `template`.
+pub(crate) fn expand(template: &str, ctx: &PromptContext) -> Prompt {
+    let chars: Vec<char> = template.chars().collect();
+    let mut prompt = Prompt::default();
+    let mut printing = true;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let ch = chars[i];
+        i += 1;
+        match ch {
+            '\\' if i < chars.len() => {
+                let escape = chars[i];
+                i += 1;
+                let text = match escape {
+                    'u' => user_name(),
+                    'h' => host_name().split('.').next().unwrap_or_default().to_string(),
+                    'H' => host_name(),
+                    'w' => working_directory(),
+                    'W' => {
+                        let cwd = working_directory();
+                        if cwd == "/" || cwd == "~" {
+                            cwd
+                        } else {
+                            cwd.rsplit('/').next().unwrap_or_default().to_string()
+                        }
+                    }
+                    // SAFETY: geteuid cannot fail.
+                    '$' => if unsafe { libc::geteuid() } == 0 { "#" } else { "$" }.to_string(),
+                    't' => strftime("%H:%M:%S"),
+                    'T' => strftime("%I:%M:%S"),
+                    '@' => strftime("%I:%M %p"),
+                    'A' => strftime("%H:%M"),
+                    'd' => strftime("%a %b %d"),
+                    'D' if chars.get(i) == Some(&'{') => {
+                        let end = chars[i..].iter().position(|&c| c == '}').map_or(chars.len(), |p| i + p);
+                        let format: String = chars[i + 1..end].iter().collect();
+                        i = (end + 1).min(chars.len());
+                        strftime(if format.is_empty() { "%X" } else { &format })
+                    }
+                    'j' => ctx.jobs.to_string(),
+                    // Not in bash: the git branch and status of the working directory.
+                    'g' => ctx.git.as_ref().map(GitStatus::segment).unwrap_or_default(),
+                    '!' => ctx.history_number.to_string(),
+                    '#' => ctx.command_number.to_string(),
+                    'n' => "\n".to_string(),
+                    'r' => "\r".to_string(),
+                    'a' => "\x07".to_string(),
+                    'e' => "\x1b".to_string(),
+                    's' => env!("CARGO_PKG_NAME").to_string(),
+                    'v' | 'V' => env!("CARGO_PKG_VERSION").to_string(),
+                    '\\' => "\\".to_string(),
+                    '[' => {
+                        printing = false;
+                        continue;
+                    }
+                    ']' => {
+                        printing = true;
+                        continue;
+                    }
+                    '0'..='7' => {
+                        // A run of octal escapes gives bytes, decoded together: `\342\234\223` is one character.
+                        let mut bytes = Vec::new();
+                        i -= 1;
+                        loop {
+                            let (byte, len) = octal_byte(&chars[i..]);
+                            bytes.push(byte);
+                            i += len;
+                            if chars.get(i) != Some(&'\\') || !chars.get(i + 1).is_some_and(|c| c.is_digit(8)) {
+                                break;
+                            }
+                            i += 1;
+                        }
+                        String::from_utf8_lossy(&bytes).into_owned()
+                    }
+                    other => format!("\\{}", other),
+                };
+                prompt.push(&text, printing);
+            }
+            '$' if chars.get(i) == Some(&'(') => match parser::group(&chars, i) {
+                Some((cmd, end)) => {
+                    i = end;
+                    prompt.push(&expand::command_substitution(&cmd), printing);
+                }
+                None => prompt.push("$", printing),
+            },
+            '$' => match expand::parameter_name(&chars, i) {
+                Some((name, end)) => {
+                    i = end;
+                    prompt.push(&parameter(&name, ctx), printing);
+                }
+                None => prompt.push("$", printing),
+            },
+            '`' => match parser::backquoted(&chars, i - 1) {
+                Some((cmd, end)) => {
+                    i = end;
+                    prompt.push(&expand::command_substitution(&cmd), printing);
+                }
+                None => prompt.push("`", printing),
+            },
+            other => prompt.push(&other.to_string(), printing),
+        }
+    }
+
+    prompt.plain = strip_escapes(&prompt.plain);
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(template: &str) -> String {
+        let ctx = PromptContext { status: 0, duration: None, history_number: 1, command_number: 1, jobs: 0, git: None };
+        expand(template, &ctx).plain
+    }
+
+    #[test]
+    fn octal_escapes_are_utf8_bytes() {
+        assert_eq!(plain("\\101\\102"), "AB");
+        assert_eq!(plain("\\342\\234\\223 ok"), "\u{2713} ok");
+        assert_eq!(plain("\\3420"), "\u{fffd}0");
+        assert_eq!(plain("\\0101"), "\u{8}1");
+    }
+}
@@ -0,0 +1,57 @@
+// Behaviour of `cd` and `pwd`: PWD and OLDPWD, `cd -`, CDPATH, and logical
+// versus physical paths.
+mod common;
+
+use common::{run, run_in, stderr, stdout, Scratch};
+use std::fs;
+use std::os::unix::fs::symlink;
+
+// A directory `real/sub` with a symlink `link` to `real`.
+fn tree(name: &str) -> Scratch {
+    let dir = Scratch::new(name);
+    fs::create_dir_all(dir.path().join("real/sub")).unwrap();
+    symlink(dir.path().join("real"), dir.path().join("link")).unwrap();
+    dir
+}
+
+#[test]
+fn pwd_stays_logical_through_symlinks() {
+    let dir = tree("cd-logical");
+    let output = run_in(dir.path(), "cd link/sub; echo $PWD; pwd; pwd -L; pwd -P; cd ..; pwd");
+    assert_eq!(stdout(&output), dir.fill("ROOT/link/sub\nROOT/link/sub\nROOT/link/sub\nROOT/real/sub\nROOT/link\n"));
+}
+
+#[test]
+fn cd_p_resolves_symlinks() {
+    let dir = tree("cd-physical");
+    let output = run_in(dir.path(), "cd -P link/sub; pwd; echo $PWD; cd -P ..; pwd");
+    assert_eq!(stdout(&output), dir.fill("ROOT/real/sub\nROOT/real/sub\nROOT/real\n"));
+}
+
+#[test]
+fn oldpwd_and_cd_dash() {
+    let dir = tree("cd-dash");
+    let output = run_in(dir.path(), "cd real; cd sub; echo $OLDPWD; cd -; echo $PWD $OLDPWD");
+    assert_eq!(stdout(&output), dir.fill("ROOT/real\nROOT/real\nROOT/real ROOT/real/sub\n"));
+    assert_eq!(stderr(&run("unset OLDPWD; cd -")), "cd: OLDPWD not set\n");
+}
+
+#[test]
+fn cdpath_is_searched_and_printed() {
+    let dir = tree("cd-cdpath");
+    let script = format!("CDPATH=:{}; cd sub; pwd", dir.join("real"));
+    // An entry found through CDPATH is printed; `.` (the empty entry) is tried first and is not.
+    assert_eq!(stdout(&run_in(dir.path(), &script)), dir.fill("ROOT/real/sub\nROOT/real/sub\n"));
+    // Paths starting with `/`, `.` or `..` do not use CDPATH.
+    let output = run_in(dir.path(), &format!("CDPATH={}; cd ./sub", dir.join("real")));
+    assert_eq!(stderr(&output), "cd: ./sub: No such file or directory\n");
+}
+
+#[test]
+fn home_and_errors() {
+    let dir = tree("cd-home");
+    let output = run(&format!("HOME={}; cd; pwd; cd real sub; echo $?; cd nosuch; echo $?", dir.join("real")));
+    assert_eq!(stdout(&output), format!("{}\n1\n1\n", dir.join("real")));
+    assert_eq!(stderr(&output), "cd: too many arguments\ncd: nosuch: No such file or directory\n");
+    assert_eq!(stderr(&run("unset HOME; cd")), "cd: HOME not set\n");
+}
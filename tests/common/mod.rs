@@ -0,0 +1,74 @@
+// Helpers for the behaviour tests, which run the shell binary itself: the
+// working directory, signal dispositions and descriptors belong to the process,
+// so they cannot be exercised from the unit tests.
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn shell() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_codecrafters-shell"));
+    command.env_remove("CDPATH").env_remove("HISTFILE").stdin(Stdio::null());
+    command
+}
+
+/// Runs `script` with `-c`, as a non-interactive shell.
+pub fn run(script: &str) -> Output {
+    shell().arg("-c").arg(script).output().unwrap()
+}
+
+/// Runs `script` with `-c` in `dir`.
+pub fn run_in(dir: &Path, script: &str) -> Output {
+    shell().current_dir(dir).arg("-c").arg(script).output().unwrap()
+}
+
+/// Feeds `script` to the shell's standard input, which it reads the way it
+/// reads commands at the prompt.
+pub fn run_stdin(script: &str) -> Output {
+    let mut child = shell().stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(script.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// An empty directory for one test, removed when the test is done.
+pub struct Scratch(PathBuf);
+
+impl Scratch {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("codecrafters-shell-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // Symlinks in the temporary directory itself would show up in logical paths.
+        Scratch(dir.canonicalize().unwrap())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// `text` with `ROOT` replaced by the directory, for expected output.
+    pub fn fill(&self, text: &str) -> String {
+        text.replace("ROOT", &self.0.to_string_lossy())
+    }
+
+    /// The path of `name` inside the directory, as a string for scripts.
+    pub fn join(&self, name: &str) -> String {
+        self.0.join(name).to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,29 @@
+// Behaviour of `echo`: the -n, -e and -E options, backslash escapes and the
+// xpg_echo option.
+mod common;
+
+use common::{run, stdout};
+
+#[test]
+fn n_suppresses_the_newline() {
+    assert_eq!(stdout(&run("echo -n a; echo b")), "ab\n");
+}
+
+#[test]
+fn e_interprets_escapes() {
+    assert_eq!(stdout(&run(r#"echo -e "x\ty\\z"; echo -e "\0101\x42\e[0m""#)), "x\ty\\z\nAB\x1b[0m\n");
+    // \c stops all further output, the newline included.
+    assert_eq!(stdout(&run(r#"echo -e "a\cb"; echo c"#)), "ac\n");
+    assert_eq!(stdout(&run(r#"echo -ne "p\tq""#)), "p\tq");
+    assert_eq!(stdout(&run(r#"echo -E "a\tb""#)), "a\\tb\n");
+}
+
+#[test]
+fn other_words_are_printed() {
+    assert_eq!(stdout(&run("echo -nx; echo -- a")), "-nx\n-- a\n");
+}
+
+#[test]
+fn xpg_echo_interprets_escapes_by_default() {
+    assert_eq!(stdout(&run(r#"shopt -s xpg_echo; echo "a\tb"; echo -E "a\tb""#)), "a\tb\na\\tb\n");
+}
@@ -0,0 +1,55 @@
+// Behaviour of `exec`: redirections without a command change the shell's own
+// descriptors, and a command replaces the shell.
+mod common;
+
+use common::{run, run_in, run_stdin, stderr, stdout, Scratch};
+use std::fs;
+
+#[test]
+fn descriptors_are_opened_and_inherited() {
+    let dir = Scratch::new("exec-open");
+    let output = run_in(dir.path(), "exec 3>out; echo one >&3; sh -c 'echo two >&3'");
+    assert!(output.status.success());
+    assert_eq!(fs::read_to_string(dir.path().join("out")).unwrap(), "one\ntwo\n");
+}
+
+#[test]
+fn closed_descriptors_are_bad() {
+    let dir = Scratch::new("exec-close");
+    let output = run_in(dir.path(), "exec 3>out; exec 3>&-; echo one >&3; echo $?");
+    assert_eq!(stdout(&output), "1\n");
+    assert_eq!(stderr(&output), "3: Bad file descriptor\n");
+}
+
+#[test]
+fn descriptors_keep_their_offset() {
+    let dir = Scratch::new("exec-read");
+    fs::write(dir.path().join("in"), "l1\nl2\n").unwrap();
+    assert_eq!(stdout(&run_in(dir.path(), "exec 4<in; read -u 4 a; read -u 4 b; echo $a $b")), "l1 l2\n");
+}
+
+#[test]
+fn standard_streams_can_be_saved_and_restored() {
+    let dir = Scratch::new("exec-restore");
+    let output = run_in(dir.path(), "exec 5>&1 >log 2>&1; echo logged; ls nosuch; exec >&5; echo back");
+    assert_eq!(stdout(&output), "back\n");
+    assert_eq!(stderr(&output), "");
+    let log = fs::read_to_string(dir.path().join("log")).unwrap();
+    assert!(log.starts_with("logged\nls: "), "{}", log);
+}
+
+#[test]
+fn a_command_replaces_the_shell() {
+    assert_eq!(stdout(&run("exec echo replaced; echo no")), "replaced\n");
+}
+
+#[test]
+fn a_failed_exec_exits_a_script() {
+    let output = run("exec nosuchcmd; echo no");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "exec: nosuchcmd: not found\n");
+    assert_eq!(output.status.code(), Some(127));
+    assert_eq!(run("exec /etc/passwd").status.code(), Some(126));
+    // At the prompt the shell carries on.
+    assert_eq!(stdout(&run_stdin("exec nosuchcmd\necho yes\n")), "yes\n");
+}
@@ -0,0 +1,43 @@
+// Behaviour of `wait`, `kill` and `disown` on coprocesses, the only
+// asynchronous jobs the shell starts.
+mod common;
+
+use common::{run, stderr, stdout};
+
+#[test]
+fn wait_returns_the_coproc_status() {
+    assert_eq!(stdout(&run("coproc { exit 3; }; wait $COPROC_PID; echo $?")), "3\n");
+    assert_eq!(stdout(&run("coproc { exit 4; }; wait -n; echo $?")), "4\n");
+    assert_eq!(stdout(&run("coproc sleep 5; kill $COPROC_PID; wait $COPROC_PID; echo $?")), "143\n");
+    // Without operands `wait` returns 0 whatever the jobs exited with.
+    assert_eq!(stdout(&run("coproc sleep 5; kill -s KILL %1; wait; echo $?")), "0\n");
+}
+
+#[test]
+fn wait_rejects_unknown_pids() {
+    let output = run("wait 99999; echo $?");
+    assert_eq!(stdout(&output), "127\n");
+    assert_eq!(stderr(&output), "wait: pid 99999 is not a child of this shell\n");
+}
+
+#[test]
+fn kill_lists_and_resolves_signals() {
+    assert_eq!(stdout(&run("kill -l 15; kill -l TERM; kill -l 143; type kill")), "TERM\n15\nTERM\nkill is a shell builtin\n");
+    let output = run("kill %9; echo $?");
+    assert_eq!(stdout(&output), "1\n");
+    assert_eq!(stderr(&output), "kill: %9: no such job\n");
+}
+
+#[test]
+fn disown_removes_the_job() {
+    let output = run("coproc sleep 5; p=$COPROC_PID; disown %1; kill %1; echo $?; kill $p; echo $?");
+    assert_eq!(stdout(&output), "1\n0\n");
+    assert_eq!(stderr(&output), "kill: %1: no such job\n");
+}
+
+#[test]
+fn disown_h_keeps_the_job() {
+    let output = run("coproc sleep 5; disown -h %1; kill %1; echo $?; disown %2; echo $?");
+    assert_eq!(stdout(&output), "0\n1\n");
+    assert_eq!(stderr(&output), "disown: %2: no such job\n");
+}
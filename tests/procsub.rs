@@ -0,0 +1,24 @@
+// Behaviour of process substitution: `<(…)` and `>(…)` expand to a
+// /dev/fd path connected to the command.
+mod common;
+
+use common::{run, stdout};
+
+#[test]
+fn input_substitutions_are_read_as_files() {
+    assert_eq!(stdout(&run("cat <(echo a) <(echo b)")), "a\nb\n");
+    assert_eq!(stdout(&run("diff <(echo a) <(echo b) >/dev/null; echo $?")), "1\n");
+    assert_eq!(stdout(&run(r#"wc -l < <(printf "x\ny\n")"#)), "2\n");
+}
+
+#[test]
+fn substitutions_expand_to_a_descriptor_path() {
+    let output = stdout(&run("echo <(true)"));
+    assert!(output.starts_with("/dev/fd/"), "{}", output);
+}
+
+#[test]
+fn output_substitutions_feed_the_command() {
+    // The substituted command is last, so its output is all the shell leaves behind.
+    assert_eq!(stdout(&run("echo hi > >(tr a-z A-Z)")), "HI\n");
+}
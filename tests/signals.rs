@@ -0,0 +1,43 @@
+// Behaviour of signals: the shell reading commands ignores ^C, ^\ and ^Z
+// while its children get the default dispositions, and children killed or
+// stopped by a signal are reported as bash does.
+mod common;
+
+use common::{run, run_stdin, stderr, stdout};
+use std::os::unix::process::ExitStatusExt;
+
+#[test]
+fn the_shell_ignores_interactive_signals() {
+    let output = run_stdin("kill -INT $$\nkill -QUIT $$\nkill -TSTP $$\necho alive\n");
+    assert_eq!(stdout(&output), "alive\n");
+    assert!(output.status.success());
+}
+
+#[test]
+fn children_get_the_default_dispositions() {
+    let output = run_stdin("sh -c 'kill -INT $$'; echo $?\nsh -c 'ulimit -c 0; kill -QUIT $$'; echo $?\n");
+    assert_eq!(stdout(&output), "130\n131\n");
+    // ^C only moves to a fresh line; other signals are described.
+    assert_eq!(stderr(&output), "\nQuit\n");
+}
+
+#[test]
+fn terminations_are_reported_with_128_plus_the_signal() {
+    let output = run("sh -c 'kill -TERM $$'; echo $?; sh -c 'kill -KILL $$'; echo $?");
+    assert_eq!(stdout(&output), "143\n137\n");
+    assert_eq!(stderr(&output), "Terminated\nKilled\n");
+}
+
+#[test]
+fn a_stopped_child_becomes_a_job() {
+    let output = run_stdin("sh -c 'kill -TSTP $$'; echo $?\nkill -KILL %1\n");
+    assert_eq!(stdout(&output), "148\n");
+    assert!(stderr(&output).contains("[1]+  Stopped"), "{}", stderr(&output));
+}
+
+#[test]
+fn a_non_interactive_shell_keeps_the_default_dispositions() {
+    let output = run("kill -INT $$; echo not reached");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(output.status.signal(), Some(libc::SIGINT));
+}
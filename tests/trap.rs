@@ -0,0 +1,43 @@
+// Behaviour of `trap`: EXIT, ERR and DEBUG handlers, signal handlers run
+// between commands, ignoring and resetting, and `trap -p`.
+mod common;
+
+use common::{run, run_stdin, stderr, stdout};
+
+#[test]
+fn exit_trap_runs_and_keeps_the_status() {
+    let output = run("trap 'echo bye' EXIT; echo hi; exit 3");
+    assert_eq!(stdout(&output), "hi\nbye\n");
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&run_stdin("trap 'echo bye' EXIT\necho hi\n")), "hi\nbye\n");
+}
+
+#[test]
+fn err_trap_sees_the_failing_status() {
+    let output = run("trap 'echo err $?' ERR; false; true; trap - ERR; false; echo done");
+    assert_eq!(stdout(&output), "err 1\ndone\n");
+}
+
+#[test]
+fn debug_trap_runs_before_each_command() {
+    assert_eq!(stdout(&run("trap 'echo dbg' DEBUG; echo a; trap - DEBUG; echo b")), "dbg\na\ndbg\nb\n");
+}
+
+#[test]
+fn signal_handlers_run_before_the_next_command() {
+    assert_eq!(stdout(&run("trap 'echo usr1' USR1; kill -USR1 $$; echo after")), "usr1\nafter\n");
+    assert_eq!(stdout(&run("trap '' TERM; kill $$; echo survived")), "survived\n");
+}
+
+#[test]
+fn print_lists_handlers() {
+    let output = run("trap 'echo bye' EXIT; trap 'echo int' INT; trap -p; trap -p INT; trap - EXIT; trap -p EXIT");
+    assert_eq!(stdout(&output), "trap -- 'echo bye' EXIT\ntrap -- 'echo int' SIGINT\ntrap -- 'echo int' SIGINT\n");
+}
+
+#[test]
+fn invalid_signals_are_rejected() {
+    let output = run("trap x BOGUS; echo $?");
+    assert_eq!(stdout(&output), "1\n");
+    assert_eq!(stderr(&output), "trap: BOGUS: invalid signal specification\n");
+}